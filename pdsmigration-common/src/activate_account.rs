@@ -1,14 +1,16 @@
 use crate::agent::login_helper;
-use crate::{build_agent, MigrationError};
+use crate::{build_agent, MigrationError, MigrationOptions};
 
-#[tracing::instrument]
+#[tracing::instrument(skip(options))]
 pub async fn activate_account(
     pds_host: &str,
     did: &str,
     token: &str,
+    options: &MigrationOptions,
 ) -> Result<(), MigrationError> {
-    let agent = build_agent().await?;
+    let agent = build_agent(&options.retry).await?;
     login_helper(&agent, pds_host, did, token).await?;
+    let host = agent.get_endpoint().await;
     agent
         .api
         .com
@@ -16,8 +18,6 @@ pub async fn activate_account(
         .server
         .activate_account()
         .await
-        .map_err(|error| MigrationError::Upstream {
-            message: error.to_string(),
-        })?;
+        .map_err(|error| MigrationError::from_atrium(&host, error))?;
     Ok(())
 }
@@ -1,15 +1,15 @@
 use crate::{
-    CreateAccountInput, CreateAccountInputData, CreateAccountRequest,
+    send_governed, CreateAccountInput, CreateAccountInputData, CreateAccountRequest,
     CreateAccountWithoutPDSRequest, DeactivatedAccountInput, DeactivatedAccountInputData,
-    MigrationError, CREATE_ACCOUNT_PATH,
+    MigrationAgent, MigrationError, RetryPolicy, CREATE_ACCOUNT_PATH,
 };
-use bsky_sdk::BskyAgent;
 use ipld_core::ipld::Ipld;
 
-#[tracing::instrument(skip(account_request))]
+#[tracing::instrument(skip(account_request, retry))]
 pub async fn create_account(
     pds_host: &str,
     account_request: &CreateAccountRequest,
+    retry: &RetryPolicy,
 ) -> Result<(), MigrationError> {
     let client = reqwest::Client::new();
     let request_body = serde_json::to_string(&CreateAccountInput {
@@ -41,16 +41,19 @@ pub async fn create_account(
         request_builder = request_builder.bearer_auth(token);
     }
 
-    let result = request_builder.send().await;
+    let result = match request_builder.build() {
+        Ok(request) => send_governed(&client, request, retry).await,
+        Err(e) => Err(e),
+    };
     match result {
         Ok(output) => match output.status() {
             reqwest::StatusCode::OK => {
                 tracing::info!("Successfully created account");
             }
             _ => {
-                return Err(MigrationError::Runtime {
-                    message: "Failed to create account".to_string(),
-                });
+                let error = MigrationError::from_response(pds_host, output).await;
+                tracing::error!("Failed to create account: {}", error);
+                return Err(error);
             }
         },
         Err(e) => {
@@ -97,9 +100,9 @@ pub async fn create_account_without_pds(
                 tracing::info!("Successfully created account");
             }
             _ => {
-                return Err(MigrationError::Runtime {
-                    message: "Failed to create account".to_string(),
-                });
+                let error = MigrationError::from_response(pds_host, output).await;
+                tracing::error!("Failed to create account: {}", error);
+                return Err(error);
             }
         },
         Err(e) => {
@@ -112,7 +115,8 @@ pub async fn create_account_without_pds(
 }
 
 #[tracing::instrument(skip(agent))]
-pub async fn deactivate_account(agent: &BskyAgent) -> Result<(), MigrationError> {
+pub async fn deactivate_account(agent: &MigrationAgent) -> Result<(), MigrationError> {
+    let host = agent.get_endpoint().await;
     agent
         .api
         .com
@@ -125,9 +129,64 @@ pub async fn deactivate_account(agent: &BskyAgent) -> Result<(), MigrationError>
         .await
         .map_err(|error| {
             tracing::error!("Failed to deactivate account: {:?}", error);
-            MigrationError::Runtime {
-                message: error.to_string(),
-            }
+            MigrationError::from_atrium(&host, error)
         })?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bsky_sdk::api::types::string::{Did, Handle};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn request() -> CreateAccountRequest {
+        CreateAccountRequest {
+            did: Did::new("did:plc:example123".to_string()).unwrap(),
+            email: Some("alice@example.com".to_string()),
+            handle: Handle::new("alice.example.com".to_string()).unwrap(),
+            invite_code: Some("invite-code".to_string()),
+            password: Some("hunter2".to_string()),
+            recovery_key: None,
+            verification_code: None,
+            verification_phone: None,
+            plc_op: None,
+            token: Some("service-token".to_string()),
+        }
+    }
+
+    async fn rejecting(error: &str, message: &str) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path(CREATE_ACCOUNT_PATH))
+            .respond_with(
+                ResponseTemplate::new(400)
+                    .set_body_json(serde_json::json!({ "error": error, "message": message })),
+            )
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[test]
+    fn test_create_account_keeps_upstream_error() {
+        tokio_test::block_on(async {
+            let server = rejecting("HandleNotAvailable", "Handle already taken").await;
+            let result = create_account(&server.uri(), &request(), &RetryPolicy::none()).await;
+            let Err(MigrationError::HandleUnavailable { upstream }) = result else {
+                panic!("expected HandleUnavailable, got {result:?}");
+            };
+            assert_eq!(upstream.host, server.uri());
+            assert_eq!(upstream.status, 400);
+            assert_eq!(upstream.message, "Handle already taken");
+
+            let server = rejecting("InvalidInviteCode", "Provided invite code not available").await;
+            let result = create_account(&server.uri(), &request(), &RetryPolicy::none()).await;
+            assert!(matches!(
+                result,
+                Err(MigrationError::InvalidInviteCode { .. })
+            ));
+        });
+    }
+}
@@ -1,15 +1,20 @@
 use crate::{
-    CreateSessionOutputData, GetServiceAuthParams, GetServiceAuthParamsData, MigrationError,
+    jwt_claims, CreateSessionOutputData, GetServiceAuthParams, GetServiceAuthParamsData,
+    GovernedClient, MigrationAgent, MigrationError, RetryPolicy,
 };
+use bsky_sdk::agent::config::Config;
 use bsky_sdk::api::agent::atp_agent::AtpSession;
 use bsky_sdk::api::agent::Configure;
 use bsky_sdk::api::types::string::{Did, Handle, Nsid};
-use bsky_sdk::api::xrpc::Error;
 use bsky_sdk::BskyAgent;
 use ipld_core::ipld::Ipld;
 
-pub async fn build_agent() -> Result<BskyAgent, MigrationError> {
+pub async fn build_agent(retry: &RetryPolicy) -> Result<MigrationAgent, MigrationError> {
     BskyAgent::builder()
+        .client(GovernedClient::new(
+            Config::default().endpoint,
+            retry.clone(),
+        ))
         .build()
         .await
         .map_err(|error| MigrationError::Upstream {
@@ -19,58 +24,80 @@ pub async fn build_agent() -> Result<BskyAgent, MigrationError> {
 
 #[tracing::instrument(skip(agent))]
 pub async fn login_helper(
-    agent: &BskyAgent,
+    agent: &MigrationAgent,
     pds_host: &str,
     did: &str,
     token: &str,
 ) -> Result<AtpSession, MigrationError> {
+    resume_session(agent, pds_host, did, token, None).await
+}
+
+/// Resumes the session for `did` on `pds_host` from tokens issued elsewhere.
+///
+/// Handle, email and account status come from the PDS's own `getSession` answer. Without
+/// a `refresh_token` the session ends when the access token expires; with one,
+/// [`fresh_session`](crate::fresh_session) keeps it alive.
+#[tracing::instrument(skip(agent, access_token, refresh_token))]
+pub async fn resume_session(
+    agent: &MigrationAgent,
+    pds_host: &str,
+    did: &str,
+    access_token: &str,
+    refresh_token: Option<&str>,
+) -> Result<AtpSession, MigrationError> {
+    let did = parse_did(did)?;
+    // The agent panics if getSession answers for another account, so catch a token
+    // issued to someone else before resuming with it.
+    let subject =
+        jwt_claims(access_token).and_then(|claims| claims.get("sub")?.as_str().map(str::to_string));
+    if subject.is_some_and(|subject| subject != did.as_str()) {
+        return Err(MigrationError::Authentication {
+            message: format!("Token was not issued to {}", did.as_str()),
+        });
+    }
+
     agent.configure_endpoint(pds_host.to_string());
-    match agent
+    agent
         .resume_session(AtpSession {
             data: CreateSessionOutputData {
-                access_jwt: token.to_string(),
-                active: Some(true),
-                did: Did::new(did.to_string()).unwrap(),
+                access_jwt: access_token.to_string(),
+                active: None,
+                did,
                 did_doc: None,
                 email: None,
                 email_auth_factor: None,
                 email_confirmed: None,
-                handle: Handle::new("anothermigration.bsky.social".to_string()).unwrap(),
-                refresh_jwt: "".to_string(),
+                // Replaced by the account's real handle once getSession answers.
+                handle: Handle::new("handle.invalid".to_string()).unwrap(),
+                refresh_jwt: refresh_token.unwrap_or_default().to_string(),
                 status: None,
             },
             extra_data: Ipld::Null,
         })
         .await
-    {
-        Ok(_) => Ok(agent.get_session().await.unwrap()),
-        Err(error) => {
+        .map_err(|error| {
             tracing::error!("Error while logging in: {}", error);
-            match error {
-                Error::Authentication(_) => Err(MigrationError::Authentication {
-                    message: error.to_string(),
-                }),
-                Error::XrpcResponse(ref error_response) => {
-                    if error_response.status.as_u16() == 401 {
-                        Err(MigrationError::Authentication {
-                            message: error.to_string(),
-                        })
-                    } else {
-                        Err(MigrationError::Upstream {
-                            message: error.to_string(),
-                        })
-                    }
-                }
-                _ => Err(MigrationError::Upstream {
-                    message: error.to_string(),
-                }),
-            }
-        }
-    }
+            MigrationError::from_atrium(pds_host, error)
+        })?;
+    // Resuming follows the DID document, which still points at the old PDS mid-migration.
+    agent.configure_endpoint(pds_host.to_string());
+    agent
+        .get_session()
+        .await
+        .ok_or_else(|| MigrationError::Authentication {
+            message: "Session was not stored".to_string(),
+        })
+}
+
+pub fn parse_did(did: &str) -> Result<Did, MigrationError> {
+    Did::new(did.to_string()).map_err(|_error| MigrationError::Validation {
+        field: "did".to_string(),
+    })
 }
 
 #[tracing::instrument(skip(agent))]
-pub async fn get_service_auth(agent: &BskyAgent, aud: &str) -> Result<String, MigrationError> {
+pub async fn get_service_auth(agent: &MigrationAgent, aud: &str) -> Result<String, MigrationError> {
+    let host = agent.get_endpoint().await;
     let result = agent
         .api
         .com
@@ -87,8 +114,41 @@ pub async fn get_service_auth(agent: &BskyAgent, aud: &str) -> Result<String, Mi
             extra_data: Ipld::Null,
         })
         .await
-        .map_err(|error| MigrationError::Runtime {
-            message: error.to_string(),
-        })?;
+        .map_err(|error| MigrationError::from_atrium(&host, error))?;
     Ok(result.token.clone())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resume_session_rejects_invalid_did() {
+        tokio_test::block_on(async {
+            let agent = build_agent(&RetryPolicy::none()).await.unwrap();
+            let result =
+                login_helper(&agent, "https://pds.example.com", "not a did", "token").await;
+            assert!(matches!(result, Err(MigrationError::Validation { field }) if field == "did"));
+        });
+    }
+
+    #[test]
+    fn test_resume_session_rejects_token_for_another_account() {
+        tokio_test::block_on(async {
+            let agent = build_agent(&RetryPolicy::none()).await.unwrap();
+            let claims = multibase::encode(
+                multibase::Base::Base64Url,
+                br#"{"sub":"did:plc:someoneelse"}"#,
+            );
+            let token = format!("eyJhbGciOiJFUzI1NksifQ.{}.c2ln", &claims[1..]);
+            let result = login_helper(
+                &agent,
+                "https://pds.example.com",
+                "did:plc:example123",
+                &token,
+            )
+            .await;
+            assert!(matches!(result, Err(MigrationError::Authentication { .. })));
+        });
+    }
+}
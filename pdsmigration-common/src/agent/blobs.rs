@@ -1,18 +1,20 @@
 use crate::{
-    GetBlobParams, GetBlobParamsData, GetBlobRequest, ListBlobsParams, ListBlobsParamsData,
-    ListMissingBlobsParams, ListMissingBlobsParamsData, MigrationError,
+    send_governed, GetBlobParams, GetBlobParamsData, GetBlobRequest, ListBlobsParams,
+    ListBlobsParamsData, ListMissingBlobsParams, ListMissingBlobsParamsData, MigrationAgent,
+    MigrationError, RetryPolicy,
 };
 use bsky_sdk::api::com::atproto::repo::list_missing_blobs::RecordBlob;
 use bsky_sdk::api::types::string::{Cid, Did};
-use bsky_sdk::BskyAgent;
+use bsky_sdk::api::types::{BlobRef, TypedBlobRef};
 use ipld_core::ipld::Ipld;
 
 #[tracing::instrument(skip(agent))]
-pub async fn list_all_blobs(agent: &BskyAgent) -> Result<Vec<Cid>, MigrationError> {
+pub async fn list_all_blobs(agent: &MigrationAgent) -> Result<Vec<Cid>, MigrationError> {
     let mut result = vec![];
     let mut cursor = None;
     let mut length = None;
     let did = agent.did().await.clone().unwrap();
+    let host = agent.get_endpoint().await;
     while length.is_none() || length.unwrap() >= 500 {
         let output = agent
             .api
@@ -39,9 +41,7 @@ pub async fn list_all_blobs(agent: &BskyAgent) -> Result<Vec<Cid>, MigrationErro
             }
             Err(e) => {
                 tracing::error!("{:?}", e);
-                return Err(MigrationError::Upstream {
-                    message: e.to_string(),
-                });
+                return Err(MigrationError::from_atrium(&host, e));
             }
         }
     }
@@ -49,10 +49,11 @@ pub async fn list_all_blobs(agent: &BskyAgent) -> Result<Vec<Cid>, MigrationErro
 }
 
 #[tracing::instrument(skip(agent))]
-pub async fn missing_blobs(agent: &BskyAgent) -> Result<Vec<RecordBlob>, MigrationError> {
+pub async fn missing_blobs(agent: &MigrationAgent) -> Result<Vec<RecordBlob>, MigrationError> {
     let mut result: Vec<RecordBlob> = vec![];
     let mut length = None;
     let mut cursor = None;
+    let host = agent.get_endpoint().await;
     while length.is_none() || length.unwrap() >= 500 {
         let output = agent
             .api
@@ -67,9 +68,7 @@ pub async fn missing_blobs(agent: &BskyAgent) -> Result<Vec<RecordBlob>, Migrati
                 extra_data: Ipld::Null,
             })
             .await
-            .map_err(|error| MigrationError::Upstream {
-                message: error.to_string(),
-            })?;
+            .map_err(|error| MigrationError::from_atrium(&host, error))?;
         length = Some(output.blobs.len());
         let mut temp = output.blobs.clone();
         result.append(temp.as_mut());
@@ -79,7 +78,7 @@ pub async fn missing_blobs(agent: &BskyAgent) -> Result<Vec<RecordBlob>, Migrati
 }
 
 #[tracing::instrument(skip(agent))]
-pub async fn get_blob(agent: &BskyAgent, cid: Cid, did: Did) -> Result<Vec<u8>, ()> {
+pub async fn get_blob(agent: &MigrationAgent, cid: Cid, did: Did) -> Result<Vec<u8>, ()> {
     let result = agent
         .api
         .com
@@ -106,26 +105,116 @@ pub async fn get_blob(agent: &BskyAgent, cid: Cid, did: Did) -> Result<Vec<u8>,
 }
 
 #[tracing::instrument(skip(agent))]
-pub async fn upload_blob(agent: &BskyAgent, input: Vec<u8>) -> Result<(), MigrationError> {
-    agent
+pub async fn upload_blob(agent: &MigrationAgent, input: Vec<u8>) -> Result<String, MigrationError> {
+    let host = agent.get_endpoint().await;
+    let output = agent
         .api
         .com
         .atproto
         .repo
         .upload_blob(input)
         .await
-        .map_err(|error| MigrationError::Runtime {
-            message: error.to_string(),
-        })?;
-    Ok(())
+        .map_err(|error| MigrationError::from_atrium(&host, error))?;
+    Ok(blob_ref_cid(&output.blob))
 }
 
-#[tracing::instrument]
+#[tracing::instrument(skip(retry))]
 pub async fn download_blob(
     pds_host: &str,
     request: &GetBlobRequest,
+    retry: &RetryPolicy,
 ) -> Result<impl futures_core::Stream<Item = Result<bytes::Bytes, reqwest::Error>>, MigrationError>
 {
+    let output = get_blob_response(pds_host, request, retry).await?;
+    Ok(output.bytes_stream())
+}
+
+/// Like [`download_blob`], but also returns the blob's MIME type and length as reported by
+/// the PDS.
+#[tracing::instrument(skip(retry))]
+pub async fn download_blob_with_mime_type(
+    pds_host: &str,
+    request: &GetBlobRequest,
+    retry: &RetryPolicy,
+) -> Result<
+    (
+        String,
+        Option<u64>,
+        impl futures_core::Stream<Item = Result<bytes::Bytes, reqwest::Error>>,
+    ),
+    MigrationError,
+> {
+    let output = get_blob_response(pds_host, request, retry).await?;
+    let mime_type = output
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+    Ok((mime_type, output.content_length(), output.bytes_stream()))
+}
+
+/// Sends a blob body to `uploadBlob` without buffering it, returning the CID the PDS assigned.
+/// `content_length` is sent when known, since some PDSes refuse uploads without it.
+#[tracing::instrument(skip(token, body, retry))]
+pub async fn upload_blob_stream(
+    pds_host: &str,
+    token: &str,
+    mime_type: &str,
+    content_length: Option<u64>,
+    body: reqwest::Body,
+    retry: &RetryPolicy,
+) -> Result<String, MigrationError> {
+    tracing::debug!("Uploading blob");
+    let client = reqwest::Client::new();
+    let url = format!("{pds_host}/xrpc/com.atproto.repo.uploadBlob");
+    let mut request = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, mime_type)
+        .bearer_auth(token);
+    if let Some(content_length) = content_length {
+        request = request.header(reqwest::header::CONTENT_LENGTH, content_length);
+    }
+    let request = request.body(body).build();
+    let output = match request {
+        Ok(request) => send_governed(&client, request, retry).await,
+        Err(e) => Err(e),
+    }
+    .map_err(|e| {
+        tracing::error!("Unexpected Error uploading blob: {:?}", e);
+        MigrationError::Runtime {
+            message: "Unexpected Error uploading blob".to_string(),
+        }
+    })?;
+    if output.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        tracing::error!("Ratelimit reached");
+        return Err(MigrationError::RateLimitReached);
+    }
+    if !output.status().is_success() {
+        tracing::error!("Error uploading blob: {:?}", output);
+        return Err(MigrationError::from_response(pds_host, output).await);
+    }
+    let output = output
+        .json::<bsky_sdk::api::com::atproto::repo::upload_blob::OutputData>()
+        .await
+        .map_err(|e| MigrationError::Upstream {
+            message: e.to_string(),
+        })?;
+    Ok(blob_ref_cid(&output.blob))
+}
+
+fn blob_ref_cid(blob: &BlobRef) -> String {
+    match blob {
+        BlobRef::Typed(TypedBlobRef::Blob(blob)) => blob.r#ref.0.to_string(),
+        BlobRef::Untyped(blob) => blob.cid.clone(),
+    }
+}
+
+async fn get_blob_response(
+    pds_host: &str,
+    request: &GetBlobRequest,
+    retry: &RetryPolicy,
+) -> Result<reqwest::Response, MigrationError> {
     tracing::debug!("Downloading blob");
     let client = reqwest::Client::new();
     let url = format!("{pds_host}/xrpc/com.atproto.sync.getBlob");
@@ -137,41 +226,26 @@ pub async fn download_blob(
         ])
         .header("Content-Type", "application/json")
         .bearer_auth(request.token.clone())
-        .send()
-        .await;
+        .build();
+    let result = match result {
+        Ok(request) => send_governed(&client, request, retry).await,
+        Err(e) => Err(e),
+    };
     match result {
-        Ok(output) => {
-            let ratelimit_remaining = output
-                .headers()
-                .get("ratelimit-remaining")
-                .map(|v| v.to_str().unwrap_or("1000"))
-                .unwrap_or("1000")
-                .parse::<i32>()
-                .unwrap_or(1000);
-            if ratelimit_remaining < 100 {
+        Ok(output) => match output.status() {
+            reqwest::StatusCode::OK => {
+                tracing::info!("Successfully downloaded blob");
+                Ok(output)
+            }
+            reqwest::StatusCode::TOO_MANY_REQUESTS => {
                 tracing::error!("Ratelimit reached");
-                return Err(MigrationError::RateLimitReached);
+                Err(MigrationError::RateLimitReached)
             }
-
-            match output.status() {
-                reqwest::StatusCode::OK => {
-                    tracing::info!("Successfully downloaded blob");
-                    Ok(output.bytes_stream())
-                }
-                reqwest::StatusCode::BAD_REQUEST => {
-                    tracing::error!("BadRequest Error downloading blob: {:?}", output);
-                    Err(MigrationError::Upstream {
-                        message: "BadRequest downloading blob".to_string(),
-                    })
-                }
-                _ => {
-                    tracing::error!("Runtime Error downloading blob: {:?}", output);
-                    Err(MigrationError::Upstream {
-                        message: "Runtime Error downloading blob".to_string(),
-                    })
-                }
+            _ => {
+                tracing::error!("Error downloading blob: {:?}", output);
+                Err(MigrationError::from_response(pds_host, output).await)
             }
-        }
+        },
         Err(e) => {
             tracing::error!("Unexpected Error downloading blob: {:?}", e);
             Err(MigrationError::Runtime {
@@ -1,17 +1,17 @@
 use crate::agent::types::{GetRecommendedResponse, RecommendedDidOutputData};
 use crate::{
-    MigrationError, SignPlcOperationInput, SubmitPlcOperationInput, SubmitPlcOperationInputData,
-    GET_RECOMMENDED_DID_CREDENTIALS_PATH,
+    MigrationAgent, MigrationError, SignPlcOperationInput, SubmitPlcOperationInput,
+    SubmitPlcOperationInputData, GET_RECOMMENDED_DID_CREDENTIALS_PATH,
 };
 use bsky_sdk::api::com::atproto::identity::sign_plc_operation::InputData;
 use bsky_sdk::api::types::Unknown;
-use bsky_sdk::BskyAgent;
 use ipld_core::ipld::Ipld;
 
 #[tracing::instrument(skip(agent))]
 pub async fn recommended_plc(
-    agent: &BskyAgent,
+    agent: &MigrationAgent,
 ) -> Result<RecommendedDidOutputData, MigrationError> {
+    let host = agent.get_endpoint().await;
     let result = agent
         .api
         .com
@@ -21,18 +21,17 @@ pub async fn recommended_plc(
         .await
         .map_err(|error| {
             tracing::error!("Failed to get recommended did: {:?}", error);
-            MigrationError::Runtime {
-                message: error.to_string(),
-            }
+            MigrationError::from_atrium(&host, error)
         })?;
     Ok(result.data)
 }
 
 #[tracing::instrument(skip(agent))]
 pub async fn sign_plc(
-    agent: &BskyAgent,
+    agent: &MigrationAgent,
     plc_input_data: InputData,
 ) -> Result<Unknown, MigrationError> {
+    let host = agent.get_endpoint().await;
     let result = agent
         .api
         .com
@@ -47,15 +46,14 @@ pub async fn sign_plc(
         Ok(output) => Ok(output.operation.clone()),
         Err(e) => {
             tracing::error!("Failed to sign plc: {:?}", e);
-            Err(MigrationError::Runtime {
-                message: e.to_string(),
-            })
+            Err(MigrationError::from_atrium(&host, e))
         }
     }
 }
 
 #[tracing::instrument(skip(agent))]
-pub async fn submit_plc(agent: &BskyAgent, signed_plc: Unknown) -> Result<(), MigrationError> {
+pub async fn submit_plc(agent: &MigrationAgent, signed_plc: Unknown) -> Result<(), MigrationError> {
+    let host = agent.get_endpoint().await;
     let result = agent
         .api
         .com
@@ -72,15 +70,14 @@ pub async fn submit_plc(agent: &BskyAgent, signed_plc: Unknown) -> Result<(), Mi
         Ok(res) => Ok(res),
         Err(e) => {
             tracing::error!("Failed to submit plc: {:?}", e);
-            Err(MigrationError::Runtime {
-                message: e.to_string(),
-            })
+            Err(MigrationError::from_atrium(&host, e))
         }
     }
 }
 
 #[tracing::instrument(skip(agent))]
-pub async fn request_token(agent: &BskyAgent) -> Result<(), MigrationError> {
+pub async fn request_token(agent: &MigrationAgent) -> Result<(), MigrationError> {
+    let host = agent.get_endpoint().await;
     let result = agent
         .api
         .com
@@ -92,9 +89,7 @@ pub async fn request_token(agent: &BskyAgent) -> Result<(), MigrationError> {
         Ok(_) => Ok(()),
         Err(e) => {
             tracing::error!("Failed to request token: {:?}", e);
-            Err(MigrationError::Runtime {
-                message: e.to_string(),
-            })
+            Err(MigrationError::from_atrium(&host, e))
         }
     }
 }
@@ -126,9 +121,7 @@ pub async fn get_recommended(
             }
             _ => {
                 tracing::error!("Error fetching recommended account: {:?}", output);
-                Err(MigrationError::Upstream {
-                    message: "Error fetching recommended account".to_string(),
-                })
+                Err(MigrationError::from_response(pds_host, output).await)
             }
         },
         Err(e) => {
@@ -4,6 +4,7 @@ mod blobs;
 mod identity;
 mod preferences;
 mod repo;
+mod session;
 mod types;
 
 pub use account::*;
@@ -12,4 +13,5 @@ pub use blobs::*;
 pub use identity::*;
 pub use preferences::*;
 pub use repo::*;
+pub use session::*;
 pub use types::*;
@@ -1,11 +1,11 @@
-use crate::MigrationError;
+use crate::{MigrationAgent, MigrationError};
 use bsky_sdk::api::app::bsky::actor::defs::Preferences;
-use bsky_sdk::BskyAgent;
 use ipld_core::ipld::Ipld;
 
 #[tracing::instrument(skip(agent))]
-pub async fn export_preferences(agent: &BskyAgent) -> Result<Preferences, MigrationError> {
+pub async fn export_preferences(agent: &MigrationAgent) -> Result<Preferences, MigrationError> {
     use bsky_sdk::api::app::bsky::actor::get_preferences::{Parameters, ParametersData};
+    let host = agent.get_endpoint().await;
     let result = agent
         .api
         .app
@@ -18,19 +18,18 @@ pub async fn export_preferences(agent: &BskyAgent) -> Result<Preferences, Migrat
         .await
         .map_err(|error| {
             tracing::error!("Failed to export preferences: {:?}", error);
-            MigrationError::Runtime {
-                message: error.to_string(),
-            }
+            MigrationError::from_atrium(&host, error)
         })?;
     Ok(result.preferences.clone())
 }
 
 #[tracing::instrument(skip(agent))]
 pub async fn import_preferences(
-    agent: &BskyAgent,
+    agent: &MigrationAgent,
     preferences: Preferences,
 ) -> Result<(), MigrationError> {
     use bsky_sdk::api::app::bsky::actor::put_preferences::{Input, InputData};
+    let host = agent.get_endpoint().await;
     agent
         .api
         .app
@@ -43,9 +42,7 @@ pub async fn import_preferences(
         .await
         .map_err(|error| {
             tracing::error!("Failed to import preferences: {:?}", error);
-            MigrationError::Runtime {
-                message: error.to_string(),
-            }
+            MigrationError::from_atrium(&host, error)
         })?;
     Ok(())
 }
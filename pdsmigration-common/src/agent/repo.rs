@@ -1,52 +1,49 @@
-use crate::{GetRepoRequest, MigrationError};
+use crate::{
+    repo_key, send_governed, GetRepoRequest, MigrationAgent, MigrationError, RetryPolicy,
+    StagingStore,
+};
 use bsky_sdk::api::types::string::Did;
-use bsky_sdk::BskyAgent;
 use ipld_core::ipld::Ipld;
 
-#[tracing::instrument]
+#[tracing::instrument(skip(retry))]
 pub async fn download_repo(
     pds_host: &str,
     request: &GetRepoRequest,
+    retry: &RetryPolicy,
 ) -> Result<impl futures_core::Stream<Item = Result<bytes::Bytes, reqwest::Error>>, MigrationError>
 {
     let client = reqwest::Client::new();
 
     let url = format!("{pds_host}/xrpc/com.atproto.sync.getRepo");
+    let mut query = vec![("did", request.did.as_str().to_string())];
+    if let Some(since) = &request.since {
+        query.push(("since", since.clone()));
+    }
     let result = client
         .get(url)
-        .query(&[("did", request.did.as_str().to_string())])
+        .query(&query)
         .header("Content-Type", "application/json")
         .bearer_auth(request.token.clone())
-        .send()
-        .await;
+        .build();
+    let result = match result {
+        Ok(request) => send_governed(&client, request, retry).await,
+        Err(e) => Err(e),
+    };
     match result {
-        Ok(output) => {
-            let ratelimit_remaining = match output.headers().get("ratelimit-remaining") {
-                None => 1000,
-                Some(rate_limit_remaining) => rate_limit_remaining
-                    .to_str()
-                    .unwrap_or("1000")
-                    .parse::<i32>()
-                    .unwrap_or(1000),
-            };
-            if ratelimit_remaining < 100 {
+        Ok(output) => match output.status() {
+            reqwest::StatusCode::OK => {
+                tracing::info!("Started downloading Repo");
+                Ok(output.bytes_stream())
+            }
+            reqwest::StatusCode::TOO_MANY_REQUESTS => {
                 tracing::error!("Ratelimit reached");
-                return Err(MigrationError::RateLimitReached);
+                Err(MigrationError::RateLimitReached)
             }
-
-            match output.status() {
-                reqwest::StatusCode::OK => {
-                    tracing::info!("Started downloading Repo");
-                    Ok(output.bytes_stream())
-                }
-                _ => {
-                    tracing::error!("Runtime Error downloading Repo: {:?}", output);
-                    Err(MigrationError::Upstream {
-                        message: "Runtime Error downloading Repo".to_string(),
-                    })
-                }
+            _ => {
+                tracing::error!("Runtime Error downloading Repo: {:?}", output);
+                Err(MigrationError::from_response(pds_host, output).await)
             }
-        }
+        },
         Err(e) => {
             tracing::error!("Unexpected Error downloading Repo: {:?}", e);
             Err(MigrationError::Runtime {
@@ -56,27 +53,31 @@ pub async fn download_repo(
     }
 }
 
-#[tracing::instrument(skip(agent))]
-pub async fn account_import(agent: &BskyAgent, filepath: &str) -> Result<(), MigrationError> {
+#[tracing::instrument(skip(agent, repo))]
+pub async fn account_import(agent: &MigrationAgent, repo: Vec<u8>) -> Result<(), MigrationError> {
+    let host = agent.get_endpoint().await;
     agent
         .api
         .com
         .atproto
         .repo
-        .import_repo(tokio::fs::read(filepath).await.unwrap())
+        .import_repo(repo)
         .await
         .map_err(|error| {
             tracing::error!("Failed to import account: {:?}", error);
-            MigrationError::Runtime {
-                message: error.to_string(),
-            }
+            MigrationError::from_atrium(&host, error)
         })?;
     Ok(())
 }
 
-#[tracing::instrument(skip(agent))]
-pub async fn account_export(agent: &BskyAgent, did: &Did) -> Result<(), MigrationError> {
+#[tracing::instrument(skip(agent, store))]
+pub async fn account_export(
+    agent: &MigrationAgent,
+    did: &Did,
+    store: &dyn StagingStore,
+) -> Result<(), MigrationError> {
     use bsky_sdk::api::com::atproto::sync::get_repo::{Parameters, ParametersData};
+    let host = agent.get_endpoint().await;
     let result = agent
         .api
         .com
@@ -92,22 +93,18 @@ pub async fn account_export(agent: &BskyAgent, did: &Did) -> Result<(), Migratio
         .await;
     match result {
         Ok(output) => {
-            tokio::fs::write(did.as_str().to_string().replace(":", "-") + ".car", output)
+            store
+                .put(&repo_key(did.as_str()), output)
                 .await
-                .map_err(|error| {
+                .inspect_err(|error| {
                     tracing::error!("Failed write repo bytes to file: {:?}", error);
-                    MigrationError::Runtime {
-                        message: error.to_string(),
-                    }
                 })?;
             tracing::info!("write success");
             Ok(())
         }
         Err(e) => {
             tracing::error!("Failed to export account: {:?}", e);
-            Err(MigrationError::Upstream {
-                message: e.to_string(),
-            })
+            Err(MigrationError::from_atrium(&host, e))
         }
     }
 }
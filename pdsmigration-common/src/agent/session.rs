@@ -0,0 +1,193 @@
+use crate::{
+    fresh_oauth_session, oauth_session_for, CreateSessionOutputData, MigrationAgent,
+    MigrationError, MigrationOptions,
+};
+use bsky_sdk::api::agent::atp_agent::AtpSession;
+use bsky_sdk::api::agent::Configure;
+use ipld_core::ipld::Ipld;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+/// Access tokens with less than this left are refreshed before they are used.
+pub(crate) const REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+/// One lock per account on a host, so parallel transfers never spend the same refresh
+/// token twice while other sessions refresh undisturbed.
+static REFRESH_LOCKS: LazyLock<std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>>> =
+    LazyLock::new(Default::default);
+
+fn refresh_lock(endpoint: &str, did: &str) -> Arc<Mutex<()>> {
+    let mut locks = REFRESH_LOCKS.lock().unwrap();
+    // Locks nobody holds or waits for are dropped, so the map only keeps live sessions
+    locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+    locks
+        .entry(format!("{did} {endpoint}"))
+        .or_default()
+        .clone()
+}
+
+/// Tokens issued by a session refresh, for callers that keep their own copy.
+#[derive(Clone)]
+pub struct RefreshedSession {
+    pub pds_host: String,
+    pub did: String,
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+impl fmt::Debug for RefreshedSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RefreshedSession")
+            .field("pds_host", &self.pds_host)
+            .field("did", &self.did)
+            .field("access_token", &"[REDACTED]")
+            .field("refresh_token", &"[REDACTED]")
+            .finish()
+    }
+}
+
+/// Called with the new tokens every time a session is refreshed.
+pub type SessionRefreshCallback = Arc<dyn Fn(&RefreshedSession) + Send + Sync>;
+
+/// The claims of a JWT, without checking its signature.
+pub(crate) fn jwt_claims(token: &str) -> Option<serde_json::Value> {
+    let payload = token.split('.').nth(1)?;
+    let (_, bytes) = multibase::decode(format!("u{payload}")).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// When the token's `exp` claim says it expires, if it has one.
+pub fn jwt_expiry(token: &str) -> Option<SystemTime> {
+    let exp = jwt_claims(token)?.get("exp")?.as_u64()?;
+    Some(UNIX_EPOCH + Duration::from_secs(exp))
+}
+
+fn expires_soon(token: &str, now: SystemTime) -> bool {
+    jwt_expiry(token).is_some_and(|expiry| expiry <= now + REFRESH_MARGIN)
+}
+
+async fn current_session(agent: &MigrationAgent) -> Result<AtpSession, MigrationError> {
+    agent
+        .get_session()
+        .await
+        .ok_or_else(|| MigrationError::Authentication {
+            message: "No active session".to_string(),
+        })
+}
+
+/// Returns the agent's session, refreshing it first when the access token is about to
+/// expire and the session has a refresh token. Agents from
+/// [`build_oauth_agent`](crate::build_oauth_agent) are refreshed at their authorization server.
+///
+/// Raw requests that take a bearer token should fetch it from here right before they are
+/// sent. New tokens are passed to [`MigrationOptions::on_session_refresh`].
+#[tracing::instrument(skip(agent, options))]
+pub async fn fresh_session(
+    agent: &MigrationAgent,
+    options: &MigrationOptions,
+) -> Result<AtpSession, MigrationError> {
+    let session = current_session(agent).await?;
+    if let Some(oauth) = oauth_session_for(&session.access_jwt) {
+        return fresh_oauth_session(agent, oauth, options).await;
+    }
+    if session.refresh_jwt.is_empty() || !expires_soon(&session.access_jwt, SystemTime::now()) {
+        return Ok(session);
+    }
+    let endpoint = agent.get_endpoint().await;
+    let lock = refresh_lock(&endpoint, session.did.as_str());
+    let _guard = lock.lock().await;
+    // Another transfer may have refreshed the session while we waited.
+    let session = current_session(agent).await?;
+    if !expires_soon(&session.access_jwt, SystemTime::now()) {
+        return Ok(session);
+    }
+
+    tracing::info!("Refreshing session for {}", session.did.as_str());
+    let output = agent
+        .api
+        .com
+        .atproto
+        .server
+        .refresh_session()
+        .await
+        .map_err(|error| MigrationError::from_atrium(&endpoint, error))?;
+    let refreshed = AtpSession {
+        data: CreateSessionOutputData {
+            access_jwt: output.access_jwt.clone(),
+            active: output.active,
+            did: output.did.clone(),
+            did_doc: output.did_doc.clone(),
+            email: session.email.clone(),
+            email_auth_factor: session.email_auth_factor,
+            email_confirmed: session.email_confirmed,
+            handle: output.handle.clone(),
+            refresh_jwt: output.refresh_jwt.clone(),
+            status: output.status.clone(),
+        },
+        extra_data: Ipld::Null,
+    };
+    agent
+        .resume_session(refreshed)
+        .await
+        .map_err(|error| MigrationError::from_atrium(&endpoint, error))?;
+    // Resuming follows the DID document, which may point somewhere other than the
+    // host we are talking to mid-migration.
+    agent.configure_endpoint(endpoint.clone());
+
+    if let Some(on_session_refresh) = &options.on_session_refresh {
+        on_session_refresh(&RefreshedSession {
+            pds_host: endpoint,
+            did: output.did.as_str().to_string(),
+            access_token: output.access_jwt.clone(),
+            refresh_token: output.refresh_jwt.clone(),
+        });
+    }
+    current_session(agent).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(exp: u64) -> String {
+        let claims = serde_json::json!({ "sub": "did:plc:abc", "exp": exp });
+        let payload = multibase::encode(
+            multibase::Base::Base64Url,
+            serde_json::to_vec(&claims).unwrap(),
+        );
+        format!("eyJhbGciOiJFUzI1NksifQ.{}.c2ln", &payload[1..])
+    }
+
+    #[test]
+    fn test_jwt_expiry_reads_exp_claim() {
+        assert_eq!(
+            jwt_expiry(&token(1_700_000_000)),
+            Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+        assert_eq!(jwt_expiry("not-a-jwt"), None);
+    }
+
+    #[test]
+    fn test_expires_soon_within_margin() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert!(expires_soon(&token(1_700_000_060), now));
+        assert!(!expires_soon(&token(1_700_003_600), now));
+        assert!(!expires_soon("opaque", now));
+    }
+
+    #[test]
+    fn test_refresh_lock_is_per_session() {
+        let alice = refresh_lock("https://pds.example.com", "did:plc:alice");
+        let _guard = alice.try_lock().unwrap();
+        // Refreshes of the same session wait for each other, other sessions do not
+        let same = refresh_lock("https://pds.example.com", "did:plc:alice");
+        assert!(same.try_lock().is_err());
+        let bob = refresh_lock("https://pds.example.com", "did:plc:bob");
+        assert!(bob.try_lock().is_ok());
+        let other_host = refresh_lock("https://other.example.com", "did:plc:alice");
+        assert!(other_host.try_lock().is_ok());
+    }
+}
@@ -1,8 +1,11 @@
+use crate::GovernedClient;
 use bsky_sdk::BskyAgent;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
-pub type GetAgentResult = Result<BskyAgent, Box<dyn std::error::Error>>;
+/// Agent used for every migration call, with requests paced by the rate-limit governor.
+pub type MigrationAgent = BskyAgent<GovernedClient>;
+pub type GetAgentResult = Result<MigrationAgent, Box<dyn std::error::Error>>;
 pub type RecommendedDidOutputData =
     bsky_sdk::api::com::atproto::identity::get_recommended_did_credentials::OutputData;
 pub type CreateAccountInput = bsky_sdk::api::com::atproto::server::create_account::Input;
@@ -35,17 +38,29 @@ pub const GET_RECOMMENDED_DID_CREDENTIALS_PATH: &str =
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlcLogAuditEntry {
     pub did: String,
-    pub operation: PlcOperation,
+    pub operation: PlcLogOperation,
     pub cid: String,
     pub nullified: bool,
     #[serde(rename = "createdAt")]
     pub created_at: String,
 }
 
+/// An operation in the log of a did:plc, in any of the formats the directory has accepted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum PlcLogOperation {
+    #[serde(rename = "plc_operation")]
+    Operation(PlcOperation),
+    /// The genesis format of DIDs created before `plc_operation` existed.
+    #[serde(rename = "create")]
+    LegacyCreate(PlcLegacyCreate),
+    /// Deactivates the DID; nothing can follow it.
+    #[serde(rename = "plc_tombstone")]
+    Tombstone(PlcTombstone),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlcOperation {
-    #[serde(rename = "type")]
-    pub r#type: String,
     #[serde(rename = "rotationKeys")]
     pub rotation_keys: Vec<String>,
     #[serde(rename = "verificationMethods")]
@@ -58,6 +73,108 @@ pub struct PlcOperation {
     pub sig: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlcLegacyCreate {
+    #[serde(rename = "signingKey")]
+    pub signing_key: String,
+    #[serde(rename = "recoveryKey")]
+    pub recovery_key: String,
+    pub handle: String,
+    pub service: String,
+    pub prev: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sig: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlcTombstone {
+    pub prev: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sig: Option<String>,
+}
+
+impl From<PlcOperation> for PlcLogOperation {
+    fn from(operation: PlcOperation) -> Self {
+        PlcLogOperation::Operation(operation)
+    }
+}
+
+impl PlcLogOperation {
+    pub fn prev(&self) -> Option<&str> {
+        match self {
+            PlcLogOperation::Operation(operation) => operation.prev.as_deref(),
+            PlcLogOperation::LegacyCreate(create) => create.prev.as_deref(),
+            PlcLogOperation::Tombstone(tombstone) => Some(&tombstone.prev),
+        }
+    }
+
+    pub fn sig(&self) -> Option<&str> {
+        match self {
+            PlcLogOperation::Operation(operation) => operation.sig.as_deref(),
+            PlcLogOperation::LegacyCreate(create) => create.sig.as_deref(),
+            PlcLogOperation::Tombstone(tombstone) => tombstone.sig.as_deref(),
+        }
+    }
+
+    /// The operation without its signature, which is what gets signed.
+    pub fn unsigned(&self) -> Self {
+        let mut unsigned = self.clone();
+        match &mut unsigned {
+            PlcLogOperation::Operation(operation) => operation.sig = None,
+            PlcLogOperation::LegacyCreate(create) => create.sig = None,
+            PlcLogOperation::Tombstone(tombstone) => tombstone.sig = None,
+        }
+        unsigned
+    }
+
+    /// The DID's state after this operation, with a legacy `create` read the way the directory
+    /// reads it. `None` once the DID is tombstoned.
+    pub fn normalized(&self) -> Option<PlcOperation> {
+        match self {
+            PlcLogOperation::Operation(operation) => Some(operation.clone()),
+            PlcLogOperation::LegacyCreate(create) => {
+                let handle = create
+                    .handle
+                    .trim_start_matches("at://")
+                    .trim_start_matches("https://")
+                    .trim_start_matches("http://");
+                let endpoint = if create.service.starts_with("https://")
+                    || create.service.starts_with("http://")
+                {
+                    create.service.clone()
+                } else {
+                    format!("https://{}", create.service)
+                };
+                Some(PlcOperation {
+                    rotation_keys: vec![create.recovery_key.clone(), create.signing_key.clone()],
+                    verification_methods: BTreeMap::from([(
+                        "atproto".to_string(),
+                        create.signing_key.clone(),
+                    )]),
+                    also_known_as: vec![format!("at://{handle}")],
+                    services: BTreeMap::from([(
+                        "atproto_pds".to_string(),
+                        PlcOpService {
+                            r#type: "AtprotoPersonalDataServer".to_string(),
+                            endpoint,
+                        },
+                    )]),
+                    prev: create.prev.clone(),
+                    sig: create.sig.clone(),
+                })
+            }
+            PlcLogOperation::Tombstone(_) => None,
+        }
+    }
+
+    /// Keys allowed to sign the operation that follows this one, highest priority first.
+    pub fn rotation_keys(&self) -> Vec<String> {
+        self.normalized()
+            .map(|operation| operation.rotation_keys)
+            .unwrap_or_default()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlcOpService {
     #[serde(rename = "type")]
@@ -0,0 +1,172 @@
+use crate::{verify_block, CarError, MigrationError, StagingStore, StagingStream};
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use ipld_core::cid::multihash::Multihash;
+use ipld_core::cid::Cid;
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
+
+const SHA2_256: u64 = 0x12;
+const RAW: u64 = 0x55;
+
+/// Outcome of staging a blob whose bytes were hashed on the way in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlobCheck {
+    Verified,
+    Corrupt,
+}
+
+/// The CID a PDS assigns to a blob: raw codec over a sha-256 digest.
+pub fn blob_cid(data: &[u8]) -> Cid {
+    let digest = Sha256::digest(data);
+    Cid::new_v1(RAW, Multihash::wrap(SHA2_256, &digest).unwrap())
+}
+
+pub(crate) fn parse_cid(cid: &str) -> Result<Cid, MigrationError> {
+    Cid::try_from(cid).map_err(|error| {
+        tracing::error!("Invalid blob CID {}: {}", cid, error);
+        MigrationError::Validation {
+            field: "cid".to_string(),
+        }
+    })
+}
+
+/// Checks staged blob bytes against the CID they are stored under.
+pub fn verify_blob(cid: &str, data: &[u8]) -> Result<BlobCheck, MigrationError> {
+    match verify_block(&parse_cid(cid)?, data) {
+        Ok(()) => Ok(BlobCheck::Verified),
+        Err(CarError::HashMismatch { .. }) => Ok(BlobCheck::Corrupt),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Streams a downloaded blob into `store`, hashing it as it goes. A blob whose
+/// bytes do not match `cid` is removed again so it is never uploaded.
+pub async fn put_verified_blob(
+    store: &dyn StagingStore,
+    key: &str,
+    cid: &str,
+    stream: StagingStream<'_>,
+) -> Result<BlobCheck, MigrationError> {
+    let expected = parse_cid(cid)?;
+    if expected.hash().code() != SHA2_256 {
+        return Err(CarError::UnsupportedHash {
+            cid: cid.to_string(),
+            code: expected.hash().code(),
+        }
+        .into());
+    }
+
+    let hasher = BlobHasher::default();
+    store.put_stream(key, hasher.tap(stream).boxed()).await?;
+
+    if hasher.check(&expected) == BlobCheck::Verified {
+        Ok(BlobCheck::Verified)
+    } else {
+        tracing::error!("Blob {} does not match its CID", cid);
+        store.delete(key).await?;
+        Ok(BlobCheck::Corrupt)
+    }
+}
+
+/// Hashes blob bytes as they stream past, so a blob can be checked without buffering it.
+#[derive(Clone, Default)]
+pub struct BlobHasher {
+    hasher: Arc<Mutex<Sha256>>,
+}
+
+impl BlobHasher {
+    /// Feeds every chunk of `stream` into the hash on its way through.
+    pub fn tap<S, E>(&self, stream: S) -> impl Stream<Item = Result<Bytes, E>>
+    where
+        S: Stream<Item = Result<Bytes, E>>,
+    {
+        let hasher = self.hasher.clone();
+        stream.inspect(move |chunk| {
+            if let Ok(bytes) = chunk {
+                hasher.lock().unwrap().update(bytes);
+            }
+        })
+    }
+
+    /// Compares everything seen so far with the sha-256 digest in `expected`.
+    pub fn check(&self, expected: &Cid) -> BlobCheck {
+        let digest = self.hasher.lock().unwrap().clone().finalize();
+        if expected.hash().code() == SHA2_256 && digest.as_slice() == expected.hash().digest() {
+            BlobCheck::Verified
+        } else {
+            BlobCheck::Corrupt
+        }
+    }
+}
+
+/// Compares the CID a PDS returned from `uploadBlob` with the one we expected.
+pub fn check_uploaded_cid(expected: &str, returned: &str) -> BlobCheck {
+    match (Cid::try_from(expected), Cid::try_from(returned)) {
+        (Ok(expected), Ok(returned)) if expected.hash() == returned.hash() => BlobCheck::Verified,
+        _ => BlobCheck::Corrupt,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryStagingStore;
+
+    fn stream_of(data: &'static [u8]) -> StagingStream<'static> {
+        futures_util::stream::iter(vec![
+            Ok(Bytes::from_static(&data[..2])),
+            Ok(Bytes::from_static(&data[2..])),
+        ])
+        .boxed()
+    }
+
+    #[test]
+    fn test_verified_blob_is_kept() {
+        let store = MemoryStagingStore::new();
+        let cid = blob_cid(b"blob bytes").to_string();
+        let check = tokio_test::block_on(put_verified_blob(
+            &store,
+            "blobs/a",
+            &cid,
+            stream_of(b"blob bytes"),
+        ))
+        .unwrap();
+        assert_eq!(check, BlobCheck::Verified);
+        assert!(tokio_test::block_on(store.exists("blobs/a")).unwrap());
+    }
+
+    #[test]
+    fn test_corrupt_blob_is_removed() {
+        let store = MemoryStagingStore::new();
+        let cid = blob_cid(b"blob bytes").to_string();
+        let check = tokio_test::block_on(put_verified_blob(
+            &store,
+            "blobs/a",
+            &cid,
+            stream_of(b"blob bytez"),
+        ))
+        .unwrap();
+        assert_eq!(check, BlobCheck::Corrupt);
+        assert!(!tokio_test::block_on(store.exists("blobs/a")).unwrap());
+    }
+
+    #[test]
+    fn test_verify_staged_blob() {
+        let cid = blob_cid(b"blob bytes").to_string();
+        assert_eq!(
+            verify_blob(&cid, b"blob bytes").unwrap(),
+            BlobCheck::Verified
+        );
+        assert_eq!(verify_blob(&cid, b"other").unwrap(), BlobCheck::Corrupt);
+        assert!(verify_blob("not-a-cid", b"other").is_err());
+    }
+
+    #[test]
+    fn test_check_uploaded_cid() {
+        let cid = blob_cid(b"blob bytes").to_string();
+        assert_eq!(check_uploaded_cid(&cid, &cid), BlobCheck::Verified);
+        let other = blob_cid(b"other").to_string();
+        assert_eq!(check_uploaded_cid(&cid, &other), BlobCheck::Corrupt);
+    }
+}
@@ -0,0 +1,238 @@
+use crate::MigrationError;
+use derive_more::{Display, Error};
+use ipld_core::cid::Cid;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Cursor;
+
+const SHA2_256: u64 = 0x12;
+const IDENTITY: u64 = 0x00;
+
+/// Why a CAR file could not be read. Offsets are byte positions in the file.
+#[derive(Debug, Display, Error, PartialEq)]
+pub enum CarError {
+    #[display("CAR file is truncated in the section at byte {offset}")]
+    Truncated { offset: usize },
+    #[display("Invalid CAR header: {message}")]
+    InvalidHeader { message: String },
+    #[display("Unsupported CAR version {version}")]
+    UnsupportedVersion { version: u64 },
+    #[display("Invalid CID at byte {offset}: {message}")]
+    InvalidCid { offset: usize, message: String },
+    #[display("Unsupported hash function {code:#x} for block {cid}")]
+    UnsupportedHash { cid: String, code: u64 },
+    #[display("Block {cid} does not match its content hash")]
+    HashMismatch { cid: String },
+    #[display("Root {cid} is not present in the CAR file")]
+    MissingRoot { cid: String },
+    #[display("Block {cid} is referenced but not present in the CAR file")]
+    MissingBlock { cid: String },
+    #[display("Invalid repo commit: {message}")]
+    InvalidCommit { message: String },
+    #[display("Invalid repo node {cid}: {message}")]
+    InvalidNode { cid: String, message: String },
+}
+
+impl From<CarError> for MigrationError {
+    fn from(error: CarError) -> Self {
+        MigrationError::Upstream {
+            message: error.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CarHeader {
+    pub version: u64,
+    pub roots: Vec<Cid>,
+}
+
+/// A fully read CAR v1 file whose blocks have all been checked against their CIDs.
+#[derive(Debug, Clone)]
+pub struct CarFile {
+    pub header: CarHeader,
+    pub blocks: HashMap<Cid, Vec<u8>>,
+}
+
+impl CarFile {
+    /// Parses `bytes` as a CAR v1 file, verifying every block hash and that each
+    /// root is present.
+    pub fn parse(bytes: &[u8]) -> Result<Self, CarError> {
+        let (header_bytes, mut offset) = read_section(bytes, 0)?;
+        let header: CarHeader = serde_ipld_dagcbor::from_slice(header_bytes).map_err(|error| {
+            CarError::InvalidHeader {
+                message: error.to_string(),
+            }
+        })?;
+        if header.version != 1 {
+            return Err(CarError::UnsupportedVersion {
+                version: header.version,
+            });
+        }
+
+        let mut blocks = HashMap::new();
+        while offset < bytes.len() {
+            let (section, next) = read_section(bytes, offset)?;
+            let mut cursor = Cursor::new(section);
+            let cid = Cid::read_bytes(&mut cursor).map_err(|error| CarError::InvalidCid {
+                offset,
+                message: error.to_string(),
+            })?;
+            let data = &section[cursor.position() as usize..];
+            verify_block(&cid, data)?;
+            blocks.insert(cid, data.to_vec());
+            offset = next;
+        }
+
+        for root in &header.roots {
+            if !blocks.contains_key(root) {
+                return Err(CarError::MissingRoot {
+                    cid: root.to_string(),
+                });
+            }
+        }
+        Ok(Self { header, blocks })
+    }
+
+    pub fn root(&self) -> Option<&Cid> {
+        self.header.roots.first()
+    }
+
+    pub fn get(&self, cid: &Cid) -> Option<&[u8]> {
+        self.blocks.get(cid).map(Vec::as_slice)
+    }
+}
+
+/// Checks that `data` hashes to the digest carried by `cid`.
+pub fn verify_block(cid: &Cid, data: &[u8]) -> Result<(), CarError> {
+    let hash = cid.hash();
+    let matches = match hash.code() {
+        SHA2_256 => Sha256::digest(data).as_slice() == hash.digest(),
+        IDENTITY => data == hash.digest(),
+        code => {
+            return Err(CarError::UnsupportedHash {
+                cid: cid.to_string(),
+                code,
+            })
+        }
+    };
+    if matches {
+        Ok(())
+    } else {
+        Err(CarError::HashMismatch {
+            cid: cid.to_string(),
+        })
+    }
+}
+
+/// Reads one varint length-prefixed section starting at `offset`, returning it and
+/// the offset of the following section.
+fn read_section(bytes: &[u8], offset: usize) -> Result<(&[u8], usize), CarError> {
+    let (length, rest) = unsigned_varint::decode::u64(&bytes[offset..])
+        .map_err(|_| CarError::Truncated { offset })?;
+    let start = bytes.len() - rest.len();
+    let end = usize::try_from(length)
+        .ok()
+        .and_then(|length| start.checked_add(length))
+        .filter(|end| *end <= bytes.len())
+        .ok_or(CarError::Truncated { offset })?;
+    Ok((&bytes[start..end], end))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use ipld_core::cid::multihash::Multihash;
+
+    const DAG_CBOR: u64 = 0x71;
+
+    pub(crate) fn cid_for(data: &[u8]) -> Cid {
+        let digest = Sha256::digest(data);
+        Cid::new_v1(DAG_CBOR, Multihash::wrap(SHA2_256, &digest).unwrap())
+    }
+
+    fn push_section(out: &mut Vec<u8>, section: &[u8]) {
+        let mut buf = unsigned_varint::encode::u64_buffer();
+        out.extend_from_slice(unsigned_varint::encode::u64(section.len() as u64, &mut buf));
+        out.extend_from_slice(section);
+    }
+
+    pub(crate) fn write_car(roots: Vec<Cid>, blocks: &[(Cid, Vec<u8>)]) -> Vec<u8> {
+        let header = CarHeader { version: 1, roots };
+        let mut out = Vec::new();
+        push_section(&mut out, &serde_ipld_dagcbor::to_vec(&header).unwrap());
+        for (cid, data) in blocks {
+            let mut section = cid.to_bytes();
+            section.extend_from_slice(data);
+            push_section(&mut out, &section);
+        }
+        out
+    }
+
+    fn sample() -> (Cid, Vec<u8>, Vec<u8>) {
+        let data = serde_ipld_dagcbor::to_vec(&"hello").unwrap();
+        let cid = cid_for(&data);
+        let car = write_car(vec![cid], &[(cid, data.clone())]);
+        (cid, data, car)
+    }
+
+    #[test]
+    fn test_parse_valid_car() {
+        let (cid, data, car) = sample();
+        let parsed = CarFile::parse(&car).unwrap();
+        assert_eq!(parsed.root(), Some(&cid));
+        assert_eq!(parsed.get(&cid), Some(data.as_slice()));
+    }
+
+    #[test]
+    fn test_truncated_car_is_rejected() {
+        let (_, _, car) = sample();
+        let error = CarFile::parse(&car[..car.len() - 1]).unwrap_err();
+        // The block after the header is the section cut short
+        let (_, block_offset) = read_section(&car, 0).unwrap();
+        assert_eq!(
+            error,
+            CarError::Truncated {
+                offset: block_offset
+            }
+        );
+    }
+
+    #[test]
+    fn test_corrupted_block_is_rejected() {
+        let (cid, _, mut car) = sample();
+        let last = car.len() - 1;
+        car[last] ^= 0xff;
+        assert_eq!(
+            CarFile::parse(&car).unwrap_err(),
+            CarError::HashMismatch {
+                cid: cid.to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_missing_root_is_rejected() {
+        let (cid, _, _) = sample();
+        let car = write_car(vec![cid], &[]);
+        assert!(matches!(
+            CarFile::parse(&car).unwrap_err(),
+            CarError::MissingRoot { .. }
+        ));
+    }
+
+    #[test]
+    fn test_unsupported_version_is_rejected() {
+        let header = CarHeader {
+            version: 2,
+            roots: vec![],
+        };
+        let mut car = Vec::new();
+        push_section(&mut car, &serde_ipld_dagcbor::to_vec(&header).unwrap());
+        assert_eq!(
+            CarFile::parse(&car).unwrap_err(),
+            CarError::UnsupportedVersion { version: 2 }
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use crate::agent::{account_import, download_repo, login_helper};
+use crate::{
+    build_agent, repo_rev_key, CarFile, GetRepoRequest, MigrationError, MigrationOptions,
+    RepoCommit, RetryPolicy, StagingStore,
+};
+use futures_util::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize)]
+pub struct CatchUpRequest {
+    pub origin: String,
+    pub destination: String,
+    pub did: String,
+    pub origin_token: String,
+    pub destination_token: String,
+    /// Repo rev the destination already has. Defaults to the rev recorded by the last import.
+    #[serde(default)]
+    pub since: Option<String>,
+}
+
+impl std::fmt::Debug for CatchUpRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CatchUpRequest")
+            .field("origin", &self.origin)
+            .field("destination", &self.destination)
+            .field("did", &self.did)
+            .field("origin_token", &"[REDACTED]")
+            .field("destination_token", &"[REDACTED]")
+            .field("since", &self.since)
+            .finish()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CatchUpResponse {
+    /// The rev the catch-up started from.
+    pub since: String,
+    /// The origin's current rev, or `None` when nothing was written since `since`.
+    pub rev: Option<String>,
+}
+
+/// Applies every record written on the origin since the initial import to the destination.
+///
+/// Only the blocks newer than `since` are downloaded, so this is cheap enough to run right
+/// before the PLC operation switches the DID over, closing the gap between export and cut-over.
+#[tracing::instrument(skip(req, store, options))]
+pub async fn catch_up_api(
+    req: CatchUpRequest,
+    store: &dyn StagingStore,
+    options: &MigrationOptions,
+) -> Result<CatchUpResponse, MigrationError> {
+    let since = match req.since {
+        Some(since) => since,
+        None => {
+            let key = repo_rev_key(req.did.as_str());
+            if !store.exists(&key).await? {
+                tracing::error!("No imported repo rev recorded for {}", req.did);
+                return Err(MigrationError::Validation {
+                    field: "since".to_string(),
+                });
+            }
+            String::from_utf8(store.get(&key).await?).map_err(|_| MigrationError::Validation {
+                field: "since".to_string(),
+            })?
+        }
+    };
+
+    let origin_agent = build_agent(&options.retry).await?;
+    let session = login_helper(
+        &origin_agent,
+        req.origin.as_str(),
+        req.did.as_str(),
+        req.origin_token.as_str(),
+    )
+    .await?;
+    let request = GetRepoRequest {
+        did: session.did.clone(),
+        token: session.access_jwt.clone(),
+        since: Some(since.clone()),
+    };
+    let Some((rev, diff)) = fetch_repo_since(
+        origin_agent.get_endpoint().await.as_str(),
+        &request,
+        &options.retry,
+    )
+    .await?
+    else {
+        tracing::info!("Destination is already up to date at rev {}", since);
+        return Ok(CatchUpResponse { since, rev: None });
+    };
+
+    let destination_agent = build_agent(&options.retry).await?;
+    login_helper(
+        &destination_agent,
+        req.destination.as_str(),
+        req.did.as_str(),
+        req.destination_token.as_str(),
+    )
+    .await?;
+    options.check_canceled()?;
+    account_import(&destination_agent, diff).await?;
+    store
+        .put(&repo_rev_key(req.did.as_str()), rev.clone().into_bytes())
+        .await?;
+    tracing::info!("Caught up repository from rev {} to {}", since, rev);
+    Ok(CatchUpResponse {
+        since,
+        rev: Some(rev),
+    })
+}
+
+/// Downloads the blocks written after `request.since` and returns them with the commit's rev,
+/// or `None` when the origin has no commit newer than `since`.
+pub async fn fetch_repo_since(
+    pds_host: &str,
+    request: &GetRepoRequest,
+    retry: &RetryPolicy,
+) -> Result<Option<(String, Vec<u8>)>, MigrationError> {
+    let stream = download_repo(pds_host, request, retry).await?;
+    let bytes: Vec<u8> = stream
+        .map_ok(|chunk| chunk.to_vec())
+        .try_concat()
+        .await
+        .map_err(|error| {
+            tracing::error!("Failed to read repo diff: {}", error);
+            MigrationError::Runtime {
+                message: "Failed to read repo diff".to_string(),
+            }
+        })?;
+    let commit = RepoCommit::from_car(&CarFile::parse(&bytes)?)?;
+    // Revs are TIDs, which sort lexically in creation order
+    match &request.since {
+        Some(since) if commit.rev.as_str() <= since.as_str() => Ok(None),
+        _ => Ok(Some((commit.rev, bytes))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::car::tests::{cid_for, write_car};
+    use bsky_sdk::api::types::string::Did;
+    use ipld_core::ipld::Ipld;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn commit_car(rev: &str) -> Vec<u8> {
+        let commit = Ipld::Map(
+            [
+                ("did", Ipld::String("did:plc:example123".into())),
+                ("version", Ipld::Integer(3)),
+                ("rev", Ipld::String(rev.into())),
+                ("data", Ipld::Link(cid_for(b"mst"))),
+                ("prev", Ipld::Null),
+            ]
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect(),
+        );
+        let data = serde_ipld_dagcbor::to_vec(&commit).unwrap();
+        let cid = cid_for(&data);
+        write_car(vec![cid], &[(cid, data)])
+    }
+
+    async fn origin(rev: &str) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.sync.getRepo"))
+            .and(query_param("did", "did:plc:example123"))
+            .and(query_param("since", "3kaaa"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(commit_car(rev)))
+            .expect(1)
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn request() -> GetRepoRequest {
+        GetRepoRequest {
+            did: Did::new("did:plc:example123".to_string()).unwrap(),
+            token: "origin-token".to_string(),
+            since: Some("3kaaa".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_fetch_repo_since_returns_newer_commit() {
+        tokio_test::block_on(async {
+            let server = origin("3kbbb").await;
+            let (rev, diff) = fetch_repo_since(&server.uri(), &request(), &RetryPolicy::none())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(rev, "3kbbb");
+            assert_eq!(diff, commit_car("3kbbb"));
+        });
+    }
+
+    #[test]
+    fn test_fetch_repo_since_skips_unchanged_repo() {
+        tokio_test::block_on(async {
+            let server = origin("3kaaa").await;
+            let diff = fetch_repo_since(&server.uri(), &request(), &RetryPolicy::none())
+                .await
+                .unwrap();
+            assert!(diff.is_none());
+        });
+    }
+}
@@ -1,17 +1,82 @@
 use crate::agent::{deactivate_account, login_helper};
-use crate::{build_agent, MigrationError};
+use crate::{
+    build_agent, verify_migration_api, IdentityResolver, MigrationError, MigrationOptions,
+    VerifyMigrationRequest,
+};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Deserialize, Serialize)]
 pub struct DeactivateAccountRequest {
     pub pds_host: String,
     pub did: String,
     pub token: String,
+    /// The PDS the account moved to, verified against `pds_host` before deactivating.
+    #[serde(default)]
+    pub destination: Option<String>,
+    #[serde(default)]
+    pub destination_token: Option<String>,
+    /// Deactivates without checking the destination first.
+    #[serde(default)]
+    pub skip_verification: bool,
 }
 
-#[tracing::instrument(skip(req))]
-pub async fn deactivate_account_api(req: DeactivateAccountRequest) -> Result<(), MigrationError> {
-    let agent = build_agent().await?;
+impl std::fmt::Debug for DeactivateAccountRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeactivateAccountRequest")
+            .field("pds_host", &self.pds_host)
+            .field("did", &self.did)
+            .field("token", &"[REDACTED]")
+            .field("destination", &self.destination)
+            .field(
+                "destination_token",
+                &self.destination_token.as_ref().map(|_| "[REDACTED]"),
+            )
+            .field("skip_verification", &self.skip_verification)
+            .finish()
+    }
+}
+
+#[tracing::instrument(skip(req, resolver, options))]
+pub async fn deactivate_account_api(
+    req: DeactivateAccountRequest,
+    resolver: &IdentityResolver,
+    options: &MigrationOptions,
+) -> Result<(), MigrationError> {
+    if req.skip_verification {
+        tracing::warn!("Deactivating without verifying the destination");
+    } else {
+        let destination = req.destination.clone().ok_or(MigrationError::Validation {
+            field: "destination".to_string(),
+        })?;
+        let destination_token =
+            req.destination_token
+                .clone()
+                .ok_or(MigrationError::Validation {
+                    field: "destination_token".to_string(),
+                })?;
+        let report = verify_migration_api(
+            VerifyMigrationRequest {
+                origin: req.pds_host.clone(),
+                destination,
+                did: req.did.clone(),
+                origin_token: req.token.clone(),
+                destination_token,
+            },
+            resolver,
+            options,
+        )
+        .await?;
+        if !report.passed() {
+            return Err(MigrationError::VerificationFailed {
+                failed: report
+                    .failures()
+                    .map(|failure| failure.message.clone())
+                    .collect(),
+            });
+        }
+    }
+
+    let agent = build_agent(&options.retry).await?;
     login_helper(
         &agent,
         req.pds_host.as_str(),
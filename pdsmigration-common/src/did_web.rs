@@ -0,0 +1,490 @@
+use crate::agent::{login_helper, recommended_plc, resume_session};
+use crate::{
+    activate_account, build_agent, did_web_document_key, fresh_session, parse_did,
+    IdentityResolver, MigrationError, MigrationOptions, RecommendedDidOutputData, StagingStore,
+};
+use bsky_sdk::api::did_doc::{DidDocument, Service, VerificationMethod};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+const DID_CONTEXT: [&str; 3] = [
+    "https://www.w3.org/ns/did/v1",
+    "https://w3id.org/security/multikey/v1",
+    "https://w3id.org/security/suites/secp256k1-2019/v1",
+];
+
+#[derive(Deserialize, Serialize)]
+pub struct MigrateDidWebRequest {
+    pub destination: String,
+    pub did: String,
+    pub destination_token: String,
+    /// Lets the destination session outlive the access token while the domain is awaited.
+    /// Without it, `destination_token` must still be valid once the domain serves the document.
+    #[serde(default)]
+    pub destination_refresh_token: Option<String>,
+    /// Seconds between checks of the domain for the published `did.json`. Must be at least 1.
+    #[serde(default = "default_poll_interval_secs")]
+    pub poll_interval_secs: u64,
+    /// Seconds to wait for the domain to serve the new `did.json` before giving up.
+    #[serde(default = "default_publish_timeout_secs")]
+    pub publish_timeout_secs: u64,
+}
+
+pub(crate) fn default_poll_interval_secs() -> u64 {
+    30
+}
+
+pub(crate) fn default_publish_timeout_secs() -> u64 {
+    24 * 60 * 60
+}
+
+impl std::fmt::Debug for MigrateDidWebRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MigrateDidWebRequest")
+            .field("destination", &self.destination)
+            .field("did", &self.did)
+            .field("destination_token", &"[REDACTED]")
+            .field(
+                "destination_refresh_token",
+                &self
+                    .destination_refresh_token
+                    .as_ref()
+                    .map(|_| "[REDACTED]"),
+            )
+            .field("poll_interval_secs", &self.poll_interval_secs)
+            .field("publish_timeout_secs", &self.publish_timeout_secs)
+            .finish()
+    }
+}
+
+/// Moves a did:web account, which no PDS can update for the user: the new `did.json` is
+/// written to `store` for the user to publish on their domain, and the destination account is
+/// activated once the domain serves it.
+#[tracing::instrument(skip(store, resolver, options))]
+pub async fn migrate_did_web_api(
+    req: MigrateDidWebRequest,
+    store: &dyn StagingStore,
+    resolver: &IdentityResolver,
+    options: &MigrationOptions,
+) -> Result<DidDocument, MigrationError> {
+    let did_doc = prepare_did_web_api(&req, store, options).await?;
+    await_did_web_api(&req, &did_doc, resolver, options).await?;
+    let destination_token = match req.destination_refresh_token.as_deref() {
+        // Publishing can take hours, long after the access token has expired
+        Some(refresh_token) => {
+            let agent = build_agent(&options.retry).await?;
+            resume_session(
+                &agent,
+                req.destination.as_str(),
+                req.did.as_str(),
+                req.destination_token.as_str(),
+                Some(refresh_token),
+            )
+            .await?;
+            fresh_session(&agent, options).await?.access_jwt.clone()
+        }
+        None => req.destination_token.clone(),
+    };
+    activate_account(
+        req.destination.as_str(),
+        req.did.as_str(),
+        destination_token.as_str(),
+        options,
+    )
+    .await?;
+    Ok(did_doc)
+}
+
+/// Builds the `did.json` pointing at the destination and writes it to
+/// [`did_web_document_key`].
+#[tracing::instrument(skip(store, options))]
+pub async fn prepare_did_web_api(
+    req: &MigrateDidWebRequest,
+    store: &dyn StagingStore,
+    options: &MigrationOptions,
+) -> Result<DidDocument, MigrationError> {
+    if !req.did.starts_with("did:web:") {
+        return Err(MigrationError::Validation {
+            field: "did".to_string(),
+        });
+    }
+    let agent = build_agent(&options.retry).await?;
+    login_helper(
+        &agent,
+        req.destination.as_str(),
+        req.did.as_str(),
+        req.destination_token.as_str(),
+    )
+    .await?;
+    let recommended = recommended_plc(&agent).await?;
+    let did_doc = did_web_document(&req.did, recommended)?;
+    let bytes = serde_json::to_vec_pretty(&did_doc).map_err(|error| MigrationError::Runtime {
+        message: error.to_string(),
+    })?;
+    store.put(&did_web_document_key(&req.did), bytes).await?;
+    tracing::info!(
+        "Wrote {}; publish it as the did.json of {}",
+        did_web_document_key(&req.did),
+        req.did
+    );
+    Ok(did_doc)
+}
+
+/// Polls the domain until its `did.json` matches `expected`.
+#[tracing::instrument(skip(expected, resolver, options))]
+pub async fn await_did_web_api(
+    req: &MigrateDidWebRequest,
+    expected: &DidDocument,
+    resolver: &IdentityResolver,
+    options: &MigrationOptions,
+) -> Result<(), MigrationError> {
+    // Without a pause the domain would be fetched in a tight loop until the timeout
+    if req.poll_interval_secs == 0 {
+        return Err(MigrationError::Validation {
+            field: "poll_interval_secs".to_string(),
+        });
+    }
+    let did = parse_did(&req.did)?;
+    let started = Instant::now();
+    loop {
+        options.check_canceled()?;
+        let mismatches = match resolver.resolve_did(&did).await {
+            Ok(published) => did_web_mismatches(&published, expected),
+            Err(error) => vec![format!("Could not fetch the DID document: {error}")],
+        };
+        if mismatches.is_empty() {
+            tracing::info!("{} serves the new DID document", req.did);
+            return Ok(());
+        }
+        if started.elapsed() >= Duration::from_secs(req.publish_timeout_secs) {
+            return Err(MigrationError::VerificationFailed { failed: mismatches });
+        }
+        tracing::info!(
+            "Waiting for the new DID document: {}",
+            mismatches.join("; ")
+        );
+        tokio::time::sleep(Duration::from_secs(req.poll_interval_secs)).await;
+    }
+}
+
+/// The DID document the destination recommends, in the shape a did:web serves it.
+pub fn did_web_document(
+    did: &str,
+    recommended: RecommendedDidOutputData,
+) -> Result<DidDocument, MigrationError> {
+    let verification_methods = recommended
+        .verification_methods
+        .and_then(|methods| serde_json::to_value(methods).ok())
+        .unwrap_or_default();
+    let signing_key = verification_methods
+        .get("atproto")
+        .and_then(|key| key.as_str()?.strip_prefix("did:key:"))
+        .ok_or_else(|| MigrationError::Upstream {
+            message: "The destination recommends no atproto signing key".to_string(),
+        })?;
+    let services = recommended
+        .services
+        .and_then(|services| serde_json::to_value(services).ok())
+        .unwrap_or_default();
+    let pds = services.get("atproto_pds");
+    let endpoint = pds
+        .and_then(|pds| pds.get("endpoint")?.as_str())
+        .ok_or_else(|| MigrationError::Upstream {
+            message: "The destination recommends no PDS endpoint".to_string(),
+        })?;
+    Ok(DidDocument {
+        context: Some(
+            DID_CONTEXT
+                .iter()
+                .map(|context| context.to_string())
+                .collect(),
+        ),
+        id: did.to_string(),
+        also_known_as: recommended.also_known_as,
+        verification_method: Some(vec![VerificationMethod {
+            id: format!("{did}#atproto"),
+            r#type: "Multikey".to_string(),
+            controller: did.to_string(),
+            public_key_multibase: Some(signing_key.to_string()),
+        }]),
+        service: Some(vec![Service {
+            id: "#atproto_pds".to_string(),
+            r#type: pds
+                .and_then(|pds| pds.get("type")?.as_str())
+                .unwrap_or("AtprotoPersonalDataServer")
+                .to_string(),
+            service_endpoint: endpoint.to_string(),
+        }]),
+    })
+}
+
+/// What still differs between the document the domain serves and the one it should.
+fn did_web_mismatches(published: &DidDocument, expected: &DidDocument) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    let endpoint = |did_doc: &DidDocument| {
+        did_doc
+            .get_pds_endpoint()
+            .map(|endpoint| endpoint.trim_end_matches('/').to_string())
+    };
+    if endpoint(published) != endpoint(expected) {
+        mismatches.push(format!(
+            "PDS is {}, expected {}",
+            endpoint(published).as_deref().unwrap_or("missing"),
+            endpoint(expected).as_deref().unwrap_or("missing")
+        ));
+    }
+    let signing_key = |did_doc: &DidDocument| {
+        did_doc
+            .get_signing_key()
+            .and_then(|method| method.public_key_multibase.clone())
+    };
+    if signing_key(published) != signing_key(expected) {
+        mismatches.push("Signing key is not the destination's".to_string());
+    }
+    if published.also_known_as != expected.also_known_as {
+        mismatches.push(format!(
+            "Handle is {:?}, expected {:?}",
+            published.also_known_as, expected.also_known_as
+        ));
+    }
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LocalStagingStore, RetryPolicy};
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const DID: &str = "did:web:alice.example.com";
+    const KEY: &str = "zQ3shokFTS3brHcDQrn82RUDfCZESWL1ZdCEJwekUDPQiYBme";
+
+    /// An access token for [`DID`] that expires `exp_offset` seconds from now.
+    fn token(exp_offset: i64) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let claims = serde_json::json!({ "sub": DID, "exp": now + exp_offset });
+        let payload = multibase::encode(
+            multibase::Base::Base64Url,
+            serde_json::to_vec(&claims).unwrap(),
+        );
+        format!("eyJhbGciOiJFUzI1NksifQ.{}.c2ln", &payload[1..])
+    }
+
+    fn document(endpoint: &str) -> DidDocument {
+        serde_json::from_value(serde_json::json!({
+            "id": DID,
+            "alsoKnownAs": ["at://alice.example.com"],
+            "verificationMethod": [{
+                "id": format!("{DID}#atproto"),
+                "type": "Multikey",
+                "controller": DID,
+                "publicKeyMultibase": KEY,
+            }],
+            "service": [{
+                "id": "#atproto_pds",
+                "type": "AtprotoPersonalDataServer",
+                "serviceEndpoint": endpoint,
+            }],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_await_did_web_matches_and_times_out() {
+        tokio_test::block_on(async {
+            let domain = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/.well-known/did.json"))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_json(document("https://destination.example.com/")),
+                )
+                .mount(&domain)
+                .await;
+            let request = MigrateDidWebRequest {
+                destination: "https://destination.example.com".to_string(),
+                did: DID.to_string(),
+                destination_token: token(3600),
+                destination_refresh_token: None,
+                poll_interval_secs: 1,
+                publish_timeout_secs: 0,
+            };
+            let resolver = IdentityResolver {
+                web_base: Some(domain.uri()),
+                retry: RetryPolicy::none(),
+                ..IdentityResolver::default()
+            };
+            let options = MigrationOptions {
+                retry: RetryPolicy::none(),
+                ..MigrationOptions::default()
+            };
+
+            // A trailing slash on the endpoint still counts as published
+            let expected = document("https://destination.example.com");
+            await_did_web_api(&request, &expected, &resolver, &options)
+                .await
+                .unwrap();
+
+            let expected = document("https://elsewhere.example.com");
+            let error = await_did_web_api(&request, &expected, &resolver, &options)
+                .await
+                .unwrap_err();
+            let MigrationError::VerificationFailed { failed } = error else {
+                panic!("expected VerificationFailed, got {error:?}");
+            };
+            assert_eq!(
+                failed,
+                vec![
+                    "PDS is https://destination.example.com, expected https://elsewhere.example.com"
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn test_await_did_web_rejects_zero_poll_interval() {
+        tokio_test::block_on(async {
+            let request = MigrateDidWebRequest {
+                destination: "https://destination.example.com".to_string(),
+                did: DID.to_string(),
+                destination_token: token(3600),
+                destination_refresh_token: None,
+                poll_interval_secs: 0,
+                publish_timeout_secs: 60,
+            };
+            let error = await_did_web_api(
+                &request,
+                &document("https://destination.example.com"),
+                &IdentityResolver::default(),
+                &MigrationOptions::default(),
+            )
+            .await
+            .unwrap_err();
+            assert!(
+                matches!(error, MigrationError::Validation { ref field } if field == "poll_interval_secs")
+            );
+        });
+    }
+
+    #[test]
+    fn test_migrate_did_web_waits_for_publication() {
+        tokio_test::block_on(async {
+            let destination = MockServer::start().await;
+            let domain = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/xrpc/com.atproto.server.getSession"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(
+                    serde_json::json!({ "did": DID, "handle": "alice.example.com" }),
+                ))
+                .mount(&destination)
+                .await;
+            Mock::given(method("GET"))
+                .and(path(
+                    "/xrpc/com.atproto.identity.getRecommendedDidCredentials",
+                ))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "alsoKnownAs": ["at://alice.example.com"],
+                    "verificationMethods": { "atproto": format!("did:key:{KEY}") },
+                    "services": {
+                        "atproto_pds": {
+                            "type": "AtprotoPersonalDataServer",
+                            "endpoint": destination.uri(),
+                        }
+                    },
+                })))
+                .mount(&destination)
+                .await;
+            // The access token has expired by the time the document is published
+            let refreshed = token(3600);
+            Mock::given(method("POST"))
+                .and(path("/xrpc/com.atproto.server.refreshSession"))
+                .and(header("authorization", "Bearer destination-refresh"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "accessJwt": refreshed,
+                    "refreshJwt": "destination-refresh-2",
+                    "did": DID,
+                    "handle": "alice.example.com",
+                })))
+                .expect(1)
+                .mount(&destination)
+                .await;
+            Mock::given(method("POST"))
+                .and(path("/xrpc/com.atproto.server.activateAccount"))
+                .and(header("authorization", format!("Bearer {refreshed}")))
+                .respond_with(ResponseTemplate::new(200))
+                .expect(1)
+                .mount(&destination)
+                .await;
+
+            let old_document = serde_json::json!({
+                "id": DID,
+                "alsoKnownAs": ["at://alice.example.com"],
+                "service": [{
+                    "id": "#atproto_pds",
+                    "type": "AtprotoPersonalDataServer",
+                    "serviceEndpoint": "https://origin.example.com",
+                }],
+            });
+            Mock::given(method("GET"))
+                .and(path("/.well-known/did.json"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(old_document))
+                .up_to_n_times(1)
+                .mount(&domain)
+                .await;
+
+            let staging_dir = std::env::temp_dir().join("pdsmigration-did-web-test");
+            let store = LocalStagingStore::new(&staging_dir);
+            let request = |timeout| MigrateDidWebRequest {
+                destination: destination.uri(),
+                did: DID.to_string(),
+                destination_token: token(-60),
+                destination_refresh_token: Some("destination-refresh".to_string()),
+                poll_interval_secs: 1,
+                publish_timeout_secs: timeout,
+            };
+            let resolver = IdentityResolver {
+                web_base: Some(domain.uri()),
+                retry: RetryPolicy::none(),
+                ..IdentityResolver::default()
+            };
+            let options = MigrationOptions {
+                retry: RetryPolicy::none(),
+                ..MigrationOptions::default()
+            };
+
+            // Until the user publishes the new document, the domain serves one with no key
+            let error = migrate_did_web_api(request(0), &store, &resolver, &options)
+                .await
+                .unwrap_err();
+            assert!(matches!(error, MigrationError::VerificationFailed { .. }));
+
+            let published: DidDocument =
+                serde_json::from_slice(&store.get(&did_web_document_key(DID)).await.unwrap())
+                    .unwrap();
+            Mock::given(method("GET"))
+                .and(path("/.well-known/did.json"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(&published))
+                .mount(&domain)
+                .await;
+
+            let did_doc = migrate_did_web_api(request(60), &store, &resolver, &options)
+                .await
+                .unwrap();
+            assert_eq!(did_doc, published);
+            assert_eq!(
+                did_doc
+                    .get_signing_key()
+                    .unwrap()
+                    .public_key_multibase
+                    .as_deref(),
+                Some(KEY)
+            );
+            let _ = std::fs::remove_dir_all(staging_dir);
+        });
+    }
+}
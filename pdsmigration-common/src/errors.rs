@@ -1,4 +1,6 @@
+use atrium_xrpc::error::{Error as AtriumError, ErrorResponseBody};
 use derive_more::{Display, Error};
+use serde::Serialize;
 
 #[derive(Debug, Display, Error)]
 pub enum MigrationError {
@@ -12,4 +14,188 @@ pub enum MigrationError {
     Runtime { message: String },
     #[display("Rate limit reached. Please try again later.")]
     RateLimitReached,
+    #[display("Invalid identifier or password")]
+    InvalidCredentials,
+    #[display("Account has been taken down")]
+    AccountTakedown { message: String },
+    /// The account signs in with a code sent by email; log in again with the code.
+    #[display("Auth factor token required")]
+    AuthFactorTokenRequired,
+    /// Stopped at a safe point because the caller canceled. `finished` lists the items that
+    /// completed before that.
+    #[display("Canceled after {} items finished", finished.len())]
+    Canceled { finished: Vec<String> },
+    /// The destination refused the handle: it is taken, malformed or not on a domain it serves.
+    #[display("Handle is not available: {upstream}")]
+    HandleUnavailable { upstream: XrpcFailure },
+    #[display("Invite code was rejected: {upstream}")]
+    InvalidInviteCode { upstream: XrpcFailure },
+    /// The destination already hosts an account for the DID.
+    #[display("Account already exists: {upstream}")]
+    AccountAlreadyExists { upstream: XrpcFailure },
+    /// The access token expired; refresh the session or log in again.
+    #[display("Token has expired: {upstream}")]
+    ExpiredToken { upstream: XrpcFailure },
+    #[display("Not found: {upstream}")]
+    NotFound { upstream: XrpcFailure },
+    /// Any other error a PDS answered an XRPC call with.
+    #[display("Upstream error: {upstream}")]
+    Xrpc { upstream: XrpcFailure },
+    /// The destination does not yet hold everything the origin does; `failed` describes each
+    /// check that did not pass.
+    #[display("Migration verification failed: {}", failed.join("; "))]
+    VerificationFailed { failed: Vec<String> },
+}
+
+/// A failed XRPC call as the PDS reported it.
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+#[display("{host} answered {status} {error}: {message}")]
+pub struct XrpcFailure {
+    pub host: String,
+    pub status: u16,
+    /// The XRPC error name, e.g. `HandleNotAvailable`, or the HTTP reason when the body had none.
+    pub error: String,
+    pub message: String,
+}
+
+impl XrpcFailure {
+    /// Reads the `{"error": ..., "message": ...}` body a PDS sends with a failed call.
+    pub fn from_body(host: &str, status: u16, body: &[u8]) -> Self {
+        let body = serde_json::from_slice::<ErrorResponseBody>(body).unwrap_or(ErrorResponseBody {
+            error: None,
+            message: None,
+        });
+        Self::new(host, status, body)
+    }
+
+    fn new(host: &str, status: u16, body: ErrorResponseBody) -> Self {
+        let error = body.error.unwrap_or_else(|| {
+            reqwest::StatusCode::from_u16(status)
+                .ok()
+                .and_then(|status| status.canonical_reason())
+                .unwrap_or("Unknown")
+                .to_string()
+        });
+        Self {
+            host: host.to_string(),
+            status,
+            error,
+            message: body.message.unwrap_or_default(),
+        }
+    }
+}
+
+impl MigrationError {
+    /// Picks the variant for a failed XRPC call from its error name, then its status.
+    pub fn from_xrpc(upstream: XrpcFailure) -> Self {
+        match (upstream.error.as_str(), upstream.status) {
+            ("HandleNotAvailable" | "UnsupportedDomain" | "InvalidHandle", _) => {
+                MigrationError::HandleUnavailable { upstream }
+            }
+            ("InvalidInviteCode", _) => MigrationError::InvalidInviteCode { upstream },
+            ("AlreadyExists" | "AccountAlreadyExists", _) => {
+                MigrationError::AccountAlreadyExists { upstream }
+            }
+            // The reference PDS reports an existing DID as a plain InvalidRequest
+            ("InvalidRequest", _) if upstream.message.to_lowercase().contains("already exists") => {
+                MigrationError::AccountAlreadyExists { upstream }
+            }
+            ("ExpiredToken", _) => MigrationError::ExpiredToken { upstream },
+            (_, 429) => MigrationError::RateLimitReached,
+            (error, status) if status == 404 || error.ends_with("NotFound") => {
+                MigrationError::NotFound { upstream }
+            }
+            (_, 401) => MigrationError::Authentication {
+                message: upstream.to_string(),
+            },
+            _ => MigrationError::Xrpc { upstream },
+        }
+    }
+
+    /// Converts the error of an XRPC call made through the agent against `host`.
+    pub fn from_atrium<E: Serialize + std::fmt::Debug + std::fmt::Display>(
+        host: &str,
+        error: AtriumError<E>,
+    ) -> Self {
+        match error {
+            AtriumError::XrpcResponse(response) => {
+                // Lexicon errors serialize to the same body shape the PDS sent
+                let body = response
+                    .error
+                    .and_then(|kind| serde_json::to_value(kind).ok())
+                    .and_then(|value| serde_json::from_value(value).ok())
+                    .unwrap_or(ErrorResponseBody {
+                        error: None,
+                        message: None,
+                    });
+                Self::from_xrpc(XrpcFailure::new(host, response.status.as_u16(), body))
+            }
+            AtriumError::Authentication(_) => MigrationError::Authentication {
+                message: error.to_string(),
+            },
+            _ => MigrationError::Upstream {
+                message: error.to_string(),
+            },
+        }
+    }
+
+    /// Converts an unsuccessful response to a raw request against `host`, reading its body.
+    pub async fn from_response(host: &str, response: reqwest::Response) -> Self {
+        let status = response.status().as_u16();
+        let body = response.bytes().await.unwrap_or_default();
+        Self::from_xrpc(XrpcFailure::from_body(host, status, &body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use atrium_xrpc::error::{XrpcError, XrpcErrorKind};
+    use bsky_sdk::api::com::atproto::server::create_account;
+
+    const HOST: &str = "https://pds.example.com";
+
+    #[test]
+    fn test_body_error_names_pick_variants() {
+        let error = MigrationError::from_xrpc(XrpcFailure::from_body(
+            HOST,
+            400,
+            br#"{"error":"InvalidInviteCode","message":"Provided invite code not available"}"#,
+        ));
+        let MigrationError::InvalidInviteCode { upstream } = error else {
+            panic!("expected InvalidInviteCode, got {error:?}");
+        };
+        assert_eq!(upstream.host, HOST);
+        assert_eq!(upstream.status, 400);
+        assert_eq!(upstream.message, "Provided invite code not available");
+
+        let error = MigrationError::from_xrpc(XrpcFailure::from_body(
+            HOST,
+            400,
+            br#"{"error":"InvalidRequest","message":"Account already exists"}"#,
+        ));
+        assert!(matches!(error, MigrationError::AccountAlreadyExists { .. }));
+
+        let error = MigrationError::from_xrpc(XrpcFailure::from_body(HOST, 404, b"not json"));
+        let MigrationError::NotFound { upstream } = error else {
+            panic!("expected NotFound, got {error:?}");
+        };
+        assert_eq!(upstream.error, "Not Found");
+    }
+
+    #[test]
+    fn test_atrium_lexicon_error_keeps_its_name() {
+        let error = AtriumError::XrpcResponse(XrpcError {
+            status: atrium_xrpc::http::StatusCode::BAD_REQUEST,
+            error: Some(XrpcErrorKind::Custom(
+                create_account::Error::HandleNotAvailable(Some("Handle already taken".to_string())),
+            )),
+        });
+        let error = MigrationError::from_atrium(HOST, error);
+        let MigrationError::HandleUnavailable { upstream } = error else {
+            panic!("expected HandleUnavailable, got {error:?}");
+        };
+        assert_eq!(upstream.error, "HandleNotAvailable");
+        assert_eq!(upstream.message, "Handle already taken");
+    }
 }
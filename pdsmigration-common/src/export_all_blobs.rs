@@ -1,12 +1,11 @@
-use crate::agent::{download_blob, list_all_blobs, login_helper};
-use crate::{build_agent, MigrationError};
+use crate::agent::{list_all_blobs, resume_session};
+use crate::{
+    build_agent, run_all_blob_transfers, stage_session_blob, MigrationError, MigrationOptions,
+    StagingStore,
+};
 use bsky_sdk::api::types::string::Did;
-use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
 use std::fmt;
-use std::io::ErrorKind;
-use std::time::Duration;
-use tokio::io::AsyncWriteExt;
 
 #[derive(Deserialize, Serialize)]
 pub struct GetBlobRequest {
@@ -30,6 +29,9 @@ pub struct ExportAllBlobsRequest {
     pub origin: String,
     pub did: String,
     pub origin_token: String,
+    /// Lets the session outlive its access token on long exports.
+    #[serde(default)]
+    pub origin_refresh_token: Option<String>,
 }
 
 impl fmt::Debug for ExportAllBlobsRequest {
@@ -38,6 +40,10 @@ impl fmt::Debug for ExportAllBlobsRequest {
             .field("origin", &self.origin)
             .field("did", &self.did)
             .field("origin_token", &"[REDACTED]")
+            .field(
+                "origin_refresh_token",
+                &self.origin_refresh_token.as_ref().map(|_| "[REDACTED]"),
+            )
             .finish()
     }
 }
@@ -46,101 +52,34 @@ impl fmt::Debug for ExportAllBlobsRequest {
 pub struct ExportAllBlobsResponse {
     pub successful_blobs: Vec<String>,
     pub failed_blobs: Vec<String>,
+    pub corrupt_blobs: Vec<String>,
 }
 
-#[tracing::instrument]
+#[tracing::instrument(skip(store, options))]
 pub async fn export_all_blobs_api(
     req: ExportAllBlobsRequest,
+    store: &dyn StagingStore,
+    options: &MigrationOptions,
 ) -> Result<ExportAllBlobsResponse, MigrationError> {
-    let agent = build_agent().await?;
-    let session = login_helper(
+    let agent = build_agent(&options.retry).await?;
+    resume_session(
         &agent,
         req.origin.as_str(),
         req.did.as_str(),
         req.origin_token.as_str(),
+        req.origin_refresh_token.as_deref(),
     )
     .await?;
     let blobs = list_all_blobs(&agent).await?;
-    let mut path = std::env::current_dir().unwrap();
-    path.push(session.did.as_str().replace(":", "-"));
-    match tokio::fs::create_dir(path.as_path()).await {
-        Ok(_) => {}
-        Err(e) => {
-            if e.kind() != ErrorKind::AlreadyExists {
-                tracing::error!("Error creating directory: {:?}", e);
-                return Err(MigrationError::Runtime {
-                    message: e.to_string(),
-                });
-            }
-        }
-    }
-
-    let mut successful_blobs = vec![];
-    let mut failed_blobs = vec![];
-    for blob in &blobs {
-        let session = agent.get_session().await.unwrap();
-        let mut filepath = std::env::current_dir().unwrap();
-        filepath.push(session.did.as_str().replace(":", "-"));
-        filepath.push(
-            format!("{blob:?}")
-                .strip_prefix("Cid(Cid(")
-                .unwrap()
-                .strip_suffix("))")
-                .unwrap(),
-        );
-        if !tokio::fs::try_exists(filepath).await.unwrap() {
-            let get_blob_request = GetBlobRequest {
-                did: session.did.clone(),
-                cid: format!("{blob:?}")
-                    .strip_prefix("Cid(Cid(")
-                    .unwrap()
-                    .strip_suffix("))")
-                    .unwrap()
-                    .to_string(),
-                token: session.access_jwt.clone(),
-            };
-            match download_blob(agent.get_endpoint().await.as_str(), &get_blob_request).await {
-                Ok(mut stream) => {
-                    tracing::info!("Successfully fetched missing blob");
-                    let mut path = std::env::current_dir().unwrap();
-                    path.push(session.did.as_str().replace(":", "-"));
-                    path.push(
-                        format!("{blob:?}")
-                            .strip_prefix("Cid(Cid(")
-                            .unwrap()
-                            .strip_suffix("))")
-                            .unwrap(),
-                    );
-                    let mut file = tokio::fs::File::create(path.as_path()).await.unwrap();
-
-                    while let Some(chunk) = stream.next().await {
-                        let chunk = chunk.unwrap();
-                        file.write_all(&chunk).await.unwrap();
-                    }
-
-                    file.flush().await.unwrap();
-                    successful_blobs.push(format!("{blob:?}"));
-                }
-                Err(e) => {
-                    match e {
-                        MigrationError::RateLimitReached => {
-                            tracing::error!("Rate limit reached, waiting 5 minutes");
-                            let five_minutes = Duration::from_secs(300);
-                            tokio::time::sleep(five_minutes).await;
-                        }
-                        _ => {
-                            //todo
-                        }
-                    }
-                    tracing::error!("Failed to determine missing blobs");
-                    failed_blobs.push(format!("{blob:?}"));
-                }
-            }
-        }
-    }
 
+    let cids = blobs.iter().map(|blob| blob.as_ref().to_string()).collect();
+    let report = run_all_blob_transfers(cids, options, |cid| {
+        stage_session_blob(&agent, store, cid, options)
+    })
+    .await?;
     Ok(ExportAllBlobsResponse {
-        successful_blobs,
-        failed_blobs,
+        successful_blobs: report.successful_blobs,
+        failed_blobs: report.invalid_blobs,
+        corrupt_blobs: report.corrupt_blobs,
     })
 }
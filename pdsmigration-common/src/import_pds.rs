@@ -1,5 +1,8 @@
 use crate::agent::{account_import, login_helper};
-use crate::{build_agent, MigrationError};
+use crate::{
+    build_agent, repo_key, repo_rev_key, CarFile, MigrationError, MigrationOptions, RepoCommit,
+    StagingStore,
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -9,9 +12,13 @@ pub struct ImportPDSRequest {
     pub token: String,
 }
 
-#[tracing::instrument(skip(req))]
-pub async fn import_pds_api(req: ImportPDSRequest) -> Result<(), MigrationError> {
-    let agent = build_agent().await?;
+#[tracing::instrument(skip(req, store, options))]
+pub async fn import_pds_api(
+    req: ImportPDSRequest,
+    store: &dyn StagingStore,
+    options: &MigrationOptions,
+) -> Result<(), MigrationError> {
+    let agent = build_agent(&options.retry).await?;
     let session = login_helper(
         &agent,
         req.pds_host.as_str(),
@@ -19,10 +26,15 @@ pub async fn import_pds_api(req: ImportPDSRequest) -> Result<(), MigrationError>
         req.token.as_str(),
     )
     .await?;
-    account_import(
-        &agent,
-        (session.did.as_str().to_string().replace(":", "-") + ".car").as_str(),
-    )
-    .await?;
+    let repo = store.get(&repo_key(session.did.as_str())).await?;
+    // Catch truncated or corrupted exports before the destination PDS sees them
+    let car = CarFile::parse(&repo)?;
+    tracing::info!("Validated repository with {} blocks", car.blocks.len());
+    let commit = RepoCommit::from_car(&car)?;
+    account_import(&agent, repo).await?;
+    // Remember which rev the destination has so a later catch-up only fetches newer records
+    store
+        .put(&repo_rev_key(session.did.as_str()), commit.rev.into_bytes())
+        .await?;
     Ok(())
 }
@@ -5,40 +5,88 @@ use serde::{Deserialize, Serialize};
 
 mod activate_account;
 mod agent;
+mod blob_verify;
+mod car;
+mod catch_up;
 mod create_account;
 mod deactivate_account;
+mod did_web;
 mod errors;
 mod export_all_blobs;
 mod export_blobs;
 mod export_pds;
 mod import_pds;
+mod login;
 mod migrate_plc;
 mod migrate_preferences;
+mod migration_plan;
 mod missing_blobs;
+mod oauth;
+mod options;
+mod plc_audit;
+mod plc_directory;
+mod plc_preview;
+mod plc_recovery;
+mod plc_signing;
+mod preflight;
+mod progress;
+mod rate_limit;
+mod repo_inspect;
 mod request_token;
+mod resolver;
+mod retry;
 mod service_auth;
+mod staging;
+mod transfer;
+mod transfer_blobs;
 mod upload_blobs;
+mod verify_migration;
 
 pub use activate_account::*;
 pub use agent::*;
+pub use blob_verify::*;
+pub use car::*;
+pub use catch_up::*;
 pub use create_account::*;
 pub use deactivate_account::*;
+pub use did_web::*;
 pub use errors::*;
 pub use export_all_blobs::*;
 pub use export_blobs::*;
 pub use export_pds::*;
 pub use import_pds::*;
+pub use login::*;
 pub use migrate_plc::*;
 pub use migrate_preferences::*;
+pub use migration_plan::*;
 pub use missing_blobs::*;
+pub use oauth::*;
+pub use options::*;
+pub use plc_audit::*;
+pub use plc_directory::*;
+pub use plc_preview::*;
+pub use plc_recovery::*;
+pub use plc_signing::*;
+pub use preflight::*;
+pub use progress::*;
+pub use rate_limit::*;
+pub use repo_inspect::*;
 pub use request_token::*;
+pub use resolver::*;
+pub use retry::*;
 pub use service_auth::*;
+pub use staging::*;
+pub use transfer::*;
+pub use transfer_blobs::*;
 pub use upload_blobs::*;
+pub use verify_migration::*;
 
 #[derive(Deserialize, Serialize)]
 pub struct GetRepoRequest {
     pub did: Did,
     pub token: String,
+    /// Only return blocks written after this repo rev.
+    pub since: Option<String>,
 }
 
 impl std::fmt::Debug for GetRepoRequest {
@@ -46,6 +94,7 @@ impl std::fmt::Debug for GetRepoRequest {
         f.debug_struct("GetRepoRequest")
             .field("did", &self.did)
             .field("token", &"[REDACTED]")
+            .field("since", &self.since)
             .finish()
     }
 }
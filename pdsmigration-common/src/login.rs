@@ -0,0 +1,219 @@
+use crate::{MigrationAgent, MigrationError};
+use atrium_xrpc::error::XrpcErrorKind;
+use bsky_sdk::api::agent::atp_agent::AtpSession;
+use bsky_sdk::api::agent::Configure;
+use bsky_sdk::api::com::atproto::server::create_session;
+use bsky_sdk::api::xrpc::Error;
+use ipld_core::ipld::Ipld;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Deserialize, Serialize)]
+pub struct LoginRequest {
+    pub pds_host: String,
+    /// Handle, DID or email of the account.
+    pub identifier: String,
+    pub password: String,
+    /// The code the PDS emailed after answering with
+    /// [`MigrationError::AuthFactorTokenRequired`].
+    #[serde(default)]
+    pub auth_factor_token: Option<String>,
+    /// Log in to a taken-down account with a narrowly scoped token, e.g. to migrate away.
+    #[serde(default)]
+    pub allow_takendown: bool,
+}
+
+impl fmt::Debug for LoginRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoginRequest")
+            .field("pds_host", &self.pds_host)
+            .field("identifier", &self.identifier)
+            .field("password", &"[REDACTED]")
+            .field(
+                "auth_factor_token",
+                &self.auth_factor_token.as_ref().map(|_| "[REDACTED]"),
+            )
+            .field("allow_takendown", &self.allow_takendown)
+            .finish()
+    }
+}
+
+/// Creates a session on `req.pds_host` with the account's password.
+///
+/// Accounts with email 2FA first fail with [`MigrationError::AuthFactorTokenRequired`];
+/// send the request again with the emailed `auth_factor_token`. The returned session is
+/// not stored on `agent`; hand its tokens to [`resume_session`](crate::resume_session).
+#[tracing::instrument(skip(agent))]
+pub async fn login_with_password(
+    agent: &MigrationAgent,
+    req: &LoginRequest,
+) -> Result<AtpSession, MigrationError> {
+    if req.identifier.trim().is_empty() {
+        return Err(MigrationError::Validation {
+            field: "identifier".to_string(),
+        });
+    }
+    if req.password.is_empty() {
+        return Err(MigrationError::Validation {
+            field: "password".to_string(),
+        });
+    }
+    agent.configure_endpoint(req.pds_host.clone());
+    let output = agent
+        .api
+        .com
+        .atproto
+        .server
+        .create_session(create_session::Input {
+            data: create_session::InputData {
+                allow_takendown: req.allow_takendown.then_some(true),
+                auth_factor_token: req.auth_factor_token.clone(),
+                identifier: req.identifier.clone(),
+                password: req.password.clone(),
+            },
+            extra_data: Ipld::Null,
+        })
+        .await
+        .map_err(|error| {
+            tracing::error!("Error while logging in: {}", error);
+            login_error(&req.pds_host, error)
+        })?;
+    Ok(AtpSession {
+        data: output.data,
+        extra_data: Ipld::Null,
+    })
+}
+
+fn login_error(pds_host: &str, error: Error<create_session::Error>) -> MigrationError {
+    let Error::XrpcResponse(ref response) = error else {
+        return MigrationError::from_atrium(pds_host, error);
+    };
+    match &response.error {
+        Some(XrpcErrorKind::Custom(create_session::Error::AuthFactorTokenRequired(_))) => {
+            MigrationError::AuthFactorTokenRequired
+        }
+        Some(XrpcErrorKind::Custom(create_session::Error::AccountTakedown(message))) => {
+            MigrationError::AccountTakedown {
+                message: message.clone().unwrap_or_default(),
+            }
+        }
+        Some(XrpcErrorKind::Undefined(body))
+            if body.error.as_deref() == Some("AuthenticationRequired") =>
+        {
+            MigrationError::InvalidCredentials
+        }
+        _ => MigrationError::from_atrium(pds_host, error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{build_agent, RetryPolicy};
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn request(pds_host: String, auth_factor_token: Option<&str>) -> LoginRequest {
+        LoginRequest {
+            pds_host,
+            identifier: "alice.example.com".to_string(),
+            password: "hunter2".to_string(),
+            auth_factor_token: auth_factor_token.map(str::to_string),
+            allow_takendown: false,
+        }
+    }
+
+    async fn login_answering(
+        status: u16,
+        body: serde_json::Value,
+    ) -> Result<AtpSession, MigrationError> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.server.createSession"))
+            .respond_with(ResponseTemplate::new(status).set_body_json(body))
+            .mount(&server)
+            .await;
+        let agent = build_agent(&RetryPolicy::none()).await.unwrap();
+        login_with_password(&agent, &request(server.uri(), None)).await
+    }
+
+    #[test]
+    fn test_login_requires_auth_factor_token() {
+        tokio_test::block_on(async {
+            let result = login_answering(
+                401,
+                serde_json::json!({ "error": "AuthFactorTokenRequired", "message": "A sign in code has been sent to your email address" }),
+            )
+            .await;
+            assert!(matches!(
+                result,
+                Err(MigrationError::AuthFactorTokenRequired)
+            ));
+        });
+    }
+
+    #[test]
+    fn test_login_maps_invalid_credentials() {
+        tokio_test::block_on(async {
+            let result = login_answering(
+                401,
+                serde_json::json!({ "error": "AuthenticationRequired", "message": "Invalid identifier or password" }),
+            )
+            .await;
+            assert!(matches!(result, Err(MigrationError::InvalidCredentials)));
+        });
+    }
+
+    #[test]
+    fn test_login_maps_takedown() {
+        tokio_test::block_on(async {
+            let result = login_answering(
+                401,
+                serde_json::json!({ "error": "AccountTakedown", "message": "Account has been taken down" }),
+            )
+            .await;
+            assert!(matches!(
+                result,
+                Err(MigrationError::AccountTakedown { .. })
+            ));
+        });
+    }
+
+    #[test]
+    fn test_login_sends_auth_factor_token() {
+        tokio_test::block_on(async {
+            let server = MockServer::start().await;
+            Mock::given(method("POST"))
+                .and(path("/xrpc/com.atproto.server.createSession"))
+                .and(body_partial_json(
+                    serde_json::json!({ "authFactorToken": "ABCDE-12345" }),
+                ))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "accessJwt": "access",
+                    "refreshJwt": "refresh",
+                    "did": "did:plc:example123",
+                    "handle": "alice.example.com",
+                })))
+                .expect(1)
+                .mount(&server)
+                .await;
+            let agent = build_agent(&RetryPolicy::none()).await.unwrap();
+            let session = login_with_password(&agent, &request(server.uri(), Some("ABCDE-12345")))
+                .await
+                .unwrap();
+            assert_eq!(session.refresh_jwt, "refresh");
+            assert_eq!(session.did.as_str(), "did:plc:example123");
+        });
+    }
+
+    #[test]
+    fn test_login_request_redacts_secrets() {
+        let debug_output = format!(
+            "{:?}",
+            request("https://pds.example.com".to_string(), Some("ABCDE-12345"))
+        );
+        assert!(!debug_output.contains("hunter2"));
+        assert!(!debug_output.contains("ABCDE-12345"));
+        assert!(debug_output.contains("alice.example.com"));
+    }
+}
@@ -1,4 +1,8 @@
-use crate::{build_agent, login_helper, recommended_plc, sign_plc, submit_plc, MigrationError};
+use crate::{
+    build_agent, login_helper, recommended_plc, sign_plc, submit_plc, MigrationError,
+    MigrationOptions, RecommendedDidOutputData,
+};
+use bsky_sdk::api::com::atproto::identity::sign_plc_operation::InputData;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -13,9 +17,20 @@ pub struct MigratePlcRequest {
     pub user_recovery_key: Option<String>,
 }
 
-#[tracing::instrument(skip(req))]
-pub async fn migrate_plc_api(req: MigratePlcRequest) -> Result<(), MigrationError> {
-    let agent = build_agent().await?;
+#[tracing::instrument(skip(req, options))]
+pub async fn migrate_plc_api(
+    req: MigratePlcRequest,
+    options: &MigrationOptions,
+) -> Result<(), MigrationError> {
+    options.check_canceled()?;
+    if !req.did.starts_with("did:plc:") {
+        // A did:web is updated by publishing a new did.json, see `migrate_did_web_api`
+        tracing::error!("{} is not a did:plc", req.did);
+        return Err(MigrationError::Validation {
+            field: "did".to_string(),
+        });
+    }
+    let agent = build_agent(&options.retry).await?;
     login_helper(
         &agent,
         req.destination.as_str(),
@@ -24,21 +39,11 @@ pub async fn migrate_plc_api(req: MigratePlcRequest) -> Result<(), MigrationErro
     )
     .await?;
     let recommended_did = recommended_plc(&agent).await?;
-    use bsky_sdk::api::com::atproto::identity::sign_plc_operation::InputData;
-
-    let mut rotation_keys = recommended_did.rotation_keys.unwrap();
-
-    if let Some(recovery_key) = &req.user_recovery_key {
-        rotation_keys.insert(0, recovery_key.clone());
-    }
-
-    let new_plc = InputData {
-        also_known_as: recommended_did.also_known_as,
-        rotation_keys: Some(rotation_keys),
-        services: recommended_did.services,
-        token: Some(req.plc_signing_token.clone()),
-        verification_methods: recommended_did.verification_methods,
-    };
+    let new_plc = proposed_plc(
+        recommended_did,
+        req.user_recovery_key.clone(),
+        Some(req.plc_signing_token.clone()),
+    );
     login_helper(
         &agent,
         req.origin.as_str(),
@@ -46,6 +51,9 @@ pub async fn migrate_plc_api(req: MigratePlcRequest) -> Result<(), MigrationErro
         req.origin_token.as_str(),
     )
     .await?;
+    // Last safe point: once the origin has signed the operation it is always submitted, so
+    // the DID is never left with a signed but unpublished update.
+    options.check_canceled()?;
     let output = sign_plc(&agent, new_plc.clone()).await?;
     login_helper(
         &agent,
@@ -57,3 +65,23 @@ pub async fn migrate_plc_api(req: MigratePlcRequest) -> Result<(), MigrationErro
     submit_plc(&agent, output).await?;
     Ok(())
 }
+
+/// The operation the destination recommends, with the user's recovery key taking precedence
+/// over the destination's own rotation keys.
+pub(crate) fn proposed_plc(
+    recommended_did: RecommendedDidOutputData,
+    user_recovery_key: Option<String>,
+    token: Option<String>,
+) -> InputData {
+    let mut rotation_keys = recommended_did.rotation_keys.unwrap_or_default();
+    if let Some(recovery_key) = user_recovery_key {
+        rotation_keys.insert(0, recovery_key);
+    }
+    InputData {
+        also_known_as: recommended_did.also_known_as,
+        rotation_keys: Some(rotation_keys),
+        services: recommended_did.services,
+        token,
+        verification_methods: recommended_did.verification_methods,
+    }
+}
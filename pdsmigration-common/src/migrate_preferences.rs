@@ -1,4 +1,7 @@
-use crate::{build_agent, export_preferences, import_preferences, login_helper, MigrationError};
+use crate::{
+    build_agent, export_preferences, import_preferences, login_helper, MigrationError,
+    MigrationOptions,
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -10,9 +13,12 @@ pub struct MigratePreferencesRequest {
     pub origin_token: String,
 }
 
-#[tracing::instrument]
-pub async fn migrate_preferences_api(req: MigratePreferencesRequest) -> Result<(), MigrationError> {
-    let agent = build_agent().await?;
+#[tracing::instrument(skip(options))]
+pub async fn migrate_preferences_api(
+    req: MigratePreferencesRequest,
+    options: &MigrationOptions,
+) -> Result<(), MigrationError> {
+    let agent = build_agent(&options.retry).await?;
     login_helper(
         &agent,
         req.origin.as_str(),
@@ -0,0 +1,942 @@
+use crate::agent::{get_service_auth, login_helper};
+use crate::did_web::{default_poll_interval_secs, default_publish_timeout_secs};
+use crate::{
+    activate_account, await_did_web_api, build_agent, catch_up_api, create_account,
+    deactivate_account_api, did_web_document_key, export_blobs_api, export_pds_api, import_pds_api,
+    migrate_plc_api, migrate_preferences_api, prepare_did_web_api, request_token_api,
+    transfer_blobs_api, upload_blobs_api, CatchUpRequest, CreateAccountRequest,
+    DeactivateAccountRequest, ExportBlobsRequest, ExportPDSRequest, IdentityResolver,
+    ImportPDSRequest, MigrateDidWebRequest, MigratePlcRequest, MigratePreferencesRequest,
+    MigrationError, MigrationOptions, RequestTokenRequest, StagingStore, TransferBlobsRequest,
+    UploadBlobsRequest,
+};
+use bsky_sdk::api::agent::atp_agent::AtpSession;
+use bsky_sdk::api::agent::Configure;
+use bsky_sdk::api::did_doc::DidDocument;
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// A single step of a PDS to PDS migration, listed in the order they are run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationStep {
+    CreateAccount,
+    ExportRepo,
+    ImportRepo,
+    ExportBlobs,
+    UploadBlobs,
+    MigratePreferences,
+    RequestPlcToken,
+    CatchUpRepo,
+    MigratePlc,
+    ActivateAccount,
+    DeactivateAccount,
+}
+
+impl MigrationStep {
+    pub const ALL: [MigrationStep; 11] = [
+        MigrationStep::CreateAccount,
+        MigrationStep::ExportRepo,
+        MigrationStep::ImportRepo,
+        MigrationStep::ExportBlobs,
+        MigrationStep::UploadBlobs,
+        MigrationStep::MigratePreferences,
+        MigrationStep::RequestPlcToken,
+        MigrationStep::CatchUpRepo,
+        MigrationStep::MigratePlc,
+        MigrationStep::ActivateAccount,
+        MigrationStep::DeactivateAccount,
+    ];
+}
+
+/// Progress of a migration, persisted after every step so an interrupted run can resume.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MigrationCheckpoint {
+    pub did: String,
+    pub completed_steps: Vec<MigrationStep>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failed_step: Option<MigrationStep>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+impl MigrationCheckpoint {
+    pub fn new(did: &str) -> Self {
+        Self {
+            did: did.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_completed(&self, step: MigrationStep) -> bool {
+        self.completed_steps.contains(&step)
+    }
+
+    pub fn mark_completed(&mut self, step: MigrationStep) {
+        if !self.is_completed(step) {
+            self.completed_steps.push(step);
+        }
+        if self.failed_step == Some(step) {
+            self.failed_step = None;
+            self.last_error = None;
+        }
+    }
+
+    pub fn mark_failed(&mut self, step: MigrationStep, error: &MigrationError) {
+        self.failed_step = Some(step);
+        self.last_error = Some(error.to_string());
+    }
+
+    #[tracing::instrument]
+    pub async fn load(path: &Path) -> Result<Option<Self>, MigrationError> {
+        match tokio::fs::read(path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes).map(Some).map_err(|error| {
+                tracing::error!("Failed to parse checkpoint: {}", error);
+                MigrationError::Runtime {
+                    message: format!("Invalid checkpoint file {}: {}", path.display(), error),
+                }
+            }),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => {
+                tracing::error!("Failed to read checkpoint: {}", error);
+                Err(MigrationError::Runtime {
+                    message: format!("Failed to read checkpoint {}: {}", path.display(), error),
+                })
+            }
+        }
+    }
+
+    /// Writes the checkpoint next to its final location first and renames it into place, so a
+    /// crash mid-write never leaves a truncated checkpoint behind.
+    #[tracing::instrument(skip(self))]
+    pub async fn save(&self, path: &Path) -> Result<(), MigrationError> {
+        let bytes = serde_json::to_vec_pretty(self).map_err(|error| MigrationError::Runtime {
+            message: error.to_string(),
+        })?;
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        tokio::fs::write(&tmp_path, bytes).await.map_err(|error| {
+            tracing::error!("Failed to write checkpoint: {}", error);
+            MigrationError::Runtime {
+                message: format!("Failed to write checkpoint {}: {}", path.display(), error),
+            }
+        })?;
+        tokio::fs::rename(&tmp_path, path).await.map_err(|error| {
+            tracing::error!("Failed to move checkpoint into place: {}", error);
+            MigrationError::Runtime {
+                message: format!("Failed to write checkpoint {}: {}", path.display(), error),
+            }
+        })
+    }
+}
+
+/// Details for the account created on the destination PDS.
+#[derive(Deserialize, Serialize)]
+pub struct MigrationAccount {
+    pub email: String,
+    pub handle: String,
+    pub password: String,
+    pub invite_code: Option<String>,
+}
+
+impl std::fmt::Debug for MigrationAccount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MigrationAccount")
+            .field("email", &self.email)
+            .field("handle", &self.handle)
+            .field("password", &"[REDACTED]")
+            .field(
+                "invite_code",
+                &self.invite_code.as_ref().map(|_| "[REDACTED]"),
+            )
+            .finish()
+    }
+}
+
+/// Runs a full migration from `origin` to `destination` in order, recording a
+/// [`MigrationCheckpoint`] at `checkpoint_path` after every step.
+///
+/// Re-running a plan with the same checkpoint skips the steps that already completed and
+/// continues from the one that failed. When `new_account` is `None` the destination account is
+/// assumed to exist already and `destination_token` must be set. The PLC signing token is only
+/// known once the email sent by [`MigrationStep::RequestPlcToken`] arrives, so a first run stops
+/// at [`MigrationStep::CatchUpRepo`] until the plan is re-run with `plc_signing_token` filled in.
+/// Holding the catch-up back until then means records written while waiting for the email are
+/// still copied before the PLC operation switches the DID over.
+///
+/// A `did:web` has no PLC operation: [`MigrationStep::RequestPlcToken`] writes the new
+/// `did.json` to the staging store for the user to publish on their domain, and
+/// [`MigrationStep::MigratePlc`] waits until the domain serves it. No signing token is needed.
+#[derive(Deserialize, Serialize)]
+pub struct MigrationPlan {
+    pub did: String,
+    pub origin: String,
+    pub origin_token: String,
+    pub destination: String,
+    pub destination_token: Option<String>,
+    pub new_account: Option<MigrationAccount>,
+    pub plc_signing_token: Option<String>,
+    pub user_recovery_key: Option<String>,
+    pub checkpoint_path: PathBuf,
+}
+
+impl std::fmt::Debug for MigrationPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MigrationPlan")
+            .field("did", &self.did)
+            .field("origin", &self.origin)
+            .field("origin_token", &"[REDACTED]")
+            .field("destination", &self.destination)
+            .field(
+                "destination_token",
+                &self.destination_token.as_ref().map(|_| "[REDACTED]"),
+            )
+            .field("new_account", &self.new_account)
+            .field(
+                "plc_signing_token",
+                &self.plc_signing_token.as_ref().map(|_| "[REDACTED]"),
+            )
+            .field("user_recovery_key", &self.user_recovery_key)
+            .field("checkpoint_path", &self.checkpoint_path)
+            .finish()
+    }
+}
+
+impl MigrationPlan {
+    /// The steps this plan runs, in order.
+    pub fn steps(&self) -> Vec<MigrationStep> {
+        MigrationStep::ALL
+            .into_iter()
+            .filter(|step| *step != MigrationStep::CreateAccount || self.new_account.is_some())
+            .collect()
+    }
+
+    /// The steps still left to run given a previously saved checkpoint.
+    pub fn pending_steps(&self, checkpoint: &MigrationCheckpoint) -> Vec<MigrationStep> {
+        self.steps()
+            .into_iter()
+            .filter(|step| !checkpoint.is_completed(*step))
+            .collect()
+    }
+
+    /// Loads the existing checkpoint for this plan, or starts a fresh one.
+    pub async fn checkpoint(&self) -> Result<MigrationCheckpoint, MigrationError> {
+        match MigrationCheckpoint::load(&self.checkpoint_path).await? {
+            Some(checkpoint) => {
+                if checkpoint.did != self.did {
+                    tracing::error!("Checkpoint belongs to {}, not {}", checkpoint.did, self.did);
+                    return Err(MigrationError::Validation {
+                        field: "checkpoint_path".to_string(),
+                    });
+                }
+                Ok(checkpoint)
+            }
+            None => Ok(MigrationCheckpoint::new(&self.did)),
+        }
+    }
+
+    /// Runs the pending steps, staging the exported repo and blobs in `store`. A resumed run needs
+    /// a store holding the exports of the earlier run. `resolver` reads the published DID
+    /// document while waiting for a `did:web` and when the migration is verified.
+    #[tracing::instrument(skip(self, store, resolver, options), fields(did = %self.did))]
+    pub async fn run(
+        &self,
+        store: &dyn StagingStore,
+        resolver: &IdentityResolver,
+        options: &MigrationOptions,
+    ) -> Result<MigrationCheckpoint, MigrationError> {
+        let mut checkpoint = self.checkpoint().await?;
+        let mut destination_token = self.destination_token.clone();
+        for step in self.pending_steps(&checkpoint) {
+            if options.cancel.is_cancelled() {
+                tracing::info!("Migration canceled before step {:?}", step);
+                return Err(MigrationError::Canceled {
+                    finished: checkpoint
+                        .completed_steps
+                        .iter()
+                        .map(|step| format!("{step:?}"))
+                        .collect(),
+                });
+            }
+            tracing::info!("Running migration step {:?}", step);
+            match self
+                .run_step(step, store, resolver, options, &mut destination_token)
+                .await
+            {
+                Ok(_) => {
+                    checkpoint.mark_completed(step);
+                    checkpoint.save(&self.checkpoint_path).await?;
+                }
+                Err(error) => {
+                    tracing::error!("Migration step {:?} failed: {}", step, error);
+                    checkpoint.mark_failed(step, &error);
+                    checkpoint.save(&self.checkpoint_path).await?;
+                    return Err(error);
+                }
+            }
+        }
+        tracing::info!("Migration completed");
+        Ok(checkpoint)
+    }
+
+    async fn run_step(
+        &self,
+        step: MigrationStep,
+        store: &dyn StagingStore,
+        resolver: &IdentityResolver,
+        options: &MigrationOptions,
+        destination_token: &mut Option<String>,
+    ) -> Result<(), MigrationError> {
+        match step {
+            MigrationStep::CreateAccount => {
+                *destination_token = Some(self.create_destination_account(options).await?);
+                Ok(())
+            }
+            MigrationStep::ExportRepo => {
+                export_pds_api(
+                    ExportPDSRequest {
+                        pds_host: self.origin.clone(),
+                        did: self.did.clone(),
+                        token: self.origin_token.clone(),
+                    },
+                    store,
+                    options,
+                )
+                .await
+            }
+            MigrationStep::ImportRepo => {
+                import_pds_api(
+                    ImportPDSRequest {
+                        pds_host: self.destination.clone(),
+                        did: self.did.clone(),
+                        token: self.destination_token(destination_token, options).await?,
+                    },
+                    store,
+                    options,
+                )
+                .await
+            }
+            MigrationStep::ExportBlobs if options.stream_blobs => {
+                let response = transfer_blobs_api(
+                    TransferBlobsRequest {
+                        origin: self.origin.clone(),
+                        destination: self.destination.clone(),
+                        did: self.did.clone(),
+                        origin_token: self.origin_token.clone(),
+                        destination_token: self
+                            .destination_token(destination_token, options)
+                            .await?,
+                        origin_refresh_token: None,
+                        destination_refresh_token: None,
+                    },
+                    options,
+                )
+                .await?;
+                check_blob_results("transfer", &response.invalid_blobs, &response.corrupt_blobs)
+            }
+            // Streamed blobs already reached the destination during ExportBlobs
+            MigrationStep::UploadBlobs if options.stream_blobs => Ok(()),
+            MigrationStep::ExportBlobs => {
+                let response = export_blobs_api(
+                    ExportBlobsRequest {
+                        destination: self.destination.clone(),
+                        origin: self.origin.clone(),
+                        did: self.did.clone(),
+                        origin_token: self.origin_token.clone(),
+                        destination_token: self
+                            .destination_token(destination_token, options)
+                            .await?,
+                        origin_refresh_token: None,
+                    },
+                    store,
+                    options,
+                )
+                .await?;
+                check_blob_results("export", &response.invalid_blobs, &response.corrupt_blobs)
+            }
+            MigrationStep::UploadBlobs => {
+                let response = upload_blobs_api(
+                    UploadBlobsRequest {
+                        pds_host: self.destination.clone(),
+                        did: self.did.clone(),
+                        token: self.destination_token(destination_token, options).await?,
+                        refresh_token: None,
+                    },
+                    store,
+                    options,
+                )
+                .await?;
+                check_blob_results("upload", &response.invalid_blobs, &response.corrupt_blobs)
+            }
+            MigrationStep::MigratePreferences => {
+                migrate_preferences_api(
+                    MigratePreferencesRequest {
+                        destination: self.destination.clone(),
+                        destination_token: self
+                            .destination_token(destination_token, options)
+                            .await?,
+                        origin: self.origin.clone(),
+                        did: self.did.clone(),
+                        origin_token: self.origin_token.clone(),
+                    },
+                    options,
+                )
+                .await
+            }
+            MigrationStep::RequestPlcToken if self.is_did_web() => {
+                // No PLC operation to sign; the user publishes the written did.json instead
+                let token = self.destination_token(destination_token, options).await?;
+                prepare_did_web_api(&self.did_web_request(token), store, options).await?;
+                Ok(())
+            }
+            MigrationStep::RequestPlcToken => {
+                request_token_api(
+                    RequestTokenRequest {
+                        pds_host: self.origin.clone(),
+                        did: self.did.clone(),
+                        token: self.origin_token.clone(),
+                    },
+                    options,
+                )
+                .await
+            }
+            MigrationStep::CatchUpRepo => {
+                if !self.is_did_web() {
+                    self.plc_signing_token()?;
+                }
+                catch_up_api(
+                    CatchUpRequest {
+                        origin: self.origin.clone(),
+                        destination: self.destination.clone(),
+                        did: self.did.clone(),
+                        origin_token: self.origin_token.clone(),
+                        destination_token: self
+                            .destination_token(destination_token, options)
+                            .await?,
+                        since: None,
+                    },
+                    store,
+                    options,
+                )
+                .await?;
+                Ok(())
+            }
+            MigrationStep::MigratePlc if self.is_did_web() => {
+                let token = self.destination_token(destination_token, options).await?;
+                let bytes = store.get(&did_web_document_key(&self.did)).await?;
+                let expected: DidDocument =
+                    serde_json::from_slice(&bytes).map_err(|error| MigrationError::Runtime {
+                        message: error.to_string(),
+                    })?;
+                await_did_web_api(&self.did_web_request(token), &expected, resolver, options).await
+            }
+            MigrationStep::MigratePlc => {
+                let plc_signing_token = self.plc_signing_token()?;
+                migrate_plc_api(
+                    MigratePlcRequest {
+                        destination: self.destination.clone(),
+                        destination_token: self
+                            .destination_token(destination_token, options)
+                            .await?,
+                        origin: self.origin.clone(),
+                        did: self.did.clone(),
+                        origin_token: self.origin_token.clone(),
+                        plc_signing_token,
+                        user_recovery_key: self.user_recovery_key.clone(),
+                    },
+                    options,
+                )
+                .await
+            }
+            MigrationStep::ActivateAccount => {
+                let token = self.destination_token(destination_token, options).await?;
+                activate_account(
+                    self.destination.as_str(),
+                    self.did.as_str(),
+                    token.as_str(),
+                    options,
+                )
+                .await
+            }
+            MigrationStep::DeactivateAccount => {
+                let token = self.destination_token(destination_token, options).await?;
+                deactivate_account_api(
+                    DeactivateAccountRequest {
+                        pds_host: self.origin.clone(),
+                        did: self.did.clone(),
+                        token: self.origin_token.clone(),
+                        destination: Some(self.destination.clone()),
+                        destination_token: Some(token),
+                        skip_verification: false,
+                    },
+                    resolver,
+                    options,
+                )
+                .await
+            }
+        }
+    }
+
+    fn is_did_web(&self) -> bool {
+        self.did.starts_with("did:web:")
+    }
+
+    fn did_web_request(&self, destination_token: String) -> MigrateDidWebRequest {
+        MigrateDidWebRequest {
+            destination: self.destination.clone(),
+            did: self.did.clone(),
+            destination_token,
+            destination_refresh_token: None,
+            poll_interval_secs: default_poll_interval_secs(),
+            publish_timeout_secs: default_publish_timeout_secs(),
+        }
+    }
+
+    fn plc_signing_token(&self) -> Result<String, MigrationError> {
+        match &self.plc_signing_token {
+            Some(token) => Ok(token.clone()),
+            None => {
+                tracing::info!("Waiting for the PLC signing token sent by email");
+                Err(MigrationError::Validation {
+                    field: "plc_signing_token".to_string(),
+                })
+            }
+        }
+    }
+
+    async fn create_destination_account(
+        &self,
+        options: &MigrationOptions,
+    ) -> Result<String, MigrationError> {
+        let account = self
+            .new_account
+            .as_ref()
+            .ok_or(MigrationError::Validation {
+                field: "new_account".to_string(),
+            })?;
+        let agent = build_agent(&options.retry).await?;
+        login_helper(
+            &agent,
+            self.origin.as_str(),
+            self.did.as_str(),
+            self.origin_token.as_str(),
+        )
+        .await?;
+        let aud = self
+            .destination
+            .replace("https://", "did:web:")
+            .replace("http://", "did:web:");
+        let service_token = get_service_auth(&agent, aud.as_str()).await?;
+        let created = create_account(
+            self.destination.as_str(),
+            &CreateAccountRequest {
+                did: self
+                    .did
+                    .parse()
+                    .map_err(|_error| MigrationError::Validation {
+                        field: "did".to_string(),
+                    })?,
+                email: Some(account.email.clone()),
+                handle: account.handle.trim().parse().map_err(|_error| {
+                    MigrationError::Validation {
+                        field: "handle".to_string(),
+                    }
+                })?,
+                invite_code: account
+                    .invite_code
+                    .as_ref()
+                    .map(|code| code.trim().to_string()),
+                password: Some(account.password.clone()),
+                recovery_key: None,
+                verification_code: Some(String::from("")),
+                verification_phone: None,
+                plc_op: None,
+                token: Some(service_token),
+            },
+            &options.retry,
+        )
+        .await;
+        match created {
+            Ok(()) => Ok(self
+                .login_destination(account, options)
+                .await?
+                .access_jwt
+                .clone()),
+            // A run that stopped after creating the account but before saving the checkpoint
+            // finds it already there on resume; it is ours if the new password logs in to it.
+            Err(MigrationError::AccountAlreadyExists { upstream }) => {
+                match self.login_destination(account, options).await {
+                    Ok(session) if session.did.as_str() == self.did => {
+                        tracing::info!("Destination account already exists, resuming with it");
+                        Ok(session.access_jwt.clone())
+                    }
+                    Ok(session) => {
+                        tracing::error!(
+                            "Destination login belongs to {}, not {}",
+                            session.did.as_str(),
+                            self.did
+                        );
+                        Err(MigrationError::AccountAlreadyExists { upstream })
+                    }
+                    Err(error) => {
+                        tracing::error!("Failed to log in to existing account: {}", error);
+                        Err(MigrationError::AccountAlreadyExists { upstream })
+                    }
+                }
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    async fn login_destination(
+        &self,
+        account: &MigrationAccount,
+        options: &MigrationOptions,
+    ) -> Result<AtpSession, MigrationError> {
+        let agent = build_agent(&options.retry).await?;
+        agent.configure_endpoint(self.destination.clone());
+        agent
+            .login(account.handle.trim(), account.password.as_str())
+            .await
+            .map_err(|error| {
+                tracing::error!("Failed to log in to destination: {}", error);
+                MigrationError::Authentication {
+                    message: error.to_string(),
+                }
+            })
+    }
+
+    /// Returns the destination access token, logging in with the new account's password when the
+    /// plan is resumed after the account was created in an earlier run.
+    async fn destination_token(
+        &self,
+        destination_token: &mut Option<String>,
+        options: &MigrationOptions,
+    ) -> Result<String, MigrationError> {
+        if let Some(token) = destination_token {
+            return Ok(token.clone());
+        }
+        match &self.new_account {
+            Some(account) => {
+                let token = self
+                    .login_destination(account, options)
+                    .await?
+                    .access_jwt
+                    .clone();
+                *destination_token = Some(token.clone());
+                Ok(token)
+            }
+            None => Err(MigrationError::Validation {
+                field: "destination_token".to_string(),
+            }),
+        }
+    }
+}
+
+/// Fails a blob step unless every blob made it across intact.
+fn check_blob_results(
+    action: &str,
+    invalid_blobs: &[String],
+    corrupt_blobs: &[String],
+) -> Result<(), MigrationError> {
+    if !invalid_blobs.is_empty() {
+        tracing::error!("Failed to {} blobs: {:?}", action, invalid_blobs);
+        return Err(MigrationError::Upstream {
+            message: format!("Failed to {} {} blobs", action, invalid_blobs.len()),
+        });
+    }
+    if !corrupt_blobs.is_empty() {
+        tracing::error!("Corrupt blobs on {}: {:?}", action, corrupt_blobs);
+        return Err(MigrationError::Upstream {
+            message: format!("{} blobs did not match their CIDs", corrupt_blobs.len()),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryStagingStore, RetryPolicy, CREATE_ACCOUNT_PATH};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn test_plan(checkpoint_path: PathBuf) -> MigrationPlan {
+        MigrationPlan {
+            did: "did:plc:example123".to_string(),
+            origin: "https://origin.example.com".to_string(),
+            origin_token: "secret-origin-token".to_string(),
+            destination: "https://destination.example.com".to_string(),
+            destination_token: Some("secret-destination-token".to_string()),
+            new_account: None,
+            plc_signing_token: None,
+            user_recovery_key: None,
+            checkpoint_path,
+        }
+    }
+
+    fn temp_checkpoint_path(name: &str) -> PathBuf {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "pdsmigration-{}-{}.json",
+            name,
+            rand::random::<u64>()
+        ));
+        path
+    }
+
+    #[test]
+    fn test_steps_skip_account_creation_without_new_account() {
+        let plan = test_plan(temp_checkpoint_path("steps"));
+        let steps = plan.steps();
+        assert_eq!(steps.first(), Some(&MigrationStep::ExportRepo));
+        assert_eq!(steps.last(), Some(&MigrationStep::DeactivateAccount));
+        assert!(!steps.contains(&MigrationStep::CreateAccount));
+    }
+
+    #[test]
+    fn test_catch_up_runs_right_before_plc_migration() {
+        let plan = test_plan(temp_checkpoint_path("catch-up"));
+        let steps = plan.steps();
+        let catch_up = steps
+            .iter()
+            .position(|step| *step == MigrationStep::CatchUpRepo)
+            .unwrap();
+        assert_eq!(steps[catch_up + 1], MigrationStep::MigratePlc);
+    }
+
+    #[test]
+    fn test_pending_steps_resume_from_failed_step() {
+        let plan = test_plan(temp_checkpoint_path("pending"));
+        let mut checkpoint = MigrationCheckpoint::new(&plan.did);
+        checkpoint.mark_completed(MigrationStep::ExportRepo);
+        checkpoint.mark_completed(MigrationStep::ImportRepo);
+        checkpoint.mark_failed(
+            MigrationStep::ExportBlobs,
+            &MigrationError::Runtime {
+                message: "boom".to_string(),
+            },
+        );
+
+        let pending = plan.pending_steps(&checkpoint);
+        assert_eq!(pending.first(), Some(&MigrationStep::ExportBlobs));
+        assert!(!pending.contains(&MigrationStep::ImportRepo));
+
+        checkpoint.mark_completed(MigrationStep::ExportBlobs);
+        assert_eq!(checkpoint.failed_step, None);
+        assert_eq!(checkpoint.last_error, None);
+    }
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let path = temp_checkpoint_path("round-trip");
+        let mut checkpoint = MigrationCheckpoint::new("did:plc:example123");
+        checkpoint.mark_completed(MigrationStep::ExportRepo);
+        checkpoint.mark_failed(
+            MigrationStep::ImportRepo,
+            &MigrationError::Upstream {
+                message: "import failed".to_string(),
+            },
+        );
+
+        tokio_test::block_on(checkpoint.save(&path)).unwrap();
+        let loaded = tokio_test::block_on(MigrationCheckpoint::load(&path)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, Some(checkpoint));
+    }
+
+    #[test]
+    fn test_checkpoint_for_other_did_is_rejected() {
+        let path = temp_checkpoint_path("other-did");
+        let checkpoint = MigrationCheckpoint::new("did:plc:someoneelse");
+        tokio_test::block_on(checkpoint.save(&path)).unwrap();
+
+        let plan = test_plan(path.clone());
+        let result = tokio_test::block_on(plan.checkpoint());
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(MigrationError::Validation { .. })));
+    }
+
+    #[test]
+    fn test_create_account_resumes_with_existing_account() {
+        tokio_test::block_on(async {
+            let origin = MockServer::start().await;
+            let destination = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/xrpc/com.atproto.server.getSession"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(
+                    serde_json::json!({ "did": "did:plc:example123", "handle": "user.example.com" }),
+                ))
+                .mount(&origin)
+                .await;
+            Mock::given(method("GET"))
+                .and(path("/xrpc/com.atproto.server.getServiceAuth"))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_json(serde_json::json!({ "token": "service-token" })),
+                )
+                .mount(&origin)
+                .await;
+            Mock::given(method("POST"))
+                .and(path(CREATE_ACCOUNT_PATH))
+                .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                    "error": "AlreadyExists",
+                    "message": "Account already exists"
+                })))
+                .mount(&destination)
+                .await;
+            Mock::given(method("POST"))
+                .and(path("/xrpc/com.atproto.server.createSession"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "accessJwt": "access",
+                    "refreshJwt": "refresh",
+                    "did": "did:plc:example123",
+                    "handle": "user.example.com",
+                })))
+                .expect(1)
+                .mount(&destination)
+                .await;
+
+            // The earlier run created the account but died before checkpointing it
+            let path = temp_checkpoint_path("create-resume");
+            let mut checkpoint = MigrationCheckpoint::new("did:plc:example123");
+            for step in MigrationStep::ALL {
+                if step != MigrationStep::CreateAccount {
+                    checkpoint.mark_completed(step);
+                }
+            }
+            checkpoint.save(&path).await.unwrap();
+            let mut plan = test_plan(path.clone());
+            plan.origin = origin.uri();
+            plan.destination = destination.uri();
+            plan.destination_token = None;
+            plan.new_account = Some(MigrationAccount {
+                email: "user@example.com".to_string(),
+                handle: "user.example.com".to_string(),
+                password: "secret-password".to_string(),
+                invite_code: None,
+            });
+            let options = MigrationOptions {
+                retry: RetryPolicy::none(),
+                ..Default::default()
+            };
+
+            let result = plan
+                .run(
+                    &MemoryStagingStore::new(),
+                    &IdentityResolver::default(),
+                    &options,
+                )
+                .await;
+            std::fs::remove_file(&path).unwrap();
+
+            let checkpoint = result.unwrap();
+            assert!(checkpoint.is_completed(MigrationStep::CreateAccount));
+            assert_eq!(checkpoint.failed_step, None);
+        });
+    }
+
+    #[test]
+    fn test_did_web_plan_awaits_published_did_json() {
+        tokio_test::block_on(async {
+            let did = "did:web:alice.example.com";
+            let key = "zQ3shokFTS3brHcDQrn82RUDfCZESWL1ZdCEJwekUDPQiYBme";
+            let destination = MockServer::start().await;
+            let domain = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/xrpc/com.atproto.server.getSession"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(
+                    serde_json::json!({ "did": did, "handle": "alice.example.com" }),
+                ))
+                .mount(&destination)
+                .await;
+            Mock::given(method("GET"))
+                .and(path(
+                    "/xrpc/com.atproto.identity.getRecommendedDidCredentials",
+                ))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "alsoKnownAs": ["at://alice.example.com"],
+                    "verificationMethods": { "atproto": format!("did:key:{key}") },
+                    "services": {
+                        "atproto_pds": {
+                            "type": "AtprotoPersonalDataServer",
+                            "endpoint": destination.uri(),
+                        }
+                    },
+                })))
+                .mount(&destination)
+                .await;
+            Mock::given(method("GET"))
+                .and(path("/.well-known/did.json"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "id": did,
+                    "alsoKnownAs": ["at://alice.example.com"],
+                    "verificationMethod": [{
+                        "id": format!("{did}#atproto"),
+                        "type": "Multikey",
+                        "controller": did,
+                        "publicKeyMultibase": key,
+                    }],
+                    "service": [{
+                        "id": "#atproto_pds",
+                        "type": "AtprotoPersonalDataServer",
+                        "serviceEndpoint": destination.uri(),
+                    }],
+                })))
+                .mount(&domain)
+                .await;
+
+            // Only the did:web steps are left; neither needs the PLC signing token
+            let path = temp_checkpoint_path("did-web");
+            let mut checkpoint = MigrationCheckpoint::new(did);
+            for step in MigrationStep::ALL {
+                if step != MigrationStep::RequestPlcToken && step != MigrationStep::MigratePlc {
+                    checkpoint.mark_completed(step);
+                }
+            }
+            checkpoint.save(&path).await.unwrap();
+            let mut plan = test_plan(path.clone());
+            plan.did = did.to_string();
+            plan.destination = destination.uri();
+            let store = MemoryStagingStore::new();
+            let resolver = IdentityResolver {
+                web_base: Some(domain.uri()),
+                retry: RetryPolicy::none(),
+                ..IdentityResolver::default()
+            };
+            let options = MigrationOptions {
+                retry: RetryPolicy::none(),
+                ..Default::default()
+            };
+
+            let result = plan.run(&store, &resolver, &options).await;
+            std::fs::remove_file(&path).unwrap();
+
+            let checkpoint = result.unwrap();
+            assert!(checkpoint.is_completed(MigrationStep::RequestPlcToken));
+            assert!(checkpoint.is_completed(MigrationStep::MigratePlc));
+            assert!(store.exists(&did_web_document_key(did)).await.unwrap());
+        });
+    }
+
+    #[test]
+    fn test_migration_plan_redacts_secrets() {
+        let mut plan = test_plan(temp_checkpoint_path("redact"));
+        plan.new_account = Some(MigrationAccount {
+            email: "user@example.com".to_string(),
+            handle: "user.example.com".to_string(),
+            password: "secret-password".to_string(),
+            invite_code: Some("secret-invite".to_string()),
+        });
+        plan.plc_signing_token = Some("secret-plc-token".to_string());
+
+        let debug_output = format!("{:?}", plan);
+        assert!(!debug_output.contains("secret-"));
+        assert!(debug_output.contains("did:plc:example123"));
+        assert!(debug_output.contains("user.example.com"));
+    }
+}
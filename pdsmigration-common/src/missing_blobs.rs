@@ -1,5 +1,5 @@
 use crate::agent::{login_helper, missing_blobs};
-use crate::{build_agent, MigrationError};
+use crate::{build_agent, MigrationError, MigrationOptions};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -14,11 +14,12 @@ pub struct MissingBlobsResponse {
     pub missing_blobs: Vec<String>,
 }
 
-#[tracing::instrument(skip(req))]
+#[tracing::instrument(skip(req, options))]
 pub async fn missing_blobs_api(
     req: MissingBlobsRequest,
+    options: &MigrationOptions,
 ) -> Result<MissingBlobsResponse, MigrationError> {
-    let agent = build_agent().await?;
+    let agent = build_agent(&options.retry).await?;
     login_helper(
         &agent,
         req.pds_host.as_str(),
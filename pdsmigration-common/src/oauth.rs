@@ -0,0 +1,1097 @@
+use crate::{
+    parse_did, resume_session, send_governed, send_governed_with, GovernedClient, IdentityResolver,
+    MigrationAgent, MigrationError, MigrationOptions, RefreshedSession, RetryPolicy,
+    REFRESH_MARGIN,
+};
+use atrium_xrpc::http::{HeaderMap, HeaderValue, Request};
+use bsky_sdk::api::agent::atp_agent::AtpSession;
+use bsky_sdk::BskyAgent;
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Scope that grants the same access as an app password.
+pub const DEFAULT_OAUTH_SCOPE: &str = "atproto transition:generic";
+
+/// How this tool identifies itself to authorization servers.
+#[derive(Debug, Clone)]
+pub struct OAuthClientConfig {
+    /// URL of the client metadata document, or a `http://localhost` loopback client id.
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub scope: String,
+}
+
+impl OAuthClientConfig {
+    /// A development client that needs no published metadata; authorization servers only
+    /// accept it with a loopback `redirect_uri` such as `http://127.0.0.1:8080/callback`.
+    pub fn loopback(redirect_uri: &str) -> Self {
+        let client_id = reqwest::Url::parse_with_params(
+            "http://localhost",
+            &[
+                ("redirect_uri", redirect_uri),
+                ("scope", DEFAULT_OAUTH_SCOPE),
+            ],
+        )
+        .map(String::from)
+        .unwrap_or_else(|_| "http://localhost".to_string());
+        Self {
+            client_id,
+            redirect_uri: redirect_uri.to_string(),
+            scope: DEFAULT_OAUTH_SCOPE.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuthorizationServerMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub pushed_authorization_request_endpoint: String,
+    #[serde(default)]
+    pub dpop_signing_alg_values_supported: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ProtectedResourceMetadata {
+    #[serde(default)]
+    authorization_servers: Vec<String>,
+}
+
+/// Finds the authorization server that issues tokens for `pds_host`.
+#[tracing::instrument(skip(retry))]
+pub async fn discover_authorization_server(
+    pds_host: &str,
+    retry: &RetryPolicy,
+) -> Result<AuthorizationServerMetadata, MigrationError> {
+    let issuer = &authorization_server_of(pds_host, retry).await?;
+    let metadata: AuthorizationServerMetadata = get_json(
+        &format!(
+            "{}/.well-known/oauth-authorization-server",
+            issuer.trim_end_matches('/')
+        ),
+        retry,
+    )
+    .await?;
+    if metadata.issuer != *issuer {
+        return Err(MigrationError::Upstream {
+            message: format!(
+                "Authorization server {} claims to be {}",
+                issuer, metadata.issuer
+            ),
+        });
+    }
+    if !metadata.dpop_signing_alg_values_supported.is_empty()
+        && !metadata
+            .dpop_signing_alg_values_supported
+            .iter()
+            .any(|alg| alg == "ES256")
+    {
+        return Err(MigrationError::Upstream {
+            message: format!("{issuer} does not accept ES256 DPoP proofs"),
+        });
+    }
+    Ok(metadata)
+}
+
+/// The issuer `pds_host` names in its protected resource metadata.
+async fn authorization_server_of(
+    pds_host: &str,
+    retry: &RetryPolicy,
+) -> Result<String, MigrationError> {
+    let resource: ProtectedResourceMetadata = get_json(
+        &format!(
+            "{}/.well-known/oauth-protected-resource",
+            pds_host.trim_end_matches('/')
+        ),
+        retry,
+    )
+    .await?;
+    resource
+        .authorization_servers
+        .into_iter()
+        .next()
+        .ok_or_else(|| MigrationError::Upstream {
+            message: format!("{pds_host} does not name an authorization server"),
+        })
+}
+
+/// PKCE verifier and its S256 challenge.
+#[derive(Clone)]
+pub struct Pkce {
+    pub verifier: String,
+    pub challenge: String,
+}
+
+impl Pkce {
+    pub fn generate() -> Self {
+        Self::from_verifier(base64url(&rand::random::<[u8; 32]>()))
+    }
+
+    pub fn from_verifier(verifier: String) -> Self {
+        let challenge = base64url(&Sha256::digest(verifier.as_bytes()));
+        Self {
+            verifier,
+            challenge,
+        }
+    }
+}
+
+/// The key DPoP-bound tokens are tied to, plus the latest nonce each server handed out.
+pub struct DpopKey {
+    signing_key: SigningKey,
+    nonces: Mutex<HashMap<String, String>>,
+}
+
+impl fmt::Debug for DpopKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DpopKey")
+            .field("jwk", &self.public_jwk())
+            .finish()
+    }
+}
+
+impl DpopKey {
+    pub fn generate() -> Self {
+        loop {
+            if let Ok(signing_key) = SigningKey::from_slice(&rand::random::<[u8; 32]>()) {
+                return Self::new(signing_key);
+            }
+        }
+    }
+
+    pub fn new(signing_key: SigningKey) -> Self {
+        Self {
+            signing_key,
+            nonces: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn public_jwk(&self) -> serde_json::Value {
+        let point = self.signing_key.verifying_key().to_encoded_point(false);
+        serde_json::json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "x": point.x().map(|x| base64url(x)),
+            "y": point.y().map(|y| base64url(y)),
+        })
+    }
+
+    /// Signs a DPoP proof for `method` on `url`, using the last nonce that server sent.
+    /// `access_token` binds the proof to a token when calling a resource server.
+    pub fn proof(
+        &self,
+        method: &str,
+        url: &str,
+        access_token: Option<&str>,
+    ) -> Result<String, MigrationError> {
+        let mut url = reqwest::Url::parse(url).map_err(|_error| MigrationError::Validation {
+            field: "url".to_string(),
+        })?;
+        let nonce = self.nonce(&url);
+        url.set_query(None);
+        url.set_fragment(None);
+        let header = serde_json::json!({
+            "typ": "dpop+jwt",
+            "alg": "ES256",
+            "jwk": self.public_jwk(),
+        });
+        let mut claims = serde_json::json!({
+            "jti": base64url(&rand::random::<[u8; 16]>()),
+            "htm": method,
+            "htu": url.as_str(),
+            "iat": SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        });
+        if let Some(nonce) = nonce {
+            claims["nonce"] = nonce.into();
+        }
+        if let Some(access_token) = access_token {
+            claims["ath"] = base64url(&Sha256::digest(access_token.as_bytes())).into();
+        }
+        let signing_input = format!(
+            "{}.{}",
+            base64url(header.to_string().as_bytes()),
+            base64url(claims.to_string().as_bytes())
+        );
+        let signature: Signature = self.signing_key.sign(signing_input.as_bytes());
+        Ok(format!(
+            "{}.{}",
+            signing_input,
+            base64url(&signature.to_bytes())
+        ))
+    }
+
+    /// Remembers the nonce in a response from `url`. Returns true when the server
+    /// rejected the request only because it wanted a fresh nonce, so it should be re-sent.
+    pub fn observe(&self, url: &str, status: u16, headers: &HeaderMap) -> bool {
+        let Ok(url) = reqwest::Url::parse(url) else {
+            return false;
+        };
+        let Some(nonce) = headers
+            .get("dpop-nonce")
+            .and_then(|value| value.to_str().ok())
+        else {
+            return false;
+        };
+        let previous = self
+            .nonces
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(url.origin().ascii_serialization(), nonce.to_string());
+        matches!(status, 400 | 401) && previous.as_deref() != Some(nonce)
+    }
+
+    fn nonce(&self, url: &reqwest::Url) -> Option<String> {
+        self.nonces
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(&url.origin().ascii_serialization())
+            .cloned()
+    }
+
+    /// Turns the agent's `Bearer` authorization into a DPoP one with a fresh proof.
+    pub(crate) fn authorize(&self, request: &mut Request<Vec<u8>>) {
+        let Some(token) = request
+            .headers()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::to_string)
+        else {
+            return;
+        };
+        let url = request.uri().to_string();
+        let Ok(proof) = self.proof(request.method().as_str(), &url, Some(&token)) else {
+            return;
+        };
+        let headers = request.headers_mut();
+        if let Ok(value) = HeaderValue::from_str(&format!("DPoP {token}")) {
+            headers.insert("authorization", value);
+        }
+        if let Ok(value) = HeaderValue::from_str(&proof) {
+            headers.insert("dpop", value);
+        }
+    }
+}
+
+/// Everything needed to finish a login after the user comes back from the browser.
+pub struct PendingAuthorization {
+    /// Open this in the user's browser.
+    pub authorization_url: String,
+    pub state: String,
+    pub pds_host: String,
+    pub metadata: AuthorizationServerMetadata,
+    pkce: Pkce,
+    dpop_key: Arc<DpopKey>,
+}
+
+impl fmt::Debug for PendingAuthorization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PendingAuthorization")
+            .field("authorization_url", &self.authorization_url)
+            .field("pds_host", &self.pds_host)
+            .field("issuer", &self.metadata.issuer)
+            .field("state", &"[REDACTED]")
+            .field("pkce", &"[REDACTED]")
+            .finish()
+    }
+}
+
+/// Query parameters the authorization server appends to the redirect URI.
+#[derive(Deserialize)]
+pub struct AuthorizationCallback {
+    pub code: String,
+    pub state: String,
+    #[serde(default)]
+    pub iss: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ParResponse {
+    request_uri: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    token_type: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    #[serde(default)]
+    expires_in: Option<u64>,
+    sub: String,
+}
+
+/// Pushes an authorization request for the account on `pds_host` and returns the URL the
+/// user has to approve it at. `login_hint` pre-fills the handle or DID.
+///
+/// The login is split in two so the user can be sent to the browser in between; pass the
+/// result and the redirect's query to [`complete_authorization`].
+#[tracing::instrument(skip(config, retry))]
+pub async fn start_authorization(
+    pds_host: &str,
+    login_hint: Option<&str>,
+    config: &OAuthClientConfig,
+    retry: &RetryPolicy,
+) -> Result<PendingAuthorization, MigrationError> {
+    let metadata = discover_authorization_server(pds_host, retry).await?;
+    let pkce = Pkce::generate();
+    let dpop_key = Arc::new(DpopKey::generate());
+    let state = base64url(&rand::random::<[u8; 16]>());
+    let mut form = vec![
+        ("response_type", "code"),
+        ("client_id", config.client_id.as_str()),
+        ("redirect_uri", config.redirect_uri.as_str()),
+        ("scope", config.scope.as_str()),
+        ("state", state.as_str()),
+        ("code_challenge", pkce.challenge.as_str()),
+        ("code_challenge_method", "S256"),
+    ];
+    if let Some(login_hint) = login_hint {
+        form.push(("login_hint", login_hint));
+    }
+    let par: ParResponse = post_form(
+        &dpop_key,
+        &metadata.pushed_authorization_request_endpoint,
+        &form,
+        retry,
+    )
+    .await?;
+    let authorization_url = reqwest::Url::parse_with_params(
+        &metadata.authorization_endpoint,
+        &[
+            ("client_id", config.client_id.as_str()),
+            ("request_uri", par.request_uri.as_str()),
+        ],
+    )
+    .map_err(|error| MigrationError::Upstream {
+        message: error.to_string(),
+    })?;
+    Ok(PendingAuthorization {
+        authorization_url: authorization_url.into(),
+        state,
+        pds_host: pds_host.to_string(),
+        metadata,
+        pkce,
+        dpop_key,
+    })
+}
+
+/// Exchanges the code from the redirect for DPoP-bound tokens.
+///
+/// The account the tokens are for is resolved with `resolver`, and its PDS has to name the
+/// same authorization server, so a server can not hand out tokens for accounts it does not
+/// host.
+#[tracing::instrument(skip(callback, config, resolver, retry))]
+pub async fn complete_authorization(
+    pending: PendingAuthorization,
+    callback: &AuthorizationCallback,
+    config: &OAuthClientConfig,
+    resolver: &IdentityResolver,
+    retry: &RetryPolicy,
+) -> Result<OAuthSession, MigrationError> {
+    if callback.state != pending.state {
+        return Err(MigrationError::Validation {
+            field: "state".to_string(),
+        });
+    }
+    if callback.iss.as_deref() != Some(pending.metadata.issuer.as_str()) {
+        return Err(MigrationError::Validation {
+            field: "iss".to_string(),
+        });
+    }
+    let tokens: TokenResponse = post_form(
+        &pending.dpop_key,
+        &pending.metadata.token_endpoint,
+        &[
+            ("grant_type", "authorization_code"),
+            ("code", callback.code.as_str()),
+            ("redirect_uri", config.redirect_uri.as_str()),
+            ("client_id", config.client_id.as_str()),
+            ("code_verifier", pending.pkce.verifier.as_str()),
+        ],
+        retry,
+    )
+    .await?;
+    let issuer = pending.metadata.issuer;
+    let mut session = OAuthSession {
+        pds_host: pending.pds_host,
+        did: String::new(),
+        access_token: String::new(),
+        refresh_token: None,
+        expires_at: None,
+        token_endpoint: pending.metadata.token_endpoint,
+        client_id: config.client_id.clone(),
+        dpop_key: pending.dpop_key,
+    };
+    session.apply(tokens)?;
+
+    let identity = resolver.resolve(&session.did).await?;
+    let authorization_server = authorization_server_of(&identity.pds_host, retry).await?;
+    if authorization_server != issuer {
+        tracing::error!(
+            "{} is hosted by {}, whose authorization server is {}, not {}",
+            session.did,
+            identity.pds_host,
+            authorization_server,
+            issuer
+        );
+        return Err(MigrationError::Authentication {
+            message: format!(
+                "{issuer} is not the authorization server of {}",
+                session.did
+            ),
+        });
+    }
+    Ok(session)
+}
+
+/// An [`OAuthSession`] shared between its owner and the agents using it, which refresh it in
+/// place.
+pub type SharedOAuthSession = Arc<tokio::sync::Mutex<OAuthSession>>;
+
+/// Sessions by the access token their agents were last resumed with, so
+/// [`fresh_session`](crate::fresh_session) can tell an OAuth agent apart and refresh it at the
+/// authorization server.
+static OAUTH_SESSIONS: LazyLock<Mutex<HashMap<String, SharedOAuthSession>>> =
+    LazyLock::new(Default::default);
+
+fn register_oauth_session(access_token: &str, session: &SharedOAuthSession) {
+    let mut sessions = OAUTH_SESSIONS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    // Sessions their owner has dropped are not refreshed any more
+    sessions.retain(|_, session| Arc::strong_count(session) > 1);
+    sessions.insert(access_token.to_string(), session.clone());
+}
+
+/// The OAuth session an agent holding `access_token` was built from, if any.
+pub(crate) fn oauth_session_for(access_token: &str) -> Option<SharedOAuthSession> {
+    OAUTH_SESSIONS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(access_token)
+        .cloned()
+}
+
+/// The OAuth counterpart of [`fresh_session`](crate::fresh_session): refreshes `session` at its
+/// token endpoint when the access token is about to expire, and resumes `agent` with the new
+/// token. New tokens are passed to [`MigrationOptions::on_session_refresh`].
+pub(crate) async fn fresh_oauth_session(
+    agent: &MigrationAgent,
+    session: SharedOAuthSession,
+    options: &MigrationOptions,
+) -> Result<AtpSession, MigrationError> {
+    // Holding the lock makes parallel transfers wait for one refresh instead of each spending
+    // the refresh token
+    let mut oauth = session.lock().await;
+    if oauth.needs_refresh() {
+        tracing::info!("Refreshing OAuth session for {}", oauth.did);
+        let previous = oauth.access_token.clone();
+        oauth.refresh(&options.retry).await?;
+        OAUTH_SESSIONS
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(&previous);
+        register_oauth_session(&oauth.access_token, &session);
+        if let Some(on_session_refresh) = &options.on_session_refresh {
+            on_session_refresh(&RefreshedSession {
+                pds_host: oauth.pds_host.clone(),
+                did: oauth.did.clone(),
+                access_token: oauth.access_token.clone(),
+                refresh_token: oauth.refresh_token.clone().unwrap_or_default(),
+            });
+        }
+    }
+    match agent.get_session().await {
+        Some(current) if current.access_jwt == oauth.access_token => Ok(current),
+        _ => oauth.resume(agent).await,
+    }
+}
+
+/// Tokens from an OAuth login, bound to the DPoP key that requested them.
+pub struct OAuthSession {
+    pub pds_host: String,
+    pub did: String,
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_at: Option<SystemTime>,
+    token_endpoint: String,
+    client_id: String,
+    dpop_key: Arc<DpopKey>,
+}
+
+impl fmt::Debug for OAuthSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OAuthSession")
+            .field("pds_host", &self.pds_host)
+            .field("did", &self.did)
+            .field("access_token", &"[REDACTED]")
+            .field(
+                "refresh_token",
+                &self.refresh_token.as_ref().map(|_| "[REDACTED]"),
+            )
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+impl OAuthSession {
+    pub fn dpop_key(&self) -> Arc<DpopKey> {
+        self.dpop_key.clone()
+    }
+
+    /// Whether the access token expires within the refresh margin.
+    pub fn needs_refresh(&self) -> bool {
+        self.refresh_token.is_some()
+            && self
+                .expires_at
+                .is_some_and(|expires_at| expires_at <= SystemTime::now() + REFRESH_MARGIN)
+    }
+
+    /// Trades the refresh token for new tokens at the authorization server.
+    #[tracing::instrument(skip(retry))]
+    pub async fn refresh(&mut self, retry: &RetryPolicy) -> Result<(), MigrationError> {
+        let refresh_token =
+            self.refresh_token
+                .clone()
+                .ok_or_else(|| MigrationError::Authentication {
+                    message: "OAuth session has no refresh token".to_string(),
+                })?;
+        let tokens: TokenResponse = post_form(
+            &self.dpop_key,
+            &self.token_endpoint,
+            &[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token.as_str()),
+                ("client_id", self.client_id.as_str()),
+            ],
+            retry,
+        )
+        .await?;
+        let did = self.did.clone();
+        self.apply(tokens)?;
+        if self.did != did {
+            return Err(MigrationError::Authentication {
+                message: "Refreshed token belongs to another account".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Logs `agent` in with this session, the same way [`resume_session`] does for
+    /// password sessions. The agent must come from [`build_oauth_agent`] so its requests
+    /// carry DPoP proofs, and so [`fresh_session`](crate::fresh_session) refreshes it here
+    /// rather than with `refreshSession`, which does not take OAuth tokens.
+    pub async fn resume(&self, agent: &MigrationAgent) -> Result<AtpSession, MigrationError> {
+        resume_session(agent, &self.pds_host, &self.did, &self.access_token, None).await
+    }
+
+    fn apply(&mut self, tokens: TokenResponse) -> Result<(), MigrationError> {
+        if !tokens.token_type.eq_ignore_ascii_case("DPoP") {
+            return Err(MigrationError::Upstream {
+                message: format!("Expected a DPoP token, got {}", tokens.token_type),
+            });
+        }
+        self.did = parse_did(&tokens.sub)?.as_str().to_string();
+        self.access_token = tokens.access_token;
+        if tokens.refresh_token.is_some() {
+            self.refresh_token = tokens.refresh_token;
+        }
+        self.expires_at = tokens
+            .expires_in
+            .map(|expires_in| SystemTime::now() + Duration::from_secs(expires_in));
+        Ok(())
+    }
+}
+
+/// An agent logged in with `session`, sending DPoP proofs with every request.
+///
+/// [`fresh_session`](crate::fresh_session) refreshes `session` in place for as long as the
+/// caller holds on to it, so keep it while the agent is in use.
+pub async fn build_oauth_agent(
+    session: &SharedOAuthSession,
+    retry: &RetryPolicy,
+) -> Result<MigrationAgent, MigrationError> {
+    let oauth = session.lock().await;
+    let agent = BskyAgent::builder()
+        .client(GovernedClient::new(&oauth.pds_host, retry.clone()).with_dpop(oauth.dpop_key()))
+        .build()
+        .await
+        .map_err(|error| MigrationError::Upstream {
+            message: error.to_string(),
+        })?;
+    oauth.resume(&agent).await?;
+    register_oauth_session(&oauth.access_token, session);
+    Ok(agent)
+}
+
+async fn get_json<T: serde::de::DeserializeOwned>(
+    url: &str,
+    retry: &RetryPolicy,
+) -> Result<T, MigrationError> {
+    let client = reqwest::Client::new();
+    let request = client.get(url).build().map_err(upstream)?;
+    let response = send_governed(&client, request, retry)
+        .await
+        .map_err(upstream)?;
+    if !response.status().is_success() {
+        return Err(MigrationError::Upstream {
+            message: format!("{} answered {}", url, response.status()),
+        });
+    }
+    response.json().await.map_err(upstream)
+}
+
+/// Posts a form with a DPoP proof, re-sending it once if the server asks for a new nonce.
+/// Every attempt carries a proof of its own, since servers reject a replayed `jti`.
+async fn post_form<T: serde::de::DeserializeOwned>(
+    dpop_key: &DpopKey,
+    url: &str,
+    form: &[(&str, &str)],
+    retry: &RetryPolicy,
+) -> Result<T, MigrationError> {
+    let client = reqwest::Client::new();
+    let build = || -> Result<reqwest::Request, MigrationError> {
+        client
+            .post(url)
+            .header("DPoP", dpop_key.proof("POST", url, None)?)
+            .form(form)
+            .build()
+            .map_err(upstream)
+    };
+    for _ in 0..2 {
+        let response = send_governed_with(&client, build()?, retry, |_| build().ok())
+            .await
+            .map_err(upstream)?;
+        let status = response.status();
+        if dpop_key.observe(url, status.as_u16(), response.headers()) {
+            continue;
+        }
+        let body: serde_json::Value = response.json().await.map_err(upstream)?;
+        if !status.is_success() {
+            return Err(oauth_error(&body));
+        }
+        return serde_json::from_value(body).map_err(upstream);
+    }
+    Err(MigrationError::Upstream {
+        message: format!("{url} kept asking for a new DPoP nonce"),
+    })
+}
+
+fn oauth_error(body: &serde_json::Value) -> MigrationError {
+    let error = body["error"].as_str().unwrap_or("unknown_error");
+    let message = match body["error_description"].as_str() {
+        Some(description) => format!("{error}: {description}"),
+        None => error.to_string(),
+    };
+    match error {
+        "invalid_grant" | "invalid_client" | "access_denied" => {
+            MigrationError::Authentication { message }
+        }
+        _ => MigrationError::Upstream { message },
+    }
+}
+
+fn upstream(error: impl fmt::Display) -> MigrationError {
+    MigrationError::Upstream {
+        message: error.to_string(),
+    }
+}
+
+fn base64url(bytes: &[u8]) -> String {
+    multibase::encode(multibase::Base::Base64Url, bytes)[1..].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jwt_claims;
+    use p256::ecdsa::signature::Verifier;
+    use p256::ecdsa::VerifyingKey;
+    use wiremock::matchers::{body_string_contains, header_exists, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn config() -> OAuthClientConfig {
+        OAuthClientConfig::loopback("http://127.0.0.1:8080/callback")
+    }
+
+    /// A directory that places did:plc:example123 on `pds_host`.
+    async fn resolver(pds_host: &str) -> (MockServer, IdentityResolver) {
+        let directory = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/did:plc:example123"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "did:plc:example123",
+                "alsoKnownAs": ["at://alice.example.com"],
+                "service": [{
+                    "id": "#atproto_pds",
+                    "type": "AtprotoPersonalDataServer",
+                    "serviceEndpoint": pds_host,
+                }],
+            })))
+            .mount(&directory)
+            .await;
+        let resolver = IdentityResolver {
+            plc_directory: directory.uri(),
+            retry: RetryPolicy::none(),
+            ..IdentityResolver::default()
+        };
+        (directory, resolver)
+    }
+
+    async fn authorization_server() -> MockServer {
+        let server = MockServer::start().await;
+        let issuer = server.uri();
+        Mock::given(method("GET"))
+            .and(path("/.well-known/oauth-protected-resource"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "resource": issuer,
+                "authorization_servers": [issuer],
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/.well-known/oauth-authorization-server"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "issuer": issuer,
+                "authorization_endpoint": format!("{issuer}/oauth/authorize"),
+                "token_endpoint": format!("{issuer}/oauth/token"),
+                "pushed_authorization_request_endpoint": format!("{issuer}/oauth/par"),
+                "dpop_signing_alg_values_supported": ["ES256"],
+            })))
+            .mount(&server)
+            .await;
+        // The first request is always told to retry with a server nonce.
+        Mock::given(method("POST"))
+            .and(path("/oauth/par"))
+            .respond_with(
+                ResponseTemplate::new(400)
+                    .insert_header("DPoP-Nonce", "nonce-1")
+                    .set_body_json(serde_json::json!({ "error": "use_dpop_nonce" })),
+            )
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/par"))
+            .and(header_exists("dpop"))
+            .and(body_string_contains("code_challenge_method=S256"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(serde_json::json!({
+                "request_uri": "urn:ietf:params:oauth:request_uri:req-1",
+                "expires_in": 60,
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/token"))
+            .and(body_string_contains("grant_type=authorization_code"))
+            .and(body_string_contains("code_verifier="))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "access-1",
+                "token_type": "DPoP",
+                "refresh_token": "refresh-1",
+                "expires_in": 60,
+                "sub": "did:plc:example123",
+                "scope": DEFAULT_OAUTH_SCOPE,
+            })))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[test]
+    fn test_pkce_challenge_is_s256_of_verifier() {
+        let pkce = Pkce::from_verifier("dBjftJeZ4CVP-mJ92-k8kc1w8ZUDPRX-Iw2GkPWEG9Y".to_string());
+        assert_eq!(
+            pkce.challenge,
+            "bI4zU2jb0QU4HnA62D1nYd3qlm78R4FpYO5zUO3EpiM"
+        );
+    }
+
+    #[test]
+    fn test_dpop_proof_is_signed_and_bound() {
+        let key = DpopKey::generate();
+        let mut headers = HeaderMap::new();
+        headers.insert("dpop-nonce", HeaderValue::from_static("nonce-1"));
+        assert!(key.observe("https://pds.example.com/xrpc/a", 401, &headers));
+        assert!(!key.observe("https://pds.example.com/xrpc/b", 401, &headers));
+
+        let proof = key
+            .proof("GET", "https://pds.example.com/xrpc/a?x=1", Some("token"))
+            .unwrap();
+        let claims = jwt_claims(&proof).unwrap();
+        assert_eq!(claims["htu"], "https://pds.example.com/xrpc/a");
+        assert_eq!(claims["nonce"], "nonce-1");
+        assert_eq!(claims["ath"], base64url(&Sha256::digest(b"token")).as_str());
+
+        let (signing_input, signature) = proof.rsplit_once('.').unwrap();
+        let (_, signature) = multibase::decode(format!("u{signature}")).unwrap();
+        let verifying_key = VerifyingKey::from(&key.signing_key);
+        assert!(verifying_key
+            .verify(
+                signing_input.as_bytes(),
+                &Signature::from_slice(&signature).unwrap()
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn test_authorization_code_flow() {
+        tokio_test::block_on(async {
+            let server = authorization_server().await;
+            let config = config();
+            let pending = start_authorization(
+                &server.uri(),
+                Some("alice.example.com"),
+                &config,
+                &RetryPolicy::none(),
+            )
+            .await
+            .unwrap();
+            assert!(pending
+                .authorization_url
+                .starts_with(&format!("{}/oauth/authorize?", server.uri())));
+            assert!(pending.authorization_url.contains("request_uri="));
+
+            let callback = AuthorizationCallback {
+                code: "code-1".to_string(),
+                state: pending.state.clone(),
+                iss: Some(server.uri()),
+            };
+            let (_directory, resolver) = resolver(&server.uri()).await;
+            let session = complete_authorization(
+                pending,
+                &callback,
+                &config,
+                &resolver,
+                &RetryPolicy::none(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(session.did, "did:plc:example123");
+            assert_eq!(session.access_token, "access-1");
+            assert_eq!(session.refresh_token.as_deref(), Some("refresh-1"));
+            assert!(session.needs_refresh());
+        });
+    }
+
+    #[test]
+    fn test_callback_with_wrong_state_is_rejected() {
+        tokio_test::block_on(async {
+            let server = authorization_server().await;
+            let config = config();
+            let pending = start_authorization(&server.uri(), None, &config, &RetryPolicy::none())
+                .await
+                .unwrap();
+            let callback = AuthorizationCallback {
+                code: "code-1".to_string(),
+                state: "forged".to_string(),
+                iss: None,
+            };
+            let (_directory, resolver) = resolver(&server.uri()).await;
+            let result = complete_authorization(
+                pending,
+                &callback,
+                &config,
+                &resolver,
+                &RetryPolicy::none(),
+            )
+            .await;
+            assert!(
+                matches!(result, Err(MigrationError::Validation { field }) if field == "state")
+            );
+        });
+    }
+
+    #[test]
+    fn test_callback_without_iss_is_rejected() {
+        tokio_test::block_on(async {
+            let server = authorization_server().await;
+            let config = config();
+            let pending = start_authorization(&server.uri(), None, &config, &RetryPolicy::none())
+                .await
+                .unwrap();
+            let callback = AuthorizationCallback {
+                code: "code-1".to_string(),
+                state: pending.state.clone(),
+                iss: None,
+            };
+            let (_directory, resolver) = resolver(&server.uri()).await;
+            let result = complete_authorization(
+                pending,
+                &callback,
+                &config,
+                &resolver,
+                &RetryPolicy::none(),
+            )
+            .await;
+            assert!(matches!(result, Err(MigrationError::Validation { field }) if field == "iss"));
+        });
+    }
+
+    #[test]
+    fn test_tokens_for_account_hosted_elsewhere_are_rejected() {
+        tokio_test::block_on(async {
+            let server = authorization_server().await;
+            let config = config();
+            let pending = start_authorization(&server.uri(), None, &config, &RetryPolicy::none())
+                .await
+                .unwrap();
+            let callback = AuthorizationCallback {
+                code: "code-1".to_string(),
+                state: pending.state.clone(),
+                iss: Some(server.uri()),
+            };
+            // The account's real PDS trusts another authorization server
+            let pds = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/.well-known/oauth-protected-resource"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "resource": pds.uri(),
+                    "authorization_servers": ["https://entryway.example.com"],
+                })))
+                .mount(&pds)
+                .await;
+            let (_directory, resolver) = resolver(&pds.uri()).await;
+            let result = complete_authorization(
+                pending,
+                &callback,
+                &config,
+                &resolver,
+                &RetryPolicy::none(),
+            )
+            .await;
+            assert!(matches!(result, Err(MigrationError::Authentication { .. })));
+        });
+    }
+
+    #[test]
+    fn test_rate_limited_token_request_gets_a_fresh_proof() {
+        tokio_test::block_on(async {
+            let server = MockServer::start().await;
+            Mock::given(method("POST"))
+                .and(path("/oauth/token"))
+                .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "0"))
+                .up_to_n_times(1)
+                .with_priority(1)
+                .mount(&server)
+                .await;
+            Mock::given(method("POST"))
+                .and(path("/oauth/token"))
+                .respond_with(
+                    ResponseTemplate::new(200).set_body_json(serde_json::json!({ "ok": true })),
+                )
+                .mount(&server)
+                .await;
+
+            let url = format!("{}/oauth/token", server.uri());
+            let body: serde_json::Value = post_form(
+                &DpopKey::generate(),
+                &url,
+                &[("grant_type", "refresh_token")],
+                &RetryPolicy::none(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(body["ok"], true);
+
+            let requests = server.received_requests().await.unwrap();
+            let jtis: Vec<_> = requests
+                .iter()
+                .map(|request| {
+                    let proof = request.headers.get("dpop").unwrap().to_str().unwrap();
+                    jwt_claims(proof).unwrap()["jti"].clone()
+                })
+                .collect();
+            assert_eq!(jtis.len(), 2);
+            assert_ne!(jtis[0], jtis[1]);
+        });
+    }
+
+    #[test]
+    fn test_fresh_session_refreshes_oauth_session() {
+        tokio_test::block_on(async {
+            let server = authorization_server().await;
+            Mock::given(method("POST"))
+                .and(path("/oauth/token"))
+                .and(body_string_contains("grant_type=refresh_token"))
+                .and(body_string_contains("refresh_token=refresh-1"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "access_token": "access-2",
+                    "token_type": "DPoP",
+                    "refresh_token": "refresh-2",
+                    "expires_in": 3600,
+                    "sub": "did:plc:example123",
+                    "scope": DEFAULT_OAUTH_SCOPE,
+                })))
+                .expect(1)
+                .mount(&server)
+                .await;
+            Mock::given(method("GET"))
+                .and(path("/xrpc/com.atproto.server.getSession"))
+                .and(header_exists("dpop"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "did": "did:plc:example123",
+                    "handle": "alice.example.com",
+                })))
+                .mount(&server)
+                .await;
+            let config = config();
+            let pending = start_authorization(&server.uri(), None, &config, &RetryPolicy::none())
+                .await
+                .unwrap();
+            let callback = AuthorizationCallback {
+                code: "code-1".to_string(),
+                state: pending.state.clone(),
+                iss: Some(server.uri()),
+            };
+            let (_directory, resolver) = resolver(&server.uri()).await;
+            let session = complete_authorization(
+                pending,
+                &callback,
+                &config,
+                &resolver,
+                &RetryPolicy::none(),
+            )
+            .await
+            .unwrap();
+            let session: SharedOAuthSession = Arc::new(tokio::sync::Mutex::new(session));
+            let agent = build_oauth_agent(&session, &RetryPolicy::none())
+                .await
+                .unwrap();
+
+            let refreshed = Arc::new(Mutex::new(vec![]));
+            let options = MigrationOptions {
+                retry: RetryPolicy::none(),
+                on_session_refresh: Some({
+                    let refreshed = refreshed.clone();
+                    Arc::new(move |session: &RefreshedSession| {
+                        refreshed
+                            .lock()
+                            .unwrap()
+                            .push(session.refresh_token.clone());
+                    })
+                }),
+                ..MigrationOptions::default()
+            };
+            // The first token expires within the refresh margin, the second does not
+            let current = crate::fresh_session(&agent, &options).await.unwrap();
+            assert_eq!(current.access_jwt, "access-2");
+            let current = crate::fresh_session(&agent, &options).await.unwrap();
+            assert_eq!(current.access_jwt, "access-2");
+
+            assert_eq!(session.lock().await.access_token, "access-2");
+            assert_eq!(*refreshed.lock().unwrap(), vec!["refresh-2".to_string()]);
+        });
+    }
+}
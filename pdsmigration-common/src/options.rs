@@ -0,0 +1,68 @@
+use crate::{MigrationError, ProgressEvent, ProgressSink, RetryPolicy, SessionRefreshCallback};
+use std::fmt;
+use std::sync::Arc;
+
+pub use tokio_util::sync::CancellationToken;
+
+/// Tuning shared by the long-running migration calls.
+#[derive(Clone)]
+pub struct MigrationOptions {
+    /// How many blobs are downloaded or uploaded at the same time.
+    pub blob_concurrency: usize,
+    /// Pipe blobs straight from the origin to the destination instead of staging them.
+    pub stream_blobs: bool,
+    /// Retries for requests that fail on the network or with a 5xx.
+    pub retry: RetryPolicy,
+    /// Told about every session refreshed during the call, so the caller can keep the
+    /// newest tokens.
+    pub on_session_refresh: Option<SessionRefreshCallback>,
+    /// Told about totals, every finished item and the bytes moved, so callers can show
+    /// progress without re-implementing the transfer loops.
+    pub progress: Option<Arc<dyn ProgressSink>>,
+    /// Stops the call at its next safe point. Partial staged files are removed and a PLC
+    /// operation that has been signed is always submitted.
+    pub cancel: CancellationToken,
+}
+
+impl fmt::Debug for MigrationOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MigrationOptions")
+            .field("blob_concurrency", &self.blob_concurrency)
+            .field("stream_blobs", &self.stream_blobs)
+            .field("retry", &self.retry)
+            .field("on_session_refresh", &self.on_session_refresh.is_some())
+            .field("progress", &self.progress.is_some())
+            .field("canceled", &self.cancel.is_cancelled())
+            .finish()
+    }
+}
+
+impl Default for MigrationOptions {
+    fn default() -> Self {
+        Self {
+            blob_concurrency: 4,
+            stream_blobs: false,
+            retry: RetryPolicy::default(),
+            on_session_refresh: None,
+            progress: None,
+            cancel: CancellationToken::new(),
+        }
+    }
+}
+
+impl MigrationOptions {
+    /// Fails with [`MigrationError::Canceled`] once [`Self::cancel`] has fired.
+    pub(crate) fn check_canceled(&self) -> Result<(), MigrationError> {
+        if self.cancel.is_cancelled() {
+            tracing::info!("Operation canceled");
+            return Err(MigrationError::Canceled { finished: vec![] });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn report(&self, event: ProgressEvent) {
+        if let Some(progress) = &self.progress {
+            progress.report(event);
+        }
+    }
+}
@@ -0,0 +1,444 @@
+use crate::{
+    dag_cbor, plc_operation_cid, MigrationError, PlcLogAuditEntry, PlcLogOperation, PlcSigningError,
+};
+use bsky_sdk::api::types::string::Datetime;
+use derive_more::{Display, Error};
+use p256::ecdsa::signature::Verifier;
+use secp256k1::ecdsa::Signature;
+use secp256k1::{Message, PublicKey, Secp256k1};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// How long a higher priority rotation key may override operations signed by a lower one.
+pub const PLC_RECOVERY_WINDOW_SECS: i64 = 72 * 60 * 60;
+
+// Multicodec prefixes of compressed public keys in a did:key
+const SECP256K1_PUB: [u8; 2] = [0xe7, 0x01];
+const P256_PUB: [u8; 2] = [0x80, 0x24];
+
+/// Why an audit log does not prove the history of a DID.
+#[derive(Debug, Display, Error, PartialEq)]
+pub enum PlcAuditError {
+    #[display("The audit log is empty")]
+    Empty,
+    #[display("Operation {index} belongs to {did}")]
+    WrongDid { index: usize, did: String },
+    /// The directory listed an operation under a CID it does not hash to.
+    #[display("Operation {index} is listed as {cid} but hashes to {computed}")]
+    CidMismatch {
+        index: usize,
+        cid: String,
+        computed: String,
+    },
+    /// A did:plc is derived from its signed genesis operation, so any other genesis is forged.
+    #[display("The genesis operation creates {computed}")]
+    GenesisMismatch { computed: String },
+    #[display("Operation {index} does not follow the operation before it")]
+    BrokenChain { index: usize },
+    #[display("Operation {index} is not signed by a rotation key of the operation it follows")]
+    InvalidSignature { index: usize },
+    /// A fork must be signed by a higher priority key than the operation it overrides, within
+    /// [`PLC_RECOVERY_WINDOW_SECS`] of it.
+    #[display("Operation {index} is marked nullified without a valid recovery")]
+    InvalidNullification { index: usize },
+    #[display("Operation {index} has an invalid timestamp")]
+    InvalidTimestamp { index: usize },
+    #[display("Could not encode operation: {message}")]
+    Encoding { message: String },
+}
+
+impl From<PlcSigningError> for PlcAuditError {
+    fn from(error: PlcSigningError) -> Self {
+        PlcAuditError::Encoding {
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<PlcAuditError> for MigrationError {
+    fn from(error: PlcAuditError) -> Self {
+        match error {
+            PlcAuditError::Encoding { message } => MigrationError::Runtime { message },
+            error => MigrationError::VerificationFailed {
+                failed: vec![error.to_string()],
+            },
+        }
+    }
+}
+
+/// Checks the audit log of `did` without trusting the directory that served it: every CID is
+/// recomputed, every `prev` must name an earlier operation and every signature must come from a
+/// rotation key of the operation it follows. Nullified operations must have been overridden by
+/// a higher priority key within the recovery window. A legacy `create` genesis is checked in
+/// its own encoding, with its recovery and signing keys as rotation keys.
+///
+/// Returns the entry the DID document is currently built from.
+pub fn verify_plc_audit_log<'a>(
+    did: &str,
+    audit_log: &'a [PlcLogAuditEntry],
+) -> Result<&'a PlcLogAuditEntry, PlcAuditError> {
+    let genesis = audit_log.first().ok_or(PlcAuditError::Empty)?;
+    for (index, entry) in audit_log.iter().enumerate() {
+        if entry.did != did {
+            return Err(PlcAuditError::WrongDid {
+                index,
+                did: entry.did.clone(),
+            });
+        }
+        let computed = plc_operation_cid(&entry.operation)?.to_string();
+        if computed != entry.cid {
+            return Err(PlcAuditError::CidMismatch {
+                index,
+                cid: entry.cid.clone(),
+                computed,
+            });
+        }
+    }
+
+    if genesis.operation.prev().is_some() {
+        return Err(PlcAuditError::BrokenChain { index: 0 });
+    }
+    if genesis.nullified {
+        return Err(PlcAuditError::InvalidNullification { index: 0 });
+    }
+    let computed = plc_did(&genesis.operation)?;
+    if computed != did {
+        return Err(PlcAuditError::GenesisMismatch { computed });
+    }
+    let genesis_signer =
+        verify_plc_signature(&genesis.operation, &genesis.operation.rotation_keys())?
+            .ok_or(PlcAuditError::InvalidSignature { index: 0 })?;
+
+    let mut indexes = HashMap::from([(genesis.cid.as_str(), 0)]);
+    let mut signers = vec![genesis_signer];
+    let mut prevs = vec![0];
+    // The last operation that was not nullified
+    let mut head = 0;
+    for (index, entry) in audit_log.iter().enumerate().skip(1) {
+        // Only a genesis operation may use the legacy format
+        if matches!(entry.operation, PlcLogOperation::LegacyCreate(_)) {
+            return Err(PlcAuditError::BrokenChain { index });
+        }
+        let prev = entry
+            .operation
+            .prev()
+            .and_then(|prev| indexes.get(prev))
+            .copied()
+            // Only the head, or an operation that was later nullified, can be built upon
+            .filter(|prev| *prev >= head)
+            .ok_or(PlcAuditError::BrokenChain { index })?;
+        let signer =
+            verify_plc_signature(&entry.operation, &audit_log[prev].operation.rotation_keys())?
+                .ok_or(PlcAuditError::InvalidSignature { index })?;
+        signers.push(signer);
+        prevs.push(prev);
+        indexes.insert(entry.cid.as_str(), index);
+        if entry.nullified {
+            continue;
+        }
+        if prev != head {
+            return Err(PlcAuditError::BrokenChain { index });
+        }
+        // Everything since the head was nullified by this operation, so it must outrank the
+        // operation it replaced on top of the head and come soon enough after it
+        if let Some(disputed) = (head + 1..index).rev().find(|k| prevs[*k] == head) {
+            let elapsed = created_at(audit_log, index)?
+                .as_ref()
+                .signed_duration_since(*created_at(audit_log, disputed)?.as_ref());
+            if signer >= signers[disputed] || elapsed.num_seconds() > PLC_RECOVERY_WINDOW_SECS {
+                return Err(PlcAuditError::InvalidNullification { index: disputed });
+            }
+        }
+        head = index;
+    }
+    if head + 1 < audit_log.len() {
+        return Err(PlcAuditError::InvalidNullification { index: head + 1 });
+    }
+    Ok(&audit_log[head])
+}
+
+/// Position in `rotation_keys` of the key that signed `operation`, if any did.
+pub fn verify_plc_signature(
+    operation: &PlcLogOperation,
+    rotation_keys: &[String],
+) -> Result<Option<usize>, PlcAuditError> {
+    let Some(sig) = operation
+        .sig()
+        .and_then(|sig| multibase::Base::Base64Url.decode(sig).ok())
+    else {
+        return Ok(None);
+    };
+    let unsigned = dag_cbor(&operation.unsigned())?;
+    Ok(rotation_keys
+        .iter()
+        .position(|did_key| verify_did_key_signature(did_key, &unsigned, &sig)))
+}
+
+fn verify_did_key_signature(did_key: &str, data: &[u8], sig: &[u8]) -> bool {
+    let Some((multibase::Base::Base58Btc, bytes)) = did_key
+        .strip_prefix("did:key:")
+        .and_then(|multikey| multibase::decode(multikey).ok())
+    else {
+        return false;
+    };
+    // atproto only accepts low-s signatures, which libsecp256k1 enforces on its own
+    if let Some(key) = bytes.strip_prefix(&SECP256K1_PUB) {
+        let (Ok(key), Ok(sig)) = (PublicKey::from_slice(key), Signature::from_compact(sig)) else {
+            return false;
+        };
+        let message = Message::from_digest(Sha256::digest(data).into());
+        Secp256k1::verification_only()
+            .verify_ecdsa(message, &sig, &key)
+            .is_ok()
+    } else if let Some(key) = bytes.strip_prefix(&P256_PUB) {
+        let (Ok(key), Ok(sig)) = (
+            p256::ecdsa::VerifyingKey::from_sec1_bytes(key),
+            p256::ecdsa::Signature::from_slice(sig),
+        ) else {
+            return false;
+        };
+        sig.normalize_s().is_none() && key.verify(data, &sig).is_ok()
+    } else {
+        false
+    }
+}
+
+/// The did:plc a signed genesis operation creates.
+pub fn plc_did(genesis: &PlcLogOperation) -> Result<String, PlcAuditError> {
+    let hash = Sha256::digest(dag_cbor(genesis)?);
+    let encoded = multibase::encode(multibase::Base::Base32Lower, hash);
+    Ok(format!("did:plc:{}", &encoded[1..25]))
+}
+
+fn created_at(audit_log: &[PlcLogAuditEntry], index: usize) -> Result<Datetime, PlcAuditError> {
+    Datetime::from_str(&audit_log[index].created_at)
+        .map_err(|_error| PlcAuditError::InvalidTimestamp { index })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        atproto_sign, create_update_op, decode_did_secret_key, public_key_to_did_key,
+        PlcLegacyCreate,
+    };
+    use secp256k1::SecretKey;
+
+    fn rotation_key(seed: u8) -> (SecretKey, String) {
+        let (secret_key, public_key) = decode_did_secret_key(&hex::encode([seed; 32])).unwrap();
+        (secret_key, public_key_to_did_key(public_key))
+    }
+
+    fn genesis(rotation_keys: &[&str], key: &SecretKey) -> PlcLogOperation {
+        let operation = serde_json::from_value(serde_json::json!({
+            "type": "plc_operation",
+            "rotationKeys": rotation_keys,
+            "verificationMethods": {
+                "atproto": "did:key:zQ3shokFTS3brHcDQrn82RUDfCZESWL1ZdCEJwekUDPQiYBme"
+            },
+            "alsoKnownAs": ["at://alice.example.com"],
+            "services": {
+                "atproto_pds": {
+                    "type": "AtprotoPersonalDataServer",
+                    "endpoint": "https://origin.example.com"
+                }
+            },
+            "prev": null,
+        }))
+        .unwrap();
+        crate::add_signature(operation, key).unwrap().into()
+    }
+
+    fn move_to(last_op: &PlcLogOperation, key: &SecretKey, endpoint: &str) -> PlcLogOperation {
+        create_update_op(last_op, key, |mut operation| {
+            operation.services.get_mut("atproto_pds").unwrap().endpoint = endpoint.to_string();
+            operation
+        })
+        .unwrap()
+        .into()
+    }
+
+    fn audit_log(operations: &[(&PlcLogOperation, &str, bool)]) -> (String, Vec<PlcLogAuditEntry>) {
+        let did = plc_did(operations[0].0).unwrap();
+        let entries = operations
+            .iter()
+            .map(|(operation, created_at, nullified)| PlcLogAuditEntry {
+                did: did.clone(),
+                operation: (*operation).clone(),
+                cid: plc_operation_cid(operation).unwrap().to_string(),
+                nullified: *nullified,
+                created_at: created_at.to_string(),
+            })
+            .collect();
+        (did, entries)
+    }
+
+    #[test]
+    fn test_verify_plc_audit_log_checks_hashes_and_signatures() {
+        let (key, did_key) = rotation_key(1);
+        let (other_key, _) = rotation_key(2);
+        let genesis = genesis(&[&did_key], &key);
+        let update = move_to(&genesis, &key, "https://destination.example.com");
+        let (did, mut entries) = audit_log(&[
+            (&genesis, "2024-01-01T00:00:00.000Z", false),
+            (&update, "2024-06-01T00:00:00.000Z", false),
+        ]);
+
+        let current = verify_plc_audit_log(&did, &entries).unwrap();
+        assert_eq!(current.cid, entries[1].cid);
+        assert_eq!(
+            verify_plc_audit_log("did:plc:abcdefghijklmnopqrstuvwx", &entries).unwrap_err(),
+            PlcAuditError::WrongDid {
+                index: 0,
+                did: did.clone()
+            }
+        );
+
+        // An edited operation no longer hashes to the CID the directory listed
+        let PlcLogOperation::Operation(update) = &mut entries[1].operation else {
+            panic!("expected a plc_operation");
+        };
+        update.services.get_mut("atproto_pds").unwrap().endpoint =
+            "https://attacker.example.com".to_string();
+        assert!(matches!(
+            verify_plc_audit_log(&did, &entries).unwrap_err(),
+            PlcAuditError::CidMismatch { index: 1, .. }
+        ));
+
+        // Nor does relisting it under its new CID help, as the signature no longer matches
+        let forged = move_to(&genesis, &other_key, "https://attacker.example.com");
+        let (_, forged_entries) = audit_log(&[
+            (&genesis, "2024-01-01T00:00:00.000Z", false),
+            (&forged, "2024-06-01T00:00:00.000Z", false),
+        ]);
+        assert_eq!(
+            verify_plc_audit_log(&did, &forged_entries).unwrap_err(),
+            PlcAuditError::InvalidSignature { index: 1 }
+        );
+    }
+
+    #[test]
+    fn test_verify_plc_audit_log_checks_nullification() {
+        let (recovery_key, recovery_did_key) = rotation_key(1);
+        let (pds_key, pds_did_key) = rotation_key(2);
+        let genesis = genesis(&[&recovery_did_key, &pds_did_key], &pds_key);
+        let hijack = move_to(&genesis, &pds_key, "https://attacker.example.com");
+        let recovery = move_to(&genesis, &recovery_key, "https://origin.example.com");
+
+        let (did, entries) = audit_log(&[
+            (&genesis, "2024-01-01T00:00:00.000Z", false),
+            (&hijack, "2024-06-01T00:00:00.000Z", true),
+            (&recovery, "2024-06-02T00:00:00.000Z", false),
+        ]);
+        let current = verify_plc_audit_log(&did, &entries).unwrap();
+        assert_eq!(current.cid, entries[2].cid);
+
+        // Recovering after the window closed is not allowed
+        let (_, late) = audit_log(&[
+            (&genesis, "2024-01-01T00:00:00.000Z", false),
+            (&hijack, "2024-06-01T00:00:00.000Z", true),
+            (&recovery, "2024-06-05T00:00:00.000Z", false),
+        ]);
+        assert_eq!(
+            verify_plc_audit_log(&did, &late).unwrap_err(),
+            PlcAuditError::InvalidNullification { index: 1 }
+        );
+
+        // A key can not override an operation of equal priority
+        let rival = move_to(&genesis, &pds_key, "https://rival.example.com");
+        let (_, outranked) = audit_log(&[
+            (&genesis, "2024-01-01T00:00:00.000Z", false),
+            (&hijack, "2024-06-01T00:00:00.000Z", true),
+            (&rival, "2024-06-02T00:00:00.000Z", false),
+        ]);
+        assert_eq!(
+            verify_plc_audit_log(&did, &outranked).unwrap_err(),
+            PlcAuditError::InvalidNullification { index: 1 }
+        );
+    }
+
+    #[test]
+    fn test_verify_plc_audit_log_checks_successive_recoveries() {
+        let (top_key, top_did_key) = rotation_key(1);
+        let (middle_key, middle_did_key) = rotation_key(2);
+        let (pds_key, pds_did_key) = rotation_key(3);
+        let genesis = genesis(&[&top_did_key, &middle_did_key, &pds_did_key], &pds_key);
+        let hijack = move_to(&genesis, &pds_key, "https://attacker.example.com");
+        let first = move_to(&genesis, &middle_key, "https://first.example.com");
+        let second = move_to(&genesis, &top_key, "https://second.example.com");
+
+        // Both recoveries fork from genesis; the second one overrides the first, not the hijack
+        let (did, entries) = audit_log(&[
+            (&genesis, "2024-01-01T00:00:00.000Z", false),
+            (&hijack, "2024-06-01T00:00:00.000Z", true),
+            (&first, "2024-06-02T00:00:00.000Z", true),
+            (&second, "2024-06-04T12:00:00.000Z", false),
+        ]);
+        let current = verify_plc_audit_log(&did, &entries).unwrap();
+        assert_eq!(current.cid, entries[3].cid);
+
+        // Outranking the hijack is not enough when the key only ties with the first recovery
+        let rival = move_to(&genesis, &middle_key, "https://rival.example.com");
+        let (_, tied) = audit_log(&[
+            (&genesis, "2024-01-01T00:00:00.000Z", false),
+            (&hijack, "2024-06-01T00:00:00.000Z", true),
+            (&first, "2024-06-02T00:00:00.000Z", true),
+            (&rival, "2024-06-03T00:00:00.000Z", false),
+        ]);
+        assert_eq!(
+            verify_plc_audit_log(&did, &tied).unwrap_err(),
+            PlcAuditError::InvalidNullification { index: 2 }
+        );
+    }
+
+    #[test]
+    fn test_verify_plc_audit_log_accepts_legacy_genesis() {
+        let (recovery_key, recovery_did_key) = rotation_key(1);
+        let (signing_key, signing_did_key) = rotation_key(2);
+        let mut create = PlcLegacyCreate {
+            signing_key: signing_did_key.clone(),
+            recovery_key: recovery_did_key.clone(),
+            handle: "alice.example.com".to_string(),
+            service: "https://pds.example.com".to_string(),
+            prev: None,
+            sig: None,
+        };
+        let sig =
+            atproto_sign(&PlcLogOperation::LegacyCreate(create.clone()), &signing_key).unwrap();
+        create.sig = Some(multibase::encode(multibase::Base::Base64Url, sig)[1..].to_string());
+        let genesis = PlcLogOperation::LegacyCreate(create);
+
+        // The DID commits to the legacy encoding, not to the operation it normalizes to
+        let normalized = PlcLogOperation::from(genesis.normalized().unwrap());
+        assert_ne!(plc_did(&genesis).unwrap(), plc_did(&normalized).unwrap());
+        assert_eq!(
+            normalized.rotation_keys(),
+            vec![recovery_did_key, signing_did_key]
+        );
+
+        let update = move_to(&genesis, &recovery_key, "https://destination.example.com");
+        let (did, entries) = audit_log(&[
+            (&genesis, "2023-01-01T00:00:00.000Z", false),
+            (&update, "2024-06-01T00:00:00.000Z", false),
+        ]);
+        assert_eq!(did, plc_did(&genesis).unwrap());
+        let current = verify_plc_audit_log(&did, &entries).unwrap();
+        let current = current.operation.normalized().unwrap();
+        assert_eq!(current.also_known_as, vec!["at://alice.example.com"]);
+        assert_eq!(
+            current.services["atproto_pds"].endpoint,
+            "https://destination.example.com"
+        );
+
+        // A legacy operation anywhere but genesis is not part of any valid history
+        let (_, late_create) = audit_log(&[
+            (&genesis, "2023-01-01T00:00:00.000Z", false),
+            (&genesis, "2024-06-01T00:00:00.000Z", false),
+        ]);
+        assert_eq!(
+            verify_plc_audit_log(&did, &late_create).unwrap_err(),
+            PlcAuditError::BrokenChain { index: 1 }
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use crate::{
+    plc_audit_log_key, send_governed, verify_plc_audit_log, MigrationError, PlcLogAudit,
+    PlcLogOperation, PlcOperation, RetryPolicy, StagingStore, DEFAULT_PLC_DIRECTORY,
+};
+use bsky_sdk::api::did_doc::DidDocument;
+use serde::de::DeserializeOwned;
+
+/// Talks to a PLC directory directly, without going through either PDS.
+#[derive(Debug, Clone)]
+pub struct PlcDirectory {
+    /// Base URL of the directory, e.g. `https://plc.directory`.
+    pub url: String,
+    pub retry: RetryPolicy,
+}
+
+impl Default for PlcDirectory {
+    fn default() -> Self {
+        Self::new(DEFAULT_PLC_DIRECTORY)
+    }
+}
+
+impl PlcDirectory {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into().trim_end_matches('/').to_string(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Every operation ever submitted for `did`, oldest first, including nullified ones.
+    #[tracing::instrument(skip(self))]
+    pub async fn audit_log(&self, did: &str) -> Result<PlcLogAudit, MigrationError> {
+        self.get(&format!("{}/{}/log/audit", self.url, did)).await
+    }
+
+    /// The operation the DID document is currently built from.
+    #[tracing::instrument(skip(self))]
+    pub async fn last_operation(&self, did: &str) -> Result<PlcLogOperation, MigrationError> {
+        self.get(&format!("{}/{}/log/last", self.url, did)).await
+    }
+
+    /// The operation the DID document is currently built from, read from an audit log that
+    /// [`verify_plc_audit_log`] proved genuine. Prefer this to [`Self::last_operation`] before
+    /// signing anything on top of it.
+    #[tracing::instrument(skip(self))]
+    pub async fn verified_last_operation(
+        &self,
+        did: &str,
+    ) -> Result<PlcLogOperation, MigrationError> {
+        let audit_log = self.audit_log(did).await?;
+        let current = verify_plc_audit_log(did, &audit_log)?;
+        Ok(current.operation.clone())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn did_document(&self, did: &str) -> Result<DidDocument, MigrationError> {
+        self.get(&format!("{}/{}", self.url, did)).await
+    }
+
+    /// Submits an operation signed by one of the DID's rotation keys.
+    #[tracing::instrument(skip(self, operation))]
+    pub async fn submit_operation(
+        &self,
+        did: &str,
+        operation: &PlcOperation,
+    ) -> Result<(), MigrationError> {
+        if operation.sig.is_none() {
+            return Err(MigrationError::Validation {
+                field: "sig".to_string(),
+            });
+        }
+        let client = reqwest::Client::new();
+        let request = client
+            .post(format!("{}/{}", self.url, did))
+            .json(&PlcLogOperation::from(operation.clone()))
+            .build()
+            .map_err(upstream)?;
+        let response = send_governed(&client, request, &self.retry)
+            .await
+            .map_err(upstream)?;
+        if !response.status().is_success() {
+            let error = MigrationError::from_response(&self.url, response).await;
+            tracing::error!("PLC directory rejected the operation: {}", error);
+            return Err(error);
+        }
+        tracing::info!("Submitted PLC operation for {}", did);
+        Ok(())
+    }
+
+    /// Writes the full audit log of `did` to [`plc_audit_log_key`], so a backup holds the
+    /// history needed to recover the identity. The directory's answer is stored byte for
+    /// byte, so the backup keeps anything this crate does not model.
+    #[tracing::instrument(skip(self, store))]
+    pub async fn export_audit_log(
+        &self,
+        did: &str,
+        store: &dyn StagingStore,
+    ) -> Result<PlcLogAudit, MigrationError> {
+        let bytes = self
+            .get_bytes(&format!("{}/{}/log/audit", self.url, did))
+            .await?;
+        store.put(&plc_audit_log_key(did), bytes.to_vec()).await?;
+        let audit_log: PlcLogAudit =
+            serde_json::from_slice(&bytes).map_err(|error| MigrationError::Upstream {
+                message: format!("The directory sent an unreadable audit log: {error}"),
+            })?;
+        tracing::info!("Exported {} PLC operations", audit_log.len());
+        Ok(audit_log)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, url: &str) -> Result<T, MigrationError> {
+        let bytes = self.get_bytes(url).await?;
+        serde_json::from_slice(&bytes).map_err(|error| MigrationError::Upstream {
+            message: error.to_string(),
+        })
+    }
+
+    async fn get_bytes(&self, url: &str) -> Result<bytes::Bytes, MigrationError> {
+        let client = reqwest::Client::new();
+        let request = client.get(url).build().map_err(upstream)?;
+        let response = send_governed(&client, request, &self.retry)
+            .await
+            .map_err(upstream)?;
+        if !response.status().is_success() {
+            return Err(MigrationError::from_response(&self.url, response).await);
+        }
+        response.bytes().await.map_err(upstream)
+    }
+}
+
+fn upstream(error: reqwest::Error) -> MigrationError {
+    MigrationError::Upstream {
+        message: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LocalStagingStore;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const DID: &str = "did:plc:example123";
+
+    fn operation(prev: Option<&str>) -> serde_json::Value {
+        serde_json::json!({
+            "type": "plc_operation",
+            "rotationKeys": ["did:key:zQ3shhCGUqDKjStzuDxPkTxN6ujddP4RkEKJJouJGRRkaLGbg"],
+            "verificationMethods": {
+                "atproto": "did:key:zQ3shokFTS3brHcDQrn82RUDfCZESWL1ZdCEJwekUDPQiYBme"
+            },
+            "alsoKnownAs": ["at://alice.example.com"],
+            "services": {
+                "atproto_pds": {
+                    "type": "AtprotoPersonalDataServer",
+                    "endpoint": "https://pds.example.com"
+                }
+            },
+            "prev": prev,
+            "sig": "c2lnbmF0dXJl",
+        })
+    }
+
+    fn directory(server: &MockServer) -> PlcDirectory {
+        PlcDirectory {
+            retry: RetryPolicy::none(),
+            ..PlcDirectory::new(format!("{}/", server.uri()))
+        }
+    }
+
+    #[test]
+    fn test_export_audit_log() {
+        tokio_test::block_on(async {
+            let server = MockServer::start().await;
+            let body = serde_json::json!([
+                {
+                    "did": DID,
+                    "operation": {
+                        "type": "create",
+                        "signingKey": "did:key:zQ3shokFTS3brHcDQrn82RUDfCZESWL1ZdCEJwekUDPQiYBme",
+                        "recoveryKey": "did:key:zQ3shhCGUqDKjStzuDxPkTxN6ujddP4RkEKJJouJGRRkaLGbg",
+                        "handle": "alice.example.com",
+                        "service": "https://pds.example.com",
+                        "prev": null,
+                        "sig": "c2lnbmF0dXJl",
+                    },
+                    "cid": "bafyreigenesis",
+                    "nullified": false,
+                    "createdAt": "2024-01-01T00:00:00.000Z",
+                },
+                {
+                    "did": DID,
+                    "operation": operation(Some("bafyreigenesis")),
+                    "cid": "bafyreisecond",
+                    "nullified": false,
+                    "createdAt": "2024-06-01T00:00:00.000Z",
+                    // Not modeled here, but part of the record the backup has to keep
+                    "comment": "kept as served",
+                },
+                {
+                    "did": DID,
+                    "operation": {
+                        "type": "plc_tombstone",
+                        "prev": "bafyreisecond",
+                        "sig": "c2lnbmF0dXJl",
+                    },
+                    "cid": "bafyreithird",
+                    "nullified": false,
+                    "createdAt": "2024-07-01T00:00:00.000Z",
+                },
+            ]);
+            Mock::given(method("GET"))
+                .and(path(format!("/{DID}/log/audit")))
+                .respond_with(ResponseTemplate::new(200).set_body_json(&body))
+                .mount(&server)
+                .await;
+
+            let staging_dir = std::env::temp_dir().join("pdsmigration-plc-directory-test");
+            let store = LocalStagingStore::new(&staging_dir);
+            let audit_log = directory(&server)
+                .export_audit_log(DID, &store)
+                .await
+                .unwrap();
+            assert_eq!(audit_log.len(), 3);
+            assert!(matches!(
+                audit_log[0].operation,
+                PlcLogOperation::LegacyCreate(_)
+            ));
+            assert_eq!(audit_log[2].operation.prev(), Some("bafyreisecond"));
+
+            let exported = store.get(&plc_audit_log_key(DID)).await.unwrap();
+            assert_eq!(exported, serde_json::to_vec(&body).unwrap());
+            let _ = std::fs::remove_dir_all(staging_dir);
+        });
+    }
+
+    #[test]
+    fn test_submit_operation_keeps_directory_error() {
+        tokio_test::block_on(async {
+            let server = MockServer::start().await;
+            Mock::given(method("POST"))
+                .and(path(format!("/{DID}")))
+                .and(body_partial_json(
+                    serde_json::json!({ "prev": "bafyreisecond" }),
+                ))
+                .respond_with(
+                    ResponseTemplate::new(400)
+                        .set_body_json(serde_json::json!({ "message": "Invalid signature on op" })),
+                )
+                .mount(&server)
+                .await;
+
+            let operation: PlcOperation =
+                serde_json::from_value(operation(Some("bafyreisecond"))).unwrap();
+            let error = directory(&server)
+                .submit_operation(DID, &operation)
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(&error, MigrationError::Xrpc { upstream } if upstream.message == "Invalid signature on op")
+            );
+
+            let unsigned = PlcOperation {
+                sig: None,
+                ..operation
+            };
+            let error = directory(&server)
+                .submit_operation(DID, &unsigned)
+                .await
+                .unwrap_err();
+            assert!(matches!(error, MigrationError::Validation { field } if field == "sig"));
+        });
+    }
+}
@@ -0,0 +1,348 @@
+use crate::agent::{login_helper, recommended_plc};
+use crate::migrate_plc::proposed_plc;
+use crate::{
+    build_agent, plc_operation_cid, MigrationError, MigrationOptions, PlcDirectory,
+    PlcLogOperation, PlcOperation, PlcSigningError,
+};
+use bsky_sdk::api::com::atproto::identity::sign_plc_operation::InputData;
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Deserialize, Serialize)]
+pub struct PreviewPlcRequest {
+    pub destination: String,
+    pub destination_token: String,
+    pub origin: String,
+    pub did: String,
+    pub origin_token: String,
+    #[serde(skip_serializing_if = "core::option::Option::is_none")]
+    pub user_recovery_key: Option<String>,
+}
+
+impl std::fmt::Debug for PreviewPlcRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PreviewPlcRequest")
+            .field("destination", &self.destination)
+            .field("destination_token", &"[REDACTED]")
+            .field("origin", &self.origin)
+            .field("did", &self.did)
+            .field("origin_token", &"[REDACTED]")
+            .field("user_recovery_key", &self.user_recovery_key)
+            .finish()
+    }
+}
+
+/// A change to a single value of the operation; `None` means the value is absent.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PlcFieldChange {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+impl fmt::Display for PlcFieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} -> {}",
+            self.from.as_deref().unwrap_or("(none)"),
+            self.to.as_deref().unwrap_or("(none)")
+        )
+    }
+}
+
+/// A change that could cost the user control of their DID.
+#[derive(Debug, Clone, PartialEq, Eq, Display, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum PlcDiffWarning {
+    /// A rotation key the origin PDS does not hold is dropped, most likely the user's own
+    /// recovery key.
+    #[display("Rotation key {did_key} is removed and can no longer recover the DID")]
+    RecoveryKeyRemoved { did_key: String },
+    /// A rotation key the origin PDS does not hold loses priority, so keys now ahead of it can
+    /// override its operations.
+    #[display("Rotation key {did_key} loses priority")]
+    RecoveryKeyDemoted { did_key: String },
+    #[display("The new operation names no PDS")]
+    PdsEndpointMissing,
+}
+
+/// What signing the proposed operation would change in the DID.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PlcOperationDiff {
+    pub rotation_keys_added: Vec<String>,
+    pub rotation_keys_removed: Vec<String>,
+    /// Whether the keys in both operations change priority relative to each other.
+    pub rotation_keys_reordered: bool,
+    pub signing_key: Option<PlcFieldChange>,
+    pub also_known_as: Option<PlcFieldChange>,
+    pub pds_endpoint: Option<PlcFieldChange>,
+    pub warnings: Vec<PlcDiffWarning>,
+}
+
+impl PlcOperationDiff {
+    pub fn is_empty(&self) -> bool {
+        self.rotation_keys_added.is_empty()
+            && self.rotation_keys_removed.is_empty()
+            && !self.rotation_keys_reordered
+            && self.signing_key.is_none()
+            && self.also_known_as.is_none()
+            && self.pds_endpoint.is_none()
+    }
+}
+
+impl fmt::Display for PlcOperationDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            writeln!(f, "No changes")?;
+        }
+        for did_key in &self.rotation_keys_added {
+            writeln!(f, "+ Rotation key {did_key}")?;
+        }
+        for did_key in &self.rotation_keys_removed {
+            writeln!(f, "- Rotation key {did_key}")?;
+        }
+        if self.rotation_keys_reordered {
+            writeln!(f, "~ Rotation keys reordered")?;
+        }
+        if let Some(change) = &self.signing_key {
+            writeln!(f, "~ Signing key: {change}")?;
+        }
+        if let Some(change) = &self.also_known_as {
+            writeln!(f, "~ Also known as: {change}")?;
+        }
+        if let Some(change) = &self.pds_endpoint {
+            writeln!(f, "~ PDS: {change}")?;
+        }
+        for warning in &self.warnings {
+            writeln!(f, "! {warning}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The operation `migrate_plc_api` would have the origin sign, next to the one it replaces.
+#[derive(Debug, Clone)]
+pub struct PlcPreview {
+    pub current: PlcOperation,
+    /// Unsigned; `prev` already names `current`.
+    pub proposed: PlcOperation,
+    pub diff: PlcOperationDiff,
+}
+
+/// Dry run of `migrate_plc_api`: builds the same operation without asking the origin to sign
+/// it, and compares it with the latest operation of the verified audit log.
+#[tracing::instrument(skip(directory, options))]
+pub async fn preview_plc_api(
+    req: PreviewPlcRequest,
+    directory: &PlcDirectory,
+    options: &MigrationOptions,
+) -> Result<PlcPreview, MigrationError> {
+    if !req.did.starts_with("did:plc:") {
+        return Err(MigrationError::Validation {
+            field: "did".to_string(),
+        });
+    }
+    let agent = build_agent(&options.retry).await?;
+    login_helper(
+        &agent,
+        req.destination.as_str(),
+        req.did.as_str(),
+        req.destination_token.as_str(),
+    )
+    .await?;
+    let input = proposed_plc(recommended_plc(&agent).await?, req.user_recovery_key, None);
+    login_helper(
+        &agent,
+        req.origin.as_str(),
+        req.did.as_str(),
+        req.origin_token.as_str(),
+    )
+    .await?;
+    // The origin recommends its own keys, which are expected to go
+    let origin_keys = recommended_plc(&agent)
+        .await?
+        .rotation_keys
+        .unwrap_or_default();
+
+    let last_op = directory.verified_last_operation(&req.did).await?;
+    let current = last_op.normalized().ok_or(PlcSigningError::Tombstoned)?;
+    let proposed = plc_operation_from_input(input, &last_op)?;
+    let diff = diff_plc_operations(&current, &proposed, &origin_keys);
+    tracing::info!("Proposed PLC operation:\n{}", diff);
+    Ok(PlcPreview {
+        current,
+        proposed,
+        diff,
+    })
+}
+
+/// Compares two operations of a DID. Rotation keys in `origin_keys` belong to the PDS being
+/// left, so dropping or demoting them is not a warning.
+pub fn diff_plc_operations(
+    current: &PlcOperation,
+    proposed: &PlcOperation,
+    origin_keys: &[String],
+) -> PlcOperationDiff {
+    let mut diff = PlcOperationDiff {
+        rotation_keys_added: proposed
+            .rotation_keys
+            .iter()
+            .filter(|did_key| !current.rotation_keys.contains(did_key))
+            .cloned()
+            .collect(),
+        rotation_keys_removed: current
+            .rotation_keys
+            .iter()
+            .filter(|did_key| !proposed.rotation_keys.contains(did_key))
+            .cloned()
+            .collect(),
+        ..PlcOperationDiff::default()
+    };
+    let kept = |operation: &PlcOperation| -> Vec<String> {
+        operation
+            .rotation_keys
+            .iter()
+            .filter(|did_key| {
+                current.rotation_keys.contains(did_key) && proposed.rotation_keys.contains(did_key)
+            })
+            .cloned()
+            .collect()
+    };
+    diff.rotation_keys_reordered = kept(current) != kept(proposed);
+
+    let signing_key =
+        |operation: &PlcOperation| operation.verification_methods.get("atproto").cloned();
+    diff.signing_key = change(signing_key(current), signing_key(proposed));
+    let also_known_as = |operation: &PlcOperation| {
+        Some(operation.also_known_as.join(", ")).filter(|aka| !aka.is_empty())
+    };
+    diff.also_known_as = change(also_known_as(current), also_known_as(proposed));
+    let pds_endpoint = |operation: &PlcOperation| {
+        operation
+            .services
+            .get("atproto_pds")
+            .map(|service| service.endpoint.clone())
+    };
+    diff.pds_endpoint = change(pds_endpoint(current), pds_endpoint(proposed));
+
+    for (position, did_key) in current.rotation_keys.iter().enumerate() {
+        if origin_keys.contains(did_key) {
+            continue;
+        }
+        match proposed.rotation_keys.iter().position(|key| key == did_key) {
+            None => diff.warnings.push(PlcDiffWarning::RecoveryKeyRemoved {
+                did_key: did_key.clone(),
+            }),
+            Some(new_position) if new_position > position => {
+                diff.warnings.push(PlcDiffWarning::RecoveryKeyDemoted {
+                    did_key: did_key.clone(),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    if pds_endpoint(proposed).is_none() {
+        diff.warnings.push(PlcDiffWarning::PdsEndpointMissing);
+    }
+    diff
+}
+
+fn change(from: Option<String>, to: Option<String>) -> Option<PlcFieldChange> {
+    (from != to).then_some(PlcFieldChange { from, to })
+}
+
+fn plc_operation_from_input(
+    input: InputData,
+    last_op: &PlcLogOperation,
+) -> Result<PlcOperation, MigrationError> {
+    let prev = plc_operation_cid(last_op)?;
+    serde_json::from_value(serde_json::json!({
+        "type": "plc_operation",
+        "rotationKeys": input.rotation_keys.unwrap_or_default(),
+        "verificationMethods": input.verification_methods,
+        "alsoKnownAs": input.also_known_as.unwrap_or_default(),
+        "services": input.services,
+        "prev": prev.to_string(),
+    }))
+    .map_err(|error| MigrationError::Upstream {
+        message: format!("The destination recommends an invalid operation: {error}"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn operation(rotation_keys: &[&str], endpoint: &str) -> PlcOperation {
+        serde_json::from_value(serde_json::json!({
+            "type": "plc_operation",
+            "rotationKeys": rotation_keys,
+            "verificationMethods": { "atproto": format!("did:key:signing-{endpoint}") },
+            "alsoKnownAs": ["at://alice.example.com"],
+            "services": {
+                "atproto_pds": {
+                    "type": "AtprotoPersonalDataServer",
+                    "endpoint": endpoint,
+                }
+            },
+            "prev": null,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_diff_flags_dropped_recovery_key() {
+        let current = operation(
+            &["did:key:user", "did:key:origin"],
+            "https://origin.example.com",
+        );
+        let proposed = operation(&["did:key:destination"], "https://destination.example.com");
+
+        let diff = diff_plc_operations(&current, &proposed, &["did:key:origin".to_string()]);
+        assert_eq!(diff.rotation_keys_added, vec!["did:key:destination"]);
+        assert_eq!(
+            diff.rotation_keys_removed,
+            vec!["did:key:user", "did:key:origin"]
+        );
+        assert_eq!(
+            diff.pds_endpoint,
+            Some(PlcFieldChange {
+                from: Some("https://origin.example.com".to_string()),
+                to: Some("https://destination.example.com".to_string()),
+            })
+        );
+        assert!(diff.signing_key.is_some());
+        assert_eq!(diff.also_known_as, None);
+        assert_eq!(
+            diff.warnings,
+            vec![PlcDiffWarning::RecoveryKeyRemoved {
+                did_key: "did:key:user".to_string()
+            }]
+        );
+
+        // Keeping the recovery key first is the expected migration
+        let proposed = operation(
+            &["did:key:user", "did:key:destination"],
+            "https://destination.example.com",
+        );
+        let diff = diff_plc_operations(&current, &proposed, &["did:key:origin".to_string()]);
+        assert!(diff.warnings.is_empty());
+        assert!(!diff.rotation_keys_reordered);
+
+        let proposed = operation(
+            &["did:key:destination", "did:key:user"],
+            "https://destination.example.com",
+        );
+        let diff = diff_plc_operations(&current, &proposed, &["did:key:origin".to_string()]);
+        assert_eq!(
+            diff.warnings,
+            vec![PlcDiffWarning::RecoveryKeyDemoted {
+                did_key: "did:key:user".to_string()
+            }]
+        );
+        assert!(diff
+            .to_string()
+            .contains("! Rotation key did:key:user loses priority"));
+    }
+}
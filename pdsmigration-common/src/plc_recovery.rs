@@ -0,0 +1,215 @@
+use crate::{
+    create_update_op, decode_did_secret_key, public_key_to_did_key, verify_plc_audit_log,
+    verify_plc_signature, MigrationError, PlcDirectory, PlcLogAuditEntry, PlcOperation,
+    PlcSigningError, PLC_RECOVERY_WINDOW_SECS,
+};
+use bsky_sdk::api::types::string::Datetime;
+use derive_more::{Display, Error};
+use secp256k1::{Secp256k1, SecretKey};
+use std::str::FromStr;
+
+/// Why an operation can not be nullified.
+#[derive(Debug, Display, Error, PartialEq)]
+pub enum PlcRecoveryError {
+    /// Only operations the DID document is currently built from can be nullified, and never
+    /// the genesis operation.
+    #[display("{cid} is not a current operation of the DID")]
+    UnknownOperation { cid: String },
+    #[display("{did_key} does not rank above the key that signed the operation")]
+    Outranked { did_key: String },
+    #[display("The recovery window for {cid} has closed")]
+    WindowClosed { cid: String },
+}
+
+impl From<PlcRecoveryError> for MigrationError {
+    fn from(error: PlcRecoveryError) -> Self {
+        match error {
+            PlcRecoveryError::UnknownOperation { .. } => MigrationError::Validation {
+                field: "cid".to_string(),
+            },
+            PlcRecoveryError::Outranked { .. } => MigrationError::Validation {
+                field: "rotation_key".to_string(),
+            },
+            error @ PlcRecoveryError::WindowClosed { .. } => MigrationError::VerificationFailed {
+                failed: vec![error.to_string()],
+            },
+        }
+    }
+}
+
+/// Operations a higher priority rotation key can still nullify: those after genesis that the
+/// DID document is built from, submitted within [`PLC_RECOVERY_WINDOW_SECS`] of `now`.
+pub fn recoverable_operations<'a>(
+    audit_log: &'a [PlcLogAuditEntry],
+    now: &Datetime,
+) -> Vec<&'a PlcLogAuditEntry> {
+    audit_log
+        .iter()
+        .skip(1)
+        .filter(|entry| !entry.nullified && within_window(entry, now))
+        .collect()
+}
+
+/// Builds the operation that nullifies `cid` and everything after it: `edit` changes a copy of
+/// the operation `cid` replaced, which is then signed with `key` on top of it. `key` must rank
+/// above the key that signed `cid` in that operation's rotation keys.
+pub fn build_plc_recovery_op<G>(
+    did: &str,
+    audit_log: &[PlcLogAuditEntry],
+    key: &SecretKey,
+    cid: &str,
+    now: &Datetime,
+    edit: G,
+) -> Result<PlcOperation, MigrationError>
+where
+    G: FnOnce(PlcOperation) -> PlcOperation,
+{
+    verify_plc_audit_log(did, audit_log)?;
+    let unknown = || PlcRecoveryError::UnknownOperation {
+        cid: cid.to_string(),
+    };
+    let disputed = audit_log
+        .iter()
+        .skip(1)
+        .find(|entry| entry.cid == cid && !entry.nullified)
+        .ok_or_else(unknown)?;
+    // The log is verified, so the operation it replaced is in it
+    let replaced = audit_log
+        .iter()
+        .find(|entry| Some(entry.cid.as_str()) == disputed.operation.prev())
+        .ok_or_else(unknown)?;
+
+    let did_key = public_key_to_did_key(key.public_key(&Secp256k1::new()));
+    let rotation_keys = &replaced.operation.rotation_keys();
+    let rank = rotation_keys
+        .iter()
+        .position(|rotation_key| *rotation_key == did_key)
+        .ok_or_else(|| PlcSigningError::NotARotationKey {
+            did_key: did_key.clone(),
+        })?;
+    let signer = verify_plc_signature(&disputed.operation, rotation_keys)?.unwrap_or_default();
+    if rank >= signer {
+        return Err(PlcRecoveryError::Outranked { did_key }.into());
+    }
+    if !within_window(disputed, now) {
+        return Err(PlcRecoveryError::WindowClosed {
+            cid: cid.to_string(),
+        }
+        .into());
+    }
+    Ok(create_update_op(&replaced.operation, key, edit)?)
+}
+
+/// Uses PLC's recovery window against an unwanted operation, e.g. one a compromised PDS
+/// pushed: a fork signed with a higher priority rotation key the user holds replaces `cid` and
+/// everything after it. `edit` changes the state from before `cid`; pass `|op| op` to restore it.
+#[tracing::instrument(skip(directory, rotation_key, edit))]
+pub async fn recover_plc_with_rotation_key<G>(
+    directory: &PlcDirectory,
+    did: &str,
+    rotation_key: &str,
+    cid: &str,
+    edit: G,
+) -> Result<PlcOperation, MigrationError>
+where
+    G: FnOnce(PlcOperation) -> PlcOperation,
+{
+    let (secret_key, _) = decode_did_secret_key(rotation_key)?;
+    let audit_log = directory.audit_log(did).await?;
+    let operation =
+        build_plc_recovery_op(did, &audit_log, &secret_key, cid, &Datetime::now(), edit)?;
+    directory.submit_operation(did, &operation).await?;
+    tracing::info!("Nullified {} and the operations after it", cid);
+    Ok(operation)
+}
+
+fn within_window(entry: &PlcLogAuditEntry, now: &Datetime) -> bool {
+    Datetime::from_str(&entry.created_at).is_ok_and(|created_at| {
+        now.as_ref()
+            .signed_duration_since(*created_at.as_ref())
+            .num_seconds()
+            <= PLC_RECOVERY_WINDOW_SECS
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{add_signature, plc_did, plc_operation_cid, PlcLogOperation};
+
+    fn rotation_key(seed: u8) -> (SecretKey, String) {
+        let (secret_key, public_key) = decode_did_secret_key(&hex::encode([seed; 32])).unwrap();
+        (secret_key, public_key_to_did_key(public_key))
+    }
+
+    fn entry(did: &str, operation: &PlcLogOperation, created_at: &str) -> PlcLogAuditEntry {
+        PlcLogAuditEntry {
+            did: did.to_string(),
+            operation: operation.clone(),
+            cid: plc_operation_cid(operation).unwrap().to_string(),
+            nullified: false,
+            created_at: created_at.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_build_plc_recovery_op() {
+        let (recovery_key, recovery_did_key) = rotation_key(1);
+        let (pds_key, pds_did_key) = rotation_key(2);
+        let genesis: PlcOperation = serde_json::from_value(serde_json::json!({
+            "type": "plc_operation",
+            "rotationKeys": [recovery_did_key, pds_did_key],
+            "verificationMethods": {
+                "atproto": "did:key:zQ3shokFTS3brHcDQrn82RUDfCZESWL1ZdCEJwekUDPQiYBme"
+            },
+            "alsoKnownAs": ["at://alice.example.com"],
+            "services": {
+                "atproto_pds": {
+                    "type": "AtprotoPersonalDataServer",
+                    "endpoint": "https://pds.example.com"
+                }
+            },
+            "prev": null,
+        }))
+        .unwrap();
+        let genesis = PlcLogOperation::from(add_signature(genesis, &pds_key).unwrap());
+        let hijack = create_update_op(&genesis, &pds_key, |mut operation| {
+            operation.rotation_keys = vec![pds_did_key.clone()];
+            operation
+        })
+        .unwrap()
+        .into();
+        let did = plc_did(&genesis).unwrap();
+        let mut audit_log = vec![
+            entry(&did, &genesis, "2024-01-01T00:00:00.000Z"),
+            entry(&did, &hijack, "2024-06-01T00:00:00.000Z"),
+        ];
+        let hijack_cid = audit_log[1].cid.clone();
+        let now = Datetime::from_str("2024-06-02T00:00:00.000Z").unwrap();
+        assert_eq!(recoverable_operations(&audit_log, &now).len(), 1);
+
+        // The PDS key signed the hijack, so it can not override it
+        let error = build_plc_recovery_op(&did, &audit_log, &pds_key, &hijack_cid, &now, |op| op)
+            .unwrap_err();
+        assert!(matches!(error, MigrationError::Validation { field } if field == "rotation_key"));
+
+        let late = Datetime::from_str("2024-06-05T00:00:00.000Z").unwrap();
+        assert!(recoverable_operations(&audit_log, &late).is_empty());
+        let error =
+            build_plc_recovery_op(&did, &audit_log, &recovery_key, &hijack_cid, &late, |op| op)
+                .unwrap_err();
+        assert!(matches!(error, MigrationError::VerificationFailed { .. }));
+
+        let recovery =
+            build_plc_recovery_op(&did, &audit_log, &recovery_key, &hijack_cid, &now, |op| op)
+                .unwrap();
+        assert_eq!(recovery.prev, Some(audit_log[0].cid.clone()));
+        assert_eq!(recovery.rotation_keys, genesis.rotation_keys());
+
+        // Once the directory accepts it, the log shows the hijack nullified
+        audit_log[1].nullified = true;
+        audit_log.push(entry(&did, &recovery.into(), "2024-06-02T00:00:00.000Z"));
+        let current = verify_plc_audit_log(&did, &audit_log).unwrap();
+        assert_eq!(current.operation.rotation_keys(), genesis.rotation_keys());
+    }
+}
@@ -0,0 +1,260 @@
+use crate::{public_key_to_did_key, MigrationError, PlcDirectory, PlcLogOperation, PlcOperation};
+use derive_more::{Display, Error};
+use ipld_core::cid::multihash::Multihash;
+use ipld_core::cid::Cid;
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+const SHA2_256: u64 = 0x12;
+const DAG_CBOR: u64 = 0x71;
+
+/// Why a PLC operation could not be built or signed.
+#[derive(Debug, Display, Error, PartialEq)]
+pub enum PlcSigningError {
+    #[display("Rotation key is not hex encoded")]
+    InvalidKeyEncoding,
+    #[display("Rotation key is not a secp256k1 private key")]
+    InvalidKey,
+    /// The key signing the operation is not among the rotation keys of the previous one, so
+    /// the directory would reject it.
+    #[display("{did_key} is not a rotation key of the DID")]
+    NotARotationKey { did_key: String },
+    #[display("The DID has been deactivated with a tombstone")]
+    Tombstoned,
+    #[display("Could not encode the operation: {message}")]
+    Encoding { message: String },
+}
+
+impl From<PlcSigningError> for MigrationError {
+    fn from(error: PlcSigningError) -> Self {
+        match error {
+            PlcSigningError::InvalidKeyEncoding
+            | PlcSigningError::InvalidKey
+            | PlcSigningError::NotARotationKey { .. } => MigrationError::Validation {
+                field: "rotation_key".to_string(),
+            },
+            PlcSigningError::Tombstoned => MigrationError::Validation {
+                field: "did".to_string(),
+            },
+            PlcSigningError::Encoding { message } => MigrationError::Runtime { message },
+        }
+    }
+}
+
+/// Reads a hex encoded secp256k1 private key, the format rotation keys are saved in.
+pub fn decode_did_secret_key(private_key: &str) -> Result<(SecretKey, PublicKey), PlcSigningError> {
+    let decoded_key =
+        hex::decode(private_key.trim()).map_err(|_error| PlcSigningError::InvalidKeyEncoding)?;
+    let secret_key = SecretKey::from_byte_array(
+        decoded_key
+            .try_into()
+            .map_err(|_error| PlcSigningError::InvalidKey)?,
+    )
+    .map_err(|_error| PlcSigningError::InvalidKey)?;
+    let public_key = secret_key.public_key(&Secp256k1::new());
+    Ok((secret_key, public_key))
+}
+
+/// The CID the directory assigns to an operation, which the next one names as `prev`.
+pub fn plc_operation_cid(operation: &PlcLogOperation) -> Result<Cid, PlcSigningError> {
+    let digest = Sha256::digest(dag_cbor(operation)?);
+    Ok(Cid::new_v1(
+        DAG_CBOR,
+        Multihash::wrap(SHA2_256, &digest).map_err(|error| PlcSigningError::Encoding {
+            message: error.to_string(),
+        })?,
+    ))
+}
+
+/// Builds the operation that follows `last_op`: `edit` changes a copy of the state it leaves
+/// the DID in, which is then chained onto `last_op` and signed with `key`.
+pub fn create_update_op<G>(
+    last_op: &PlcLogOperation,
+    key: &SecretKey,
+    edit: G,
+) -> Result<PlcOperation, PlcSigningError>
+where
+    G: FnOnce(PlcOperation) -> PlcOperation,
+{
+    let prev = plc_operation_cid(last_op)?;
+    // Drop the signature so it never carries over into the next operation
+    let normalized = last_op
+        .unsigned()
+        .normalized()
+        .ok_or(PlcSigningError::Tombstoned)?;
+    let mut unsigned = edit(normalized);
+    unsigned.prev = Some(prev.to_string());
+    add_signature(unsigned, key)
+}
+
+pub fn add_signature(
+    mut operation: PlcOperation,
+    key: &SecretKey,
+) -> Result<PlcOperation, PlcSigningError> {
+    operation.sig = None;
+    let sig = atproto_sign(&PlcLogOperation::from(operation.clone()), key)?;
+    operation.sig = Some(multibase::encode(multibase::Base::Base64Url, sig)[1..].to_string());
+    Ok(operation)
+}
+
+/// Signs the DAG-CBOR encoding of `obj` the way atproto expects: ECDSA over its sha-256,
+/// low-s normalized, in the 64 byte compact form.
+pub fn atproto_sign<T: Serialize>(obj: &T, key: &SecretKey) -> Result<[u8; 64], PlcSigningError> {
+    let hash = Sha256::digest(dag_cbor(obj)?);
+    let message = Message::from_digest(hash.into());
+    let mut sig = Secp256k1::new().sign_ecdsa(message, key);
+    sig.normalize_s();
+    Ok(sig.serialize_compact())
+}
+
+/// Updates a DID with a rotation key the user holds, without either PDS: the edit is applied
+/// to the latest operation of the verified audit log, signed and submitted.
+#[tracing::instrument(skip(directory, rotation_key, edit))]
+pub async fn update_plc_with_rotation_key<G>(
+    directory: &PlcDirectory,
+    did: &str,
+    rotation_key: &str,
+    edit: G,
+) -> Result<PlcOperation, MigrationError>
+where
+    G: FnOnce(PlcOperation) -> PlcOperation,
+{
+    let (secret_key, public_key) = decode_did_secret_key(rotation_key)?;
+    let last_op = directory.verified_last_operation(did).await?;
+    let did_key = public_key_to_did_key(public_key);
+    if !last_op.rotation_keys().contains(&did_key) {
+        return Err(PlcSigningError::NotARotationKey { did_key }.into());
+    }
+    let operation = create_update_op(&last_op, &secret_key, edit)?;
+    directory.submit_operation(did, &operation).await?;
+    Ok(operation)
+}
+
+// Structs and maps are both written with their keys in DAG-CBOR's canonical order.
+pub(crate) fn dag_cbor<T: Serialize>(obj: &T) -> Result<Vec<u8>, PlcSigningError> {
+    serde_ipld_dagcbor::to_vec(obj).map_err(|error| PlcSigningError::Encoding {
+        message: error.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{plc_did, RetryPolicy};
+    use secp256k1::ecdsa::Signature;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const ROTATION_KEY: &str = "9ae4c0b6e0a3a0e1ff51b8b1d1c4bb5a1a76c7c8e2d9f0e4a8b2c6d0e4f8a2b6";
+
+    fn genesis(rotation_key: &str) -> PlcLogOperation {
+        serde_json::from_value(serde_json::json!({
+            "type": "plc_operation",
+            "rotationKeys": [rotation_key],
+            "verificationMethods": {
+                "atproto": "did:key:zQ3shokFTS3brHcDQrn82RUDfCZESWL1ZdCEJwekUDPQiYBme"
+            },
+            "alsoKnownAs": ["at://alice.example.com"],
+            "services": {
+                "atproto_pds": {
+                    "type": "AtprotoPersonalDataServer",
+                    "endpoint": "https://origin.example.com"
+                }
+            },
+            "prev": null,
+            "sig": "c2lnbmF0dXJl",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_create_update_op_chains_and_signs() {
+        let (secret_key, public_key) = decode_did_secret_key(ROTATION_KEY).unwrap();
+        let last_op = genesis(&public_key_to_did_key(public_key));
+
+        let operation = create_update_op(&last_op, &secret_key, |mut operation| {
+            operation.services.get_mut("atproto_pds").unwrap().endpoint =
+                "https://destination.example.com".to_string();
+            operation
+        })
+        .unwrap();
+
+        assert_eq!(
+            operation.prev,
+            Some(plc_operation_cid(&last_op).unwrap().to_string())
+        );
+        assert_eq!(
+            operation.services["atproto_pds"].endpoint,
+            "https://destination.example.com"
+        );
+        let sig = multibase::Base::Base64Url
+            .decode(operation.sig.as_deref().unwrap())
+            .unwrap();
+        let unsigned = PlcLogOperation::from(operation).unsigned();
+        let hash = Sha256::digest(dag_cbor(&unsigned).unwrap());
+        Secp256k1::new()
+            .verify_ecdsa(
+                Message::from_digest(hash.into()),
+                &Signature::from_compact(&sig).unwrap(),
+                &public_key,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_decode_did_secret_key_rejects_bad_input() {
+        assert_eq!(
+            decode_did_secret_key("not hex").unwrap_err(),
+            PlcSigningError::InvalidKeyEncoding
+        );
+        assert_eq!(
+            decode_did_secret_key("abcd").unwrap_err(),
+            PlcSigningError::InvalidKey
+        );
+    }
+
+    #[test]
+    fn test_update_requires_a_rotation_key_of_the_did() {
+        tokio_test::block_on(async {
+            let (other_key, other_public_key) = decode_did_secret_key(&"11".repeat(32)).unwrap();
+            let genesis = genesis(&public_key_to_did_key(other_public_key))
+                .normalized()
+                .unwrap();
+            let genesis = PlcLogOperation::from(add_signature(genesis, &other_key).unwrap());
+            let did = plc_did(&genesis).unwrap();
+
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path(format!("/{did}/log/audit")))
+                .respond_with(
+                    ResponseTemplate::new(200).set_body_json(serde_json::json!([{
+                        "did": did,
+                        "operation": genesis,
+                        "cid": plc_operation_cid(&genesis).unwrap().to_string(),
+                        "nullified": false,
+                        "createdAt": "2024-01-01T00:00:00.000Z",
+                    }])),
+                )
+                .mount(&server)
+                .await;
+            Mock::given(method("POST"))
+                .and(path(format!("/{did}")))
+                .respond_with(ResponseTemplate::new(200))
+                .expect(0)
+                .mount(&server)
+                .await;
+            let directory = PlcDirectory {
+                retry: RetryPolicy::none(),
+                ..PlcDirectory::new(server.uri())
+            };
+
+            let error = update_plc_with_rotation_key(&directory, &did, ROTATION_KEY, |op| op)
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(error, MigrationError::Validation { field } if field == "rotation_key")
+            );
+        });
+    }
+}
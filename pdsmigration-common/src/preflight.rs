@@ -0,0 +1,419 @@
+use crate::agent::{login_helper, parse_did};
+use crate::{build_agent, MigrationAgent, MigrationError, MigrationOptions};
+use bsky_sdk::api::agent::Configure;
+use bsky_sdk::api::types::string::{Did, Handle};
+use ipld_core::ipld::Ipld;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize)]
+pub struct PreflightRequest {
+    pub origin: String,
+    pub destination: String,
+    pub did: String,
+    pub origin_token: String,
+    /// Checked when given, e.g. when the account already exists on the destination.
+    #[serde(default)]
+    pub destination_token: Option<String>,
+    /// The handle the account will be created with on the destination.
+    #[serde(default)]
+    pub handle: Option<String>,
+    #[serde(default)]
+    pub invite_code: Option<String>,
+}
+
+impl std::fmt::Debug for PreflightRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PreflightRequest")
+            .field("origin", &self.origin)
+            .field("destination", &self.destination)
+            .field("did", &self.did)
+            .field("origin_token", &"[REDACTED]")
+            .field(
+                "destination_token",
+                &self.destination_token.as_ref().map(|_| "[REDACTED]"),
+            )
+            .field("handle", &self.handle)
+            .field(
+                "invite_code",
+                &self.invite_code.as_ref().map(|_| "[REDACTED]"),
+            )
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PreflightCheck {
+    DidMethod,
+    OriginSession,
+    OriginRepo,
+    DestinationServer,
+    DestinationSession,
+    DestinationRepo,
+    Handle,
+    InviteCode,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PreflightIssue {
+    pub check: PreflightCheck,
+    pub message: String,
+}
+
+/// What stands in the way of a migration. Blockers will make a step fail; warnings are worth
+/// reading but do not stop it.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct PreflightReport {
+    pub blockers: Vec<PreflightIssue>,
+    pub warnings: Vec<PreflightIssue>,
+    pub invite_code_required: bool,
+    pub available_user_domains: Vec<String>,
+}
+
+impl PreflightReport {
+    pub fn ready(&self) -> bool {
+        self.blockers.is_empty()
+    }
+
+    fn block(&mut self, check: PreflightCheck, message: impl Into<String>) {
+        self.blockers.push(PreflightIssue {
+            check,
+            message: message.into(),
+        });
+    }
+
+    fn warn(&mut self, check: PreflightCheck, message: impl Into<String>) {
+        self.warnings.push(PreflightIssue {
+            check,
+            message: message.into(),
+        });
+    }
+}
+
+/// Checks both PDSes before anything is changed, collecting every problem into one report.
+///
+/// Upstream failures become report entries; only failures to run the checks at all are
+/// returned as errors.
+#[tracing::instrument(skip(options))]
+pub async fn preflight_api(
+    req: PreflightRequest,
+    options: &MigrationOptions,
+) -> Result<PreflightReport, MigrationError> {
+    let mut report = PreflightReport::default();
+    let did = match parse_did(&req.did) {
+        Ok(did) => did,
+        Err(_) => {
+            report.block(
+                PreflightCheck::DidMethod,
+                format!("{} is not a DID", req.did),
+            );
+            return Ok(report);
+        }
+    };
+    if did.as_str().starts_with("did:web:") {
+        report.warn(
+            PreflightCheck::DidMethod,
+            "did:web identities move by publishing a new did.json on their domain instead of a PLC operation",
+        );
+    } else if !did.as_str().starts_with("did:plc:") {
+        report.block(
+            PreflightCheck::DidMethod,
+            format!(
+                "Only did:plc and did:web identities can be migrated, not {}",
+                did.as_str()
+            ),
+        );
+    }
+
+    let origin_agent = build_agent(&options.retry).await?;
+    match login_helper(&origin_agent, &req.origin, &req.did, &req.origin_token).await {
+        Ok(_) => match repo_status(&origin_agent, &did).await {
+            Ok(status) if !status.active => report.warn(
+                PreflightCheck::OriginRepo,
+                format!(
+                    "The account is {} on the origin",
+                    status.status.as_deref().unwrap_or("inactive")
+                ),
+            ),
+            Ok(_) => {}
+            Err(error) => report.block(
+                PreflightCheck::OriginRepo,
+                format!("{} does not host {}: {}", req.origin, did.as_str(), error),
+            ),
+        },
+        Err(error) => report.block(
+            PreflightCheck::OriginSession,
+            format!("Could not use the origin token: {error}"),
+        ),
+    }
+
+    check_destination(&req, &did, options, &mut report).await?;
+    tracing::info!(
+        "Preflight found {} blockers and {} warnings",
+        report.blockers.len(),
+        report.warnings.len()
+    );
+    Ok(report)
+}
+
+async fn check_destination(
+    req: &PreflightRequest,
+    did: &Did,
+    options: &MigrationOptions,
+    report: &mut PreflightReport,
+) -> Result<(), MigrationError> {
+    let agent = build_agent(&options.retry).await?;
+    agent.configure_endpoint(req.destination.clone());
+    let server = match agent.api.com.atproto.server.describe_server().await {
+        Ok(server) => server,
+        Err(error) => {
+            report.block(
+                PreflightCheck::DestinationServer,
+                format!("{} is not answering as a PDS: {}", req.destination, error),
+            );
+            return Ok(());
+        }
+    };
+    report.invite_code_required = server.invite_code_required.unwrap_or(false);
+    report.available_user_domains = server.available_user_domains.clone();
+
+    let account_exists = match repo_status(&agent, did).await {
+        Ok(status) => {
+            report.warn(
+                PreflightCheck::DestinationRepo,
+                format!(
+                    "The destination already hosts this account ({}); log in instead of creating it",
+                    if status.active { "active" } else { "deactivated" }
+                ),
+            );
+            true
+        }
+        Err(MigrationError::NotFound { .. }) => false,
+        Err(error) => {
+            report.warn(
+                PreflightCheck::DestinationRepo,
+                format!("Could not check for an existing account: {error}"),
+            );
+            false
+        }
+    };
+
+    if let Some(token) = &req.destination_token {
+        let session_agent = build_agent(&options.retry).await?;
+        if let Err(error) = login_helper(&session_agent, &req.destination, &req.did, token).await {
+            report.block(
+                PreflightCheck::DestinationSession,
+                format!("Could not use the destination token: {error}"),
+            );
+        }
+    }
+
+    if account_exists {
+        return Ok(());
+    }
+    if report.invite_code_required
+        && req
+            .invite_code
+            .as_deref()
+            .is_none_or(|code| code.trim().is_empty())
+    {
+        report.block(
+            PreflightCheck::InviteCode,
+            "The destination requires an invite code",
+        );
+    }
+    if let Some(handle) = &req.handle {
+        check_handle(&agent, handle.trim(), did, report).await;
+    }
+    Ok(())
+}
+
+async fn check_handle(
+    agent: &MigrationAgent,
+    handle: &str,
+    did: &Did,
+    report: &mut PreflightReport,
+) {
+    use bsky_sdk::api::com::atproto::identity::resolve_handle::{Parameters, ParametersData};
+    let Ok(handle) = Handle::new(handle.to_lowercase()) else {
+        report.block(
+            PreflightCheck::Handle,
+            format!("{handle} is not a valid handle"),
+        );
+        return;
+    };
+    if !report
+        .available_user_domains
+        .iter()
+        .any(|domain| handle.as_str().ends_with(domain.as_str()))
+    {
+        report.warn(
+            PreflightCheck::Handle,
+            format!(
+                "{} is not under the destination's domains ({}); its DNS must already point at {}",
+                handle.as_str(),
+                report.available_user_domains.join(", "),
+                did.as_str()
+            ),
+        );
+    }
+    let resolved = agent
+        .api
+        .com
+        .atproto
+        .identity
+        .resolve_handle(Parameters {
+            data: ParametersData {
+                handle: handle.clone(),
+            },
+            extra_data: Ipld::Null,
+        })
+        .await;
+    // A handle that does not resolve is free to take
+    if let Ok(resolved) = resolved {
+        if &resolved.did != did {
+            report.block(
+                PreflightCheck::Handle,
+                format!(
+                    "{} already belongs to {}",
+                    handle.as_str(),
+                    resolved.did.as_str()
+                ),
+            );
+        }
+    }
+}
+
+async fn repo_status(
+    agent: &MigrationAgent,
+    did: &Did,
+) -> Result<bsky_sdk::api::com::atproto::sync::get_repo_status::OutputData, MigrationError> {
+    use bsky_sdk::api::com::atproto::sync::get_repo_status::{Parameters, ParametersData};
+    let host = agent.get_endpoint().await;
+    agent
+        .api
+        .com
+        .atproto
+        .sync
+        .get_repo_status(Parameters {
+            data: ParametersData { did: did.clone() },
+            extra_data: Ipld::Null,
+        })
+        .await
+        .map(|output| output.data)
+        .map_err(|error| MigrationError::from_atrium(&host, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RetryPolicy;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const DID: &str = "did:plc:example123";
+
+    fn options() -> MigrationOptions {
+        MigrationOptions {
+            retry: RetryPolicy::none(),
+            ..MigrationOptions::default()
+        }
+    }
+
+    async fn origin() -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.server.getSession"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(
+                    serde_json::json!({ "did": DID, "handle": "alice.example.com" }),
+                ),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.sync.getRepoStatus"))
+            .and(query_param("did", DID))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({ "did": DID, "active": true })),
+            )
+            .mount(&server)
+            .await;
+        server
+    }
+
+    async fn destination() -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.server.describeServer"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "did": "did:web:destination.example.com",
+                "availableUserDomains": [".destination.example.com"],
+                "inviteCodeRequired": true,
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.sync.getRepoStatus"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "error": "RepoNotFound",
+                "message": "Could not find repo for DID",
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.identity.resolveHandle"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({ "did": "did:plc:someoneelse" })),
+            )
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[test]
+    fn test_preflight_reports_every_problem_at_once() {
+        tokio_test::block_on(async {
+            let origin = origin().await;
+            let destination = destination().await;
+            let request = PreflightRequest {
+                origin: origin.uri(),
+                destination: destination.uri(),
+                did: DID.to_string(),
+                origin_token: "origin-token".to_string(),
+                destination_token: None,
+                handle: Some("alice.elsewhere.example".to_string()),
+                invite_code: None,
+            };
+            let report = preflight_api(request, &options()).await.unwrap();
+
+            assert!(!report.ready());
+            let blockers: Vec<_> = report.blockers.iter().map(|issue| issue.check).collect();
+            assert_eq!(
+                blockers,
+                vec![PreflightCheck::InviteCode, PreflightCheck::Handle]
+            );
+            let warnings: Vec<_> = report.warnings.iter().map(|issue| issue.check).collect();
+            assert_eq!(warnings, vec![PreflightCheck::Handle]);
+            assert!(report.invite_code_required);
+        });
+    }
+
+    #[test]
+    fn test_preflight_request_redacts_secrets() {
+        let request = PreflightRequest {
+            origin: "https://origin.example.com".to_string(),
+            destination: "https://destination.example.com".to_string(),
+            did: DID.to_string(),
+            origin_token: "secret-origin-token".to_string(),
+            destination_token: Some("secret-destination-token".to_string()),
+            handle: Some("alice.destination.example.com".to_string()),
+            invite_code: Some("secret-invite".to_string()),
+        };
+        let debug_output = format!("{:?}", request);
+        assert!(!debug_output.contains("secret-"));
+        assert!(debug_output.contains("alice.destination.example.com"));
+    }
+}
@@ -0,0 +1,145 @@
+use crate::BlobOutcome;
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use std::sync::Arc;
+
+/// Something that happened while a long-running operation worked through its items.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressEvent {
+    /// How many items the operation is going to work through.
+    Total {
+        items: u64,
+    },
+    ItemSucceeded {
+        id: String,
+    },
+    /// Already staged (or already on the destination), nothing to do.
+    ItemSkipped {
+        id: String,
+    },
+    /// The bytes did not match the item's CID.
+    ItemCorrupt {
+        id: String,
+    },
+    ItemFailed {
+        id: String,
+        message: String,
+    },
+    /// Stopped before it finished because the operation was canceled.
+    ItemCanceled {
+        id: String,
+    },
+    /// More bytes were downloaded or uploaded. Sent per chunk, so sinks should add them up.
+    BytesTransferred {
+        bytes: u64,
+    },
+}
+
+impl ProgressEvent {
+    pub fn for_outcome(id: String, outcome: &BlobOutcome) -> Self {
+        match outcome {
+            BlobOutcome::Transferred => ProgressEvent::ItemSucceeded { id },
+            BlobOutcome::Skipped => ProgressEvent::ItemSkipped { id },
+            BlobOutcome::Corrupt => ProgressEvent::ItemCorrupt { id },
+            BlobOutcome::Canceled => ProgressEvent::ItemCanceled { id },
+            BlobOutcome::Failed(error) => ProgressEvent::ItemFailed {
+                id,
+                message: error.to_string(),
+            },
+        }
+    }
+}
+
+/// Receives [`ProgressEvent`]s from the operations it is passed to through
+/// [`MigrationOptions::progress`](crate::MigrationOptions::progress).
+///
+/// Events can arrive from several blobs in flight at once, so implementations should be
+/// cheap and must not block.
+pub trait ProgressSink: Send + Sync {
+    fn report(&self, event: ProgressEvent);
+}
+
+impl<F> ProgressSink for F
+where
+    F: Fn(ProgressEvent) + Send + Sync,
+{
+    fn report(&self, event: ProgressEvent) {
+        self(event)
+    }
+}
+
+/// Reports every chunk of `stream` to `sink` as [`ProgressEvent::BytesTransferred`].
+pub fn count_bytes<S, E>(
+    sink: Option<Arc<dyn ProgressSink>>,
+    stream: S,
+) -> impl Stream<Item = Result<Bytes, E>>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+{
+    stream.inspect(move |chunk| {
+        if let (Some(sink), Ok(bytes)) = (&sink, chunk) {
+            sink.report(ProgressEvent::BytesTransferred {
+                bytes: bytes.len() as u64,
+            });
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run_all_blob_transfers, BlobCheck, MigrationError, MigrationOptions};
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_blob_transfers_report_totals_and_outcomes() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let options = MigrationOptions {
+            blob_concurrency: 1,
+            progress: Some(Arc::new(move |event| sink.lock().unwrap().push(event))),
+            ..MigrationOptions::default()
+        };
+        let cids = vec!["cid0".to_string(), "cid1".to_string()];
+        tokio_test::block_on(run_all_blob_transfers(cids, &options, |cid| async move {
+            match cid.as_str() {
+                "cid0" => Ok(Some(BlobCheck::Verified)),
+                _ => Err(MigrationError::Upstream {
+                    message: "gone".to_string(),
+                }),
+            }
+        }))
+        .unwrap();
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                ProgressEvent::Total { items: 2 },
+                ProgressEvent::ItemSucceeded {
+                    id: "cid0".to_string()
+                },
+                ProgressEvent::ItemFailed {
+                    id: "cid1".to_string(),
+                    message: "Upstream error: gone".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_count_bytes_reports_each_chunk() {
+        let total = Arc::new(Mutex::new(0));
+        let sink = total.clone();
+        let progress: Arc<dyn ProgressSink> = Arc::new(move |event| {
+            if let ProgressEvent::BytesTransferred { bytes } = event {
+                *sink.lock().unwrap() += bytes;
+            }
+        });
+        let chunks = futures_util::stream::iter(vec![
+            Ok::<_, MigrationError>(Bytes::from_static(b"abc")),
+            Ok(Bytes::from_static(b"de")),
+        ]);
+        let collected: Vec<_> = tokio_test::block_on(count_bytes(Some(progress), chunks).collect());
+        assert_eq!(collected.len(), 2);
+        assert_eq!(*total.lock().unwrap(), 5);
+    }
+}
@@ -0,0 +1,482 @@
+use crate::{DpopKey, RequestFailure, RetryPolicy};
+use atrium_xrpc::http::{HeaderMap, Method, Request, Response, StatusCode, Uri};
+use atrium_xrpc::{HttpClient, XrpcClient};
+use atrium_xrpc_client::reqwest::ReqwestClient;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Attempts per request, including the first, when a PDS answers 429.
+const MAX_ATTEMPTS: usize = 3;
+/// A 429 whose window resets later than this is returned to the caller instead of waited out.
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(15 * 60);
+/// Used for a 429 that carries neither `Retry-After` nor `ratelimit-reset`.
+const DEFAULT_BACKOFF: Duration = Duration::from_secs(60);
+/// `ratelimit-reset` values above this are Unix timestamps, below it a number of seconds.
+const EPOCH_THRESHOLD: u64 = 1_000_000_000;
+
+static GOVERNOR: LazyLock<RateLimitGovernor> = LazyLock::new(RateLimitGovernor::default);
+
+/// The process-wide governor shared by every agent and raw XRPC request.
+pub fn rate_limit_governor() -> &'static RateLimitGovernor {
+    &GOVERNOR
+}
+
+#[derive(Debug, Default, Clone)]
+struct HostBudget {
+    limit: Option<u64>,
+    remaining: Option<u64>,
+    reset: Option<SystemTime>,
+    blocked_until: Option<SystemTime>,
+}
+
+/// Tracks each host's advertised rate-limit budget and paces requests so the budget
+/// is spread over the window rather than exhausted.
+#[derive(Debug, Default)]
+pub struct RateLimitGovernor {
+    hosts: Mutex<HashMap<String, HostBudget>>,
+}
+
+impl RateLimitGovernor {
+    /// Waits until a request to `host` fits the host's budget, then reserves it.
+    pub async fn wait(&self, host: &str) {
+        let delay = self.reserve(host, SystemTime::now());
+        if !delay.is_zero() {
+            tracing::info!("Pacing requests to {} for {:?}", host, delay);
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// How long a request to `host` at `now` should wait. Counts the request against the
+    /// remaining budget so concurrent callers do not all spend the same slot.
+    fn reserve(&self, host: &str, now: SystemTime) -> Duration {
+        let mut hosts = self.hosts.lock().unwrap();
+        let Some(budget) = hosts.get_mut(host) else {
+            return Duration::ZERO;
+        };
+        if let Some(blocked_until) = budget.blocked_until {
+            if blocked_until > now {
+                return until(blocked_until, now);
+            }
+            budget.blocked_until = None;
+        }
+        let (Some(remaining), Some(reset)) = (budget.remaining, budget.reset) else {
+            return Duration::ZERO;
+        };
+        if reset <= now {
+            budget.remaining = budget.limit;
+            budget.reset = None;
+            return Duration::ZERO;
+        }
+        let reserve = budget.limit.map(|limit| (limit / 10).max(1)).unwrap_or(1);
+        budget.remaining = Some(remaining.saturating_sub(1));
+        if remaining == 0 {
+            until(reset, now)
+        } else if remaining <= reserve {
+            // Spread what is left evenly over the rest of the window
+            until(reset, now) / (remaining as u32 + 1)
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    /// Records the rate-limit headers of a response from `host`. Returns true when the
+    /// request was rejected for rate limiting and is worth sending again.
+    pub fn observe(&self, host: &str, status: StatusCode, headers: &HeaderMap) -> bool {
+        self.observe_at(host, status, headers, SystemTime::now())
+    }
+
+    fn observe_at(
+        &self,
+        host: &str,
+        status: StatusCode,
+        headers: &HeaderMap,
+        now: SystemTime,
+    ) -> bool {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse::<u64>().ok())
+        };
+        let mut hosts = self.hosts.lock().unwrap();
+        let budget = hosts.entry(host.to_string()).or_default();
+        if let Some(limit) = header("ratelimit-limit") {
+            budget.limit = Some(limit);
+        }
+        if let Some(remaining) = header("ratelimit-remaining") {
+            budget.remaining = Some(remaining);
+        }
+        if let Some(reset) = header("ratelimit-reset") {
+            budget.reset = Some(if reset > EPOCH_THRESHOLD {
+                UNIX_EPOCH + Duration::from_secs(reset)
+            } else {
+                now + Duration::from_secs(reset)
+            });
+        }
+        let retry_after = header("retry-after").map(|seconds| now + Duration::from_secs(seconds));
+        if retry_after.is_some() {
+            budget.blocked_until = retry_after;
+        }
+
+        if status != StatusCode::TOO_MANY_REQUESTS {
+            return false;
+        }
+        let blocked_until = retry_after
+            .or(budget.reset.filter(|reset| *reset > now))
+            .unwrap_or(now + DEFAULT_BACKOFF);
+        budget.blocked_until = Some(blocked_until);
+        budget.remaining = Some(0);
+        let wait = until(blocked_until, now);
+        tracing::error!(
+            "Rate limit reached on {}, window resets in {:?}",
+            host,
+            wait
+        );
+        wait <= MAX_RETRY_WAIT
+    }
+}
+
+fn until(time: SystemTime, now: SystemTime) -> Duration {
+    time.duration_since(now).unwrap_or_default()
+}
+
+/// The key requests are grouped under: host and port.
+pub fn rate_limit_host(uri: &Uri) -> String {
+    match (uri.host(), uri.port_u16()) {
+        (Some(host), Some(port)) => format!("{host}:{port}"),
+        (Some(host), None) => host.to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Sends a raw reqwest request through the governor, retrying it after the window
+/// resets if it was rate limited, or as `retry` allows if it failed, as long as its
+/// body can be replayed.
+pub async fn send_governed(
+    client: &reqwest::Client,
+    request: reqwest::Request,
+    retry: &RetryPolicy,
+) -> Result<reqwest::Response, reqwest::Error> {
+    send_governed_with(client, request, retry, reqwest::Request::try_clone).await
+}
+
+/// Like [`send_governed`], but every attempt after the first is the request `replay` builds
+/// from the one about to be sent, for requests that must not go out twice byte for byte, such
+/// as ones carrying a DPoP proof. When `replay` returns `None` the first answer is final.
+pub async fn send_governed_with(
+    client: &reqwest::Client,
+    mut request: reqwest::Request,
+    retry: &RetryPolicy,
+    mut replay: impl FnMut(&reqwest::Request) -> Option<reqwest::Request>,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let host = match request.url().host_str() {
+        Some(host) => match request.url().port() {
+            Some(port) => format!("{host}:{port}"),
+            None => host.to_string(),
+        },
+        None => String::new(),
+    };
+    let idempotent = is_idempotent(request.method());
+    let mut rate_limited = 1;
+    let mut attempt = 1;
+    loop {
+        rate_limit_governor().wait(&host).await;
+        let next = replay(&request);
+        let result = client.execute(request).await;
+        let (again, failure) = match &result {
+            Ok(response) => {
+                if rate_limit_governor().observe(&host, response.status(), response.headers())
+                    && rate_limited < MAX_ATTEMPTS
+                {
+                    rate_limited += 1;
+                    (true, None)
+                } else {
+                    let failure = RequestFailure::from_status(response.status().as_u16());
+                    let again = failure
+                        .is_some_and(|failure| retry.should_retry(idempotent, failure, attempt));
+                    (again, failure)
+                }
+            }
+            Err(error) => {
+                let failure = RequestFailure::from_reqwest(error);
+                (
+                    retry.should_retry(idempotent, failure, attempt),
+                    Some(failure),
+                )
+            }
+        };
+        match next {
+            Some(next) if again => request = next,
+            _ => return result,
+        }
+        if let Some(failure) = failure {
+            retry.pause(attempt, failure).await;
+            attempt += 1;
+        }
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    method == Method::GET || method == Method::HEAD
+}
+
+/// XRPC client used by every agent: reqwest, paced by the governor and retried
+/// according to a [`RetryPolicy`].
+pub struct GovernedClient {
+    inner: ReqwestClient,
+    retry: RetryPolicy,
+    dpop: Option<Arc<DpopKey>>,
+}
+
+impl GovernedClient {
+    pub fn new(base_uri: impl AsRef<str>, retry: RetryPolicy) -> Self {
+        Self {
+            inner: ReqwestClient::new(base_uri),
+            retry,
+            dpop: None,
+        }
+    }
+
+    /// Sends the agent's tokens as DPoP-bound OAuth tokens, with a proof signed by `key`.
+    pub fn with_dpop(mut self, key: Arc<DpopKey>) -> Self {
+        self.dpop = Some(key);
+        self
+    }
+}
+
+impl HttpClient for GovernedClient {
+    async fn send_http(
+        &self,
+        request: Request<Vec<u8>>,
+    ) -> Result<Response<Vec<u8>>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let host = rate_limit_host(request.uri());
+        let idempotent = is_idempotent(request.method());
+        let (parts, body) = request.into_parts();
+        let url = parts.uri.to_string();
+        let mut rate_limited = 1;
+        let mut nonce_renewed = false;
+        let mut attempt = 1;
+        loop {
+            rate_limit_governor().wait(&host).await;
+            let mut request = Request::new(body.clone());
+            *request.method_mut() = parts.method.clone();
+            *request.uri_mut() = parts.uri.clone();
+            *request.version_mut() = parts.version;
+            *request.headers_mut() = parts.headers.clone();
+            if let Some(dpop) = &self.dpop {
+                dpop.authorize(&mut request);
+            }
+            let failure = match self.inner.send_http(request).await {
+                Ok(response) => {
+                    if rate_limit_governor().observe(&host, response.status(), response.headers())
+                        && rate_limited < MAX_ATTEMPTS
+                    {
+                        rate_limited += 1;
+                        continue;
+                    }
+                    if let Some(dpop) = &self.dpop {
+                        let renew =
+                            dpop.observe(&url, response.status().as_u16(), response.headers());
+                        if renew && !nonce_renewed {
+                            nonce_renewed = true;
+                            continue;
+                        }
+                    }
+                    match RequestFailure::from_status(response.status().as_u16()) {
+                        Some(failure) if self.retry.should_retry(idempotent, failure, attempt) => {
+                            failure
+                        }
+                        _ => return Ok(response),
+                    }
+                }
+                Err(error) => {
+                    let failure = error
+                        .downcast_ref::<reqwest::Error>()
+                        .map(RequestFailure::from_reqwest)
+                        .unwrap_or(RequestFailure::Network);
+                    if !self.retry.should_retry(idempotent, failure, attempt) {
+                        return Err(error);
+                    }
+                    failure
+                }
+            };
+            self.retry.pause(attempt, failure).await;
+            attempt += 1;
+        }
+    }
+}
+
+impl XrpcClient for GovernedClient {
+    fn base_uri(&self) -> String {
+        self.inner.base_uri()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use atrium_xrpc::http::HeaderValue;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn headers(values: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in values {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_unknown_host_is_not_paced() {
+        let governor = RateLimitGovernor::default();
+        assert_eq!(
+            governor.reserve("pds.example.com", UNIX_EPOCH),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_low_budget_is_spread_over_window() {
+        let governor = RateLimitGovernor::default();
+        let now = UNIX_EPOCH + Duration::from_secs(2_000_000_000);
+        let reset = (2_000_000_000u64 + 100).to_string();
+        governor.observe_at(
+            "pds.example.com",
+            StatusCode::OK,
+            &headers(&[
+                ("ratelimit-limit", "100"),
+                ("ratelimit-remaining", "4"),
+                ("ratelimit-reset", &reset),
+            ]),
+            now,
+        );
+        assert_eq!(
+            governor.reserve("pds.example.com", now),
+            Duration::from_secs(20)
+        );
+        assert_eq!(
+            governor.reserve("pds.example.com", now),
+            Duration::from_secs(25)
+        );
+    }
+
+    #[test]
+    fn test_healthy_budget_is_not_paced() {
+        let governor = RateLimitGovernor::default();
+        let now = UNIX_EPOCH + Duration::from_secs(2_000_000_000);
+        governor.observe_at(
+            "pds.example.com",
+            StatusCode::OK,
+            &headers(&[
+                ("ratelimit-limit", "100"),
+                ("ratelimit-remaining", "90"),
+                ("ratelimit-reset", "60"),
+            ]),
+            now,
+        );
+        assert_eq!(governor.reserve("pds.example.com", now), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_too_many_requests_honours_retry_after() {
+        let governor = RateLimitGovernor::default();
+        let now = UNIX_EPOCH + Duration::from_secs(2_000_000_000);
+        let retry = governor.observe_at(
+            "pds.example.com",
+            StatusCode::TOO_MANY_REQUESTS,
+            &headers(&[("retry-after", "30")]),
+            now,
+        );
+        assert!(retry);
+        assert_eq!(
+            governor.reserve("pds.example.com", now),
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            governor.reserve("pds.example.com", now + Duration::from_secs(30)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_long_window_is_not_retried() {
+        let governor = RateLimitGovernor::default();
+        let now = UNIX_EPOCH + Duration::from_secs(2_000_000_000);
+        let retry = governor.observe_at(
+            "pds.example.com",
+            StatusCode::TOO_MANY_REQUESTS,
+            &headers(&[("ratelimit-remaining", "0"), ("ratelimit-reset", "86400")]),
+            now,
+        );
+        assert!(!retry);
+    }
+
+    #[test]
+    fn test_hosts_are_keyed_with_port() {
+        let uri: Uri = "http://localhost:2583/xrpc/com.atproto.server.describeServer"
+            .parse()
+            .unwrap();
+        assert_eq!(rate_limit_host(&uri), "localhost:2583");
+    }
+
+    #[test]
+    fn test_rate_limited_request_is_retried() {
+        tokio_test::block_on(async {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/xrpc/com.atproto.sync.getRepo"))
+                .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "0"))
+                .up_to_n_times(1)
+                .expect(1)
+                .mount(&server)
+                .await;
+            Mock::given(method("GET"))
+                .and(path("/xrpc/com.atproto.sync.getRepo"))
+                .respond_with(ResponseTemplate::new(200))
+                .expect(1)
+                .mount(&server)
+                .await;
+            let client = reqwest::Client::new();
+            let request = client
+                .get(format!("{}/xrpc/com.atproto.sync.getRepo", server.uri()))
+                .build()
+                .unwrap();
+            let response = send_governed(&client, request, &RetryPolicy::none())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), reqwest::StatusCode::OK);
+        });
+    }
+
+    #[test]
+    fn test_server_error_is_retried_for_reads_only() {
+        tokio_test::block_on(async {
+            let server = MockServer::start().await;
+            Mock::given(path("/xrpc/com.atproto.sync.getRepo"))
+                .respond_with(ResponseTemplate::new(503))
+                .up_to_n_times(1)
+                .mount(&server)
+                .await;
+            Mock::given(path("/xrpc/com.atproto.sync.getRepo"))
+                .respond_with(ResponseTemplate::new(200))
+                .mount(&server)
+                .await;
+            let retry = RetryPolicy {
+                initial_backoff: Duration::from_millis(1),
+                ..RetryPolicy::default()
+            };
+            let client = reqwest::Client::new();
+            let url = format!("{}/xrpc/com.atproto.sync.getRepo", server.uri());
+
+            let post = client.post(&url).build().unwrap();
+            let response = send_governed(&client, post, &retry).await.unwrap();
+            assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+
+            let get = client.get(&url).build().unwrap();
+            let response = send_governed(&client, get, &retry).await.unwrap();
+            assert_eq!(response.status(), reqwest::StatusCode::OK);
+        });
+    }
+}
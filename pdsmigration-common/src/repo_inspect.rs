@@ -0,0 +1,296 @@
+use crate::{CarError, CarFile};
+use ipld_core::cid::Cid;
+use ipld_core::ipld::Ipld;
+use std::collections::{BTreeMap, HashSet};
+
+/// The signed commit at the root of an exported repo.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepoCommit {
+    pub did: String,
+    pub version: i128,
+    pub rev: String,
+    pub data: Cid,
+    pub prev: Option<Cid>,
+}
+
+impl RepoCommit {
+    /// Decodes the commit at the root of `car`. Unlike [`RepoInspection::from_car`] this does not
+    /// need the MST, so it also works on the partial CARs returned by `getRepo?since=`.
+    pub fn from_car(car: &CarFile) -> Result<Self, CarError> {
+        let root = car.root().ok_or_else(|| CarError::InvalidCommit {
+            message: "CAR file has no root".to_string(),
+        })?;
+        decode_commit(&decode_block(car, root)?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepoRecord {
+    pub collection: String,
+    pub rkey: String,
+    pub cid: Cid,
+    pub value: Ipld,
+}
+
+/// Every record reachable from a repo's commit, in MST key order.
+#[derive(Debug, Clone)]
+pub struct RepoInspection {
+    pub commit: RepoCommit,
+    pub records: Vec<RepoRecord>,
+}
+
+impl RepoInspection {
+    /// Parses and verifies `bytes` as a CAR file, then walks its MST.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CarError> {
+        Self::from_car(&CarFile::parse(bytes)?)
+    }
+
+    pub fn from_car(car: &CarFile) -> Result<Self, CarError> {
+        let commit = RepoCommit::from_car(car)?;
+
+        let mut records = Vec::new();
+        let mut visited = HashSet::new();
+        walk_node(car, &commit.data, &mut visited, &mut records)?;
+        Ok(Self { commit, records })
+    }
+
+    /// Number of records in each collection, keyed by NSID.
+    pub fn collection_counts(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for record in &self.records {
+            *counts.entry(record.collection.clone()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    pub fn records_in<'a>(&'a self, collection: &'a str) -> impl Iterator<Item = &'a RepoRecord> {
+        self.records
+            .iter()
+            .filter(move |record| record.collection == collection)
+    }
+}
+
+fn decode_block(car: &CarFile, cid: &Cid) -> Result<Ipld, CarError> {
+    let data = car.get(cid).ok_or_else(|| CarError::MissingBlock {
+        cid: cid.to_string(),
+    })?;
+    serde_ipld_dagcbor::from_slice(data).map_err(|error| CarError::InvalidNode {
+        cid: cid.to_string(),
+        message: error.to_string(),
+    })
+}
+
+fn decode_commit(commit: &Ipld) -> Result<RepoCommit, CarError> {
+    let invalid = |field: &str| CarError::InvalidCommit {
+        message: format!("missing or malformed field `{field}`"),
+    };
+    let Ipld::Map(fields) = commit else {
+        return Err(invalid("commit"));
+    };
+    let string = |field: &str| match fields.get(field) {
+        Some(Ipld::String(value)) => Ok(value.clone()),
+        _ => Err(invalid(field)),
+    };
+    Ok(RepoCommit {
+        did: string("did")?,
+        rev: string("rev")?,
+        version: match fields.get("version") {
+            Some(Ipld::Integer(version)) => *version,
+            _ => return Err(invalid("version")),
+        },
+        data: match fields.get("data") {
+            Some(Ipld::Link(cid)) => *cid,
+            _ => return Err(invalid("data")),
+        },
+        prev: match fields.get("prev") {
+            Some(Ipld::Link(cid)) => Some(*cid),
+            None | Some(Ipld::Null) => None,
+            _ => return Err(invalid("prev")),
+        },
+    })
+}
+
+/// Walks an MST node in key order: the left subtree, then each entry followed by
+/// its right subtree. Entry keys are prefix-compressed against the previous key.
+fn walk_node(
+    car: &CarFile,
+    cid: &Cid,
+    visited: &mut HashSet<Cid>,
+    records: &mut Vec<RepoRecord>,
+) -> Result<(), CarError> {
+    let invalid = |message: &str| CarError::InvalidNode {
+        cid: cid.to_string(),
+        message: message.to_string(),
+    };
+    if !visited.insert(*cid) {
+        return Err(invalid("node is reachable more than once"));
+    }
+    let Ipld::Map(node) = decode_block(car, cid)? else {
+        return Err(invalid("node is not a map"));
+    };
+    if let Some(Ipld::Link(left)) = node.get("l") {
+        walk_node(car, left, visited, records)?;
+    }
+    let Some(Ipld::List(entries)) = node.get("e") else {
+        return Err(invalid("missing entries"));
+    };
+
+    let mut key: Vec<u8> = Vec::new();
+    for entry in entries {
+        let Ipld::Map(entry) = entry else {
+            return Err(invalid("entry is not a map"));
+        };
+        let (Some(Ipld::Integer(prefix)), Some(Ipld::Bytes(suffix)), Some(Ipld::Link(value))) =
+            (entry.get("p"), entry.get("k"), entry.get("v"))
+        else {
+            return Err(invalid("entry is missing p, k or v"));
+        };
+        let prefix = usize::try_from(*prefix)
+            .ok()
+            .filter(|prefix| *prefix <= key.len())
+            .ok_or_else(|| invalid("entry prefix is longer than the previous key"))?;
+        key.truncate(prefix);
+        key.extend_from_slice(suffix);
+
+        let path = String::from_utf8(key.clone()).map_err(|_| invalid("key is not UTF-8"))?;
+        let (collection, rkey) = path
+            .split_once('/')
+            .ok_or_else(|| invalid("key is not collection/rkey"))?;
+        records.push(RepoRecord {
+            collection: collection.to_string(),
+            rkey: rkey.to_string(),
+            cid: *value,
+            value: decode_block(car, value)?,
+        });
+
+        if let Some(Ipld::Link(right)) = entry.get("t") {
+            walk_node(car, right, visited, records)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::car::tests::{cid_for, write_car};
+
+    fn block(value: &Ipld) -> (Cid, Vec<u8>) {
+        let data = serde_ipld_dagcbor::to_vec(value).unwrap();
+        (cid_for(&data), data)
+    }
+
+    fn map(fields: Vec<(&str, Ipld)>) -> Ipld {
+        Ipld::Map(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    fn entry(prefix: i128, suffix: &str, value: Cid, right: Option<Cid>) -> Ipld {
+        map(vec![
+            ("p", Ipld::Integer(prefix)),
+            ("k", Ipld::Bytes(suffix.as_bytes().to_vec())),
+            ("v", Ipld::Link(value)),
+            ("t", right.map(Ipld::Link).unwrap_or(Ipld::Null)),
+        ])
+    }
+
+    fn sample_repo() -> Vec<u8> {
+        let profile = block(&map(vec![("displayName", Ipld::String("Alice".into()))]));
+        let post1 = block(&map(vec![("text", Ipld::String("first".into()))]));
+        let post2 = block(&map(vec![("text", Ipld::String("second".into()))]));
+
+        let left = block(&map(vec![
+            ("l", Ipld::Null),
+            (
+                "e",
+                Ipld::List(vec![entry(
+                    0,
+                    "app.bsky.actor.profile/self",
+                    profile.0,
+                    None,
+                )]),
+            ),
+        ]));
+        let root = block(&map(vec![
+            ("l", Ipld::Link(left.0)),
+            (
+                "e",
+                Ipld::List(vec![
+                    entry(0, "app.bsky.feed.post/3k1", post1.0, None),
+                    entry(21, "2", post2.0, None),
+                ]),
+            ),
+        ]));
+        let commit = block(&map(vec![
+            ("did", Ipld::String("did:plc:alice".into())),
+            ("version", Ipld::Integer(3)),
+            ("rev", Ipld::String("3kabc".into())),
+            ("data", Ipld::Link(root.0)),
+            ("prev", Ipld::Null),
+            ("sig", Ipld::Bytes(vec![0; 64])),
+        ]));
+        write_car(vec![commit.0], &[commit, root, left, profile, post1, post2])
+    }
+
+    #[test]
+    fn test_inspect_lists_records_in_key_order() {
+        let inspection = RepoInspection::from_bytes(&sample_repo()).unwrap();
+        assert_eq!(inspection.commit.did, "did:plc:alice");
+        assert_eq!(inspection.commit.rev, "3kabc");
+        let keys: Vec<_> = inspection
+            .records
+            .iter()
+            .map(|record| format!("{}/{}", record.collection, record.rkey))
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                "app.bsky.actor.profile/self",
+                "app.bsky.feed.post/3k1",
+                "app.bsky.feed.post/3k2",
+            ]
+        );
+        assert_eq!(
+            inspection.records[2].value,
+            map(vec![("text", Ipld::String("second".into()))])
+        );
+    }
+
+    #[test]
+    fn test_collection_counts() {
+        let inspection = RepoInspection::from_bytes(&sample_repo()).unwrap();
+        let counts = inspection.collection_counts();
+        assert_eq!(counts.get("app.bsky.feed.post"), Some(&2));
+        assert_eq!(counts.get("app.bsky.actor.profile"), Some(&1));
+        assert_eq!(inspection.records_in("app.bsky.feed.post").count(), 2);
+    }
+
+    #[test]
+    fn test_missing_record_block_is_reported() {
+        let post = block(&map(vec![("text", Ipld::String("gone".into()))]));
+        let root = block(&map(vec![
+            ("l", Ipld::Null),
+            (
+                "e",
+                Ipld::List(vec![entry(0, "app.bsky.feed.post/1", post.0, None)]),
+            ),
+        ]));
+        let commit = block(&map(vec![
+            ("did", Ipld::String("did:plc:alice".into())),
+            ("version", Ipld::Integer(3)),
+            ("rev", Ipld::String("3kabc".into())),
+            ("data", Ipld::Link(root.0)),
+        ]));
+        let car = write_car(vec![commit.0], &[commit, root]);
+        assert_eq!(
+            RepoInspection::from_bytes(&car).unwrap_err(),
+            CarError::MissingBlock {
+                cid: post.0.to_string()
+            }
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use crate::agent::{login_helper, request_token};
-use crate::{build_agent, MigrationError};
+use crate::{build_agent, MigrationError, MigrationOptions};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -9,9 +9,12 @@ pub struct RequestTokenRequest {
     pub token: String,
 }
 
-#[tracing::instrument(skip(req))]
-pub async fn request_token_api(req: RequestTokenRequest) -> Result<(), MigrationError> {
-    let agent = build_agent().await?;
+#[tracing::instrument(skip(req, options))]
+pub async fn request_token_api(
+    req: RequestTokenRequest,
+    options: &MigrationOptions,
+) -> Result<(), MigrationError> {
+    let agent = build_agent(&options.retry).await?;
     login_helper(
         &agent,
         req.pds_host.as_str(),
@@ -0,0 +1,340 @@
+use crate::{parse_did, send_governed, MigrationError, RetryPolicy};
+use bsky_sdk::api::did_doc::DidDocument;
+use bsky_sdk::api::types::string::{Did, Handle};
+use serde::Deserialize;
+
+pub const DEFAULT_PLC_DIRECTORY: &str = "https://plc.directory";
+pub const DEFAULT_DNS_OVER_HTTPS: &str = "https://cloudflare-dns.com/dns-query";
+
+/// Resolves handles and DIDs the way the AT Protocol specifies, so callers can start from
+/// "alice.example.com" instead of a DID and a PDS host.
+#[derive(Debug, Clone)]
+pub struct IdentityResolver {
+    /// Directory did:plc documents are read from.
+    pub plc_directory: String,
+    /// DNS-over-HTTPS endpoint answering JSON (`application/dns-json`) queries, used for the
+    /// `_atproto` TXT record of a handle.
+    pub dns_over_https: String,
+    /// Serves every `/.well-known/` lookup from this base instead of `https://{domain}`, for
+    /// pointing the resolver at a local stand-in.
+    pub web_base: Option<String>,
+    pub retry: RetryPolicy,
+}
+
+impl Default for IdentityResolver {
+    fn default() -> Self {
+        Self {
+            plc_directory: DEFAULT_PLC_DIRECTORY.to_string(),
+            dns_over_https: DEFAULT_DNS_OVER_HTTPS.to_string(),
+            web_base: None,
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+/// Where an account lives, as its DID document states it.
+#[derive(Debug, Clone)]
+pub struct ResolvedIdentity {
+    pub did: Did,
+    /// The handle the DID document claims. When resolving from a handle, it is that handle.
+    pub handle: Option<String>,
+    pub pds_host: String,
+    pub did_doc: DidDocument,
+}
+
+#[derive(Deserialize)]
+struct DnsResponse {
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DnsAnswer>,
+}
+
+#[derive(Deserialize)]
+struct DnsAnswer {
+    data: String,
+}
+
+impl IdentityResolver {
+    /// Resolves a handle (optionally prefixed with `@`) or a DID to its DID document and PDS.
+    #[tracing::instrument(skip(self))]
+    pub async fn resolve(&self, identifier: &str) -> Result<ResolvedIdentity, MigrationError> {
+        let identifier = identifier.trim().trim_start_matches('@');
+        let (did, handle) = if identifier.starts_with("did:") {
+            (parse_did(identifier)?, None)
+        } else {
+            let handle = parse_handle(identifier)?;
+            (self.resolve_handle(&handle).await?, Some(handle))
+        };
+        let did_doc = self.resolve_did(&did).await?;
+        let claimed = did_doc
+            .also_known_as
+            .iter()
+            .flatten()
+            .find_map(|aka| aka.strip_prefix("at://"))
+            .map(str::to_lowercase);
+        // A handle only counts when the DID document claims it back
+        if let Some(handle) = &handle {
+            if claimed.as_deref() != Some(handle.as_str()) {
+                tracing::error!(
+                    "{} resolves to {}, which claims {:?}",
+                    handle.as_str(),
+                    did.as_str(),
+                    claimed
+                );
+                return Err(MigrationError::Validation {
+                    field: "handle".to_string(),
+                });
+            }
+        }
+        let pds_host = did_doc
+            .get_pds_endpoint()
+            .ok_or_else(|| MigrationError::Upstream {
+                message: format!("The DID document of {} names no PDS", did.as_str()),
+            })?;
+        Ok(ResolvedIdentity {
+            did,
+            handle: claimed,
+            pds_host: pds_host.trim_end_matches('/').to_string(),
+            did_doc,
+        })
+    }
+
+    /// Finds the DID of a handle through its `_atproto` TXT record, then
+    /// `/.well-known/atproto-did`.
+    #[tracing::instrument(skip(self))]
+    pub async fn resolve_handle(&self, handle: &Handle) -> Result<Did, MigrationError> {
+        match self.resolve_handle_dns(handle).await {
+            Ok(Some(did)) => return Ok(did),
+            Ok(None) => {}
+            Err(error) => tracing::warn!("DNS lookup for {} failed: {}", handle.as_str(), error),
+        }
+        let url = format!(
+            "{}/.well-known/atproto-did",
+            self.web_origin(handle.as_str())
+        );
+        let response = self.get(&url, None).await?;
+        let body = response.text().await.map_err(upstream)?;
+        parse_did(body.trim())
+    }
+
+    async fn resolve_handle_dns(&self, handle: &Handle) -> Result<Option<Did>, MigrationError> {
+        let url = reqwest::Url::parse_with_params(
+            &self.dns_over_https,
+            &[
+                ("name", format!("_atproto.{}", handle.as_str())),
+                ("type", "TXT".to_string()),
+            ],
+        )
+        .map_err(|_error| MigrationError::Validation {
+            field: "dns_over_https".to_string(),
+        })?;
+        let response: DnsResponse = self
+            .get(url.as_str(), Some("application/dns-json"))
+            .await?
+            .json()
+            .await
+            .map_err(upstream)?;
+        let dids: Vec<&str> = response
+            .answer
+            .iter()
+            .filter_map(|answer| answer.data.trim_matches('"').strip_prefix("did="))
+            .collect();
+        // More than one DID makes the record ambiguous, which the spec treats as none
+        match dids.as_slice() {
+            [did] => parse_did(did).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Fetches the DID document of a did:plc from the directory, or of a did:web from its
+    /// domain.
+    #[tracing::instrument(skip(self))]
+    pub async fn resolve_did(&self, did: &Did) -> Result<DidDocument, MigrationError> {
+        let url = if did.as_str().starts_with("did:plc:") {
+            format!(
+                "{}/{}",
+                self.plc_directory.trim_end_matches('/'),
+                did.as_str()
+            )
+        } else if let Some(id) = did.as_str().strip_prefix("did:web:") {
+            // Ports are percent-encoded; further colons separate path segments
+            let mut segments = id.split(':');
+            let domain = segments.next().unwrap_or_default().replace("%3A", ":");
+            let path: Vec<&str> = segments.collect();
+            if path.is_empty() {
+                format!("{}/.well-known/did.json", self.web_origin(&domain))
+            } else {
+                format!("{}/{}/did.json", self.web_origin(&domain), path.join("/"))
+            }
+        } else {
+            return Err(MigrationError::Validation {
+                field: "did".to_string(),
+            });
+        };
+        let did_doc: DidDocument = self.get(&url, None).await?.json().await.map_err(upstream)?;
+        if did_doc.id != did.as_str() {
+            return Err(MigrationError::Upstream {
+                message: format!("{url} holds the DID document of {}", did_doc.id),
+            });
+        }
+        Ok(did_doc)
+    }
+
+    fn web_origin(&self, domain: &str) -> String {
+        match &self.web_base {
+            Some(base) => base.trim_end_matches('/').to_string(),
+            None => format!("https://{domain}"),
+        }
+    }
+
+    async fn get(
+        &self,
+        url: &str,
+        accept: Option<&str>,
+    ) -> Result<reqwest::Response, MigrationError> {
+        let client = reqwest::Client::new();
+        let mut request = client.get(url);
+        if let Some(accept) = accept {
+            request = request.header("Accept", accept);
+        }
+        let request = request.build().map_err(upstream)?;
+        let response = send_governed(&client, request, &self.retry)
+            .await
+            .map_err(upstream)?;
+        if !response.status().is_success() {
+            return Err(MigrationError::from_response(url, response).await);
+        }
+        Ok(response)
+    }
+}
+
+fn parse_handle(handle: &str) -> Result<Handle, MigrationError> {
+    Handle::new(handle.to_lowercase()).map_err(|_error| MigrationError::Validation {
+        field: "handle".to_string(),
+    })
+}
+
+fn upstream(error: reqwest::Error) -> MigrationError {
+    MigrationError::Upstream {
+        message: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const DID: &str = "did:plc:example123";
+
+    fn resolver(server: &MockServer) -> IdentityResolver {
+        IdentityResolver {
+            plc_directory: server.uri(),
+            dns_over_https: format!("{}/dns-query", server.uri()),
+            web_base: Some(server.uri()),
+            retry: RetryPolicy::none(),
+        }
+    }
+
+    async fn mount_did_doc(server: &MockServer, handle: &str) {
+        Mock::given(method("GET"))
+            .and(path(format!("/{DID}")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": DID,
+                "alsoKnownAs": [format!("at://{handle}")],
+                "service": [{
+                    "id": "#atproto_pds",
+                    "type": "AtprotoPersonalDataServer",
+                    "serviceEndpoint": "https://pds.example.com/",
+                }],
+            })))
+            .mount(server)
+            .await;
+    }
+
+    #[test]
+    fn test_resolve_handle_through_dns() {
+        tokio_test::block_on(async {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/dns-query"))
+                .and(query_param("name", "_atproto.alice.example.com"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "Status": 0,
+                    "Answer": [{
+                        "name": "_atproto.alice.example.com",
+                        "type": 16,
+                        "data": format!("\"did={DID}\""),
+                    }],
+                })))
+                .mount(&server)
+                .await;
+            mount_did_doc(&server, "alice.example.com").await;
+
+            let identity = resolver(&server)
+                .resolve("@Alice.example.com")
+                .await
+                .unwrap();
+            assert_eq!(identity.did.as_str(), DID);
+            assert_eq!(identity.handle.as_deref(), Some("alice.example.com"));
+            assert_eq!(identity.pds_host, "https://pds.example.com");
+        });
+    }
+
+    #[test]
+    fn test_resolve_handle_falls_back_to_well_known() {
+        tokio_test::block_on(async {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/dns-query"))
+                .respond_with(
+                    ResponseTemplate::new(200).set_body_json(serde_json::json!({ "Status": 3 })),
+                )
+                .mount(&server)
+                .await;
+            Mock::given(method("GET"))
+                .and(path("/.well-known/atproto-did"))
+                .respond_with(ResponseTemplate::new(200).set_body_string(format!("{DID}\n")))
+                .mount(&server)
+                .await;
+            mount_did_doc(&server, "bob.example.com").await;
+
+            // The DID document claims a different handle, so this one is not confirmed
+            let error = resolver(&server)
+                .resolve("alice.example.com")
+                .await
+                .unwrap_err();
+            assert!(matches!(error, MigrationError::Validation { field } if field == "handle"));
+
+            let handle = parse_handle("alice.example.com").unwrap();
+            let did = resolver(&server).resolve_handle(&handle).await.unwrap();
+            assert_eq!(did.as_str(), DID);
+        });
+    }
+
+    #[test]
+    fn test_resolve_did_web() {
+        tokio_test::block_on(async {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/.well-known/did.json"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "id": "did:web:alice.example.com",
+                    "service": [{
+                        "id": "#atproto_pds",
+                        "type": "AtprotoPersonalDataServer",
+                        "serviceEndpoint": "https://pds.example.com",
+                    }],
+                })))
+                .mount(&server)
+                .await;
+
+            let identity = resolver(&server)
+                .resolve("did:web:alice.example.com")
+                .await
+                .unwrap();
+            assert_eq!(identity.pds_host, "https://pds.example.com");
+            assert_eq!(identity.handle, None);
+        });
+    }
+}
@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+/// How a request failed, as far as the client can tell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestFailure {
+    /// The connection was never established, so the PDS never saw the request.
+    NotSent,
+    /// The connection broke after the request may have reached the PDS.
+    Network,
+    /// The PDS answered with a 5xx status.
+    Server,
+}
+
+impl RequestFailure {
+    pub fn from_status(status: u16) -> Option<Self> {
+        match status {
+            500 | 502 | 503 | 504 => Some(Self::Server),
+            _ => None,
+        }
+    }
+
+    pub fn from_reqwest(error: &reqwest::Error) -> Self {
+        if error.is_connect() {
+            Self::NotSent
+        } else {
+            Self::Network
+        }
+    }
+}
+
+/// When and how often a failed request is sent again.
+///
+/// Idempotent requests (XRPC queries) are retried on any failure the policy allows.
+/// Everything else is only retried when the request provably never reached the PDS.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Attempts per request, including the first.
+    pub max_attempts: u32,
+    /// Delay before the second attempt; doubles for every attempt after that.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Randomise each delay between half and all of its value so parallel transfers
+    /// do not retry in lockstep.
+    pub jitter: bool,
+    pub retry_network_errors: bool,
+    pub retry_server_errors: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            jitter: true,
+            retry_network_errors: true,
+            retry_server_errors: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry; every failure is returned straight away.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Whether a request that failed with `failure` on attempt number `attempt` should
+    /// be sent again.
+    pub fn should_retry(&self, idempotent: bool, failure: RequestFailure, attempt: u32) -> bool {
+        if attempt >= self.max_attempts {
+            return false;
+        }
+        match failure {
+            RequestFailure::NotSent => self.retry_network_errors,
+            RequestFailure::Network => idempotent && self.retry_network_errors,
+            RequestFailure::Server => idempotent && self.retry_server_errors,
+        }
+    }
+
+    /// How long to wait after attempt number `attempt` failed.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(16);
+        let delay = self
+            .initial_backoff
+            .saturating_mul(1 << exponent)
+            .min(self.max_backoff);
+        if self.jitter && !delay.is_zero() {
+            let millis = delay.as_millis() as u64;
+            Duration::from_millis(rand::random_range(millis / 2..=millis))
+        } else {
+            delay
+        }
+    }
+
+    /// Logs and sleeps for [`RetryPolicy::backoff`].
+    pub async fn pause(&self, attempt: u32, failure: RequestFailure) {
+        let delay = self.backoff(attempt);
+        tracing::warn!(
+            "Request failed ({:?}) on attempt {}, retrying in {:?}",
+            failure,
+            attempt,
+            delay
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            jitter: false,
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let policy = policy();
+        assert_eq!(policy.backoff(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(2));
+        assert_eq!(policy.backoff(20), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_jitter_stays_within_half_of_delay() {
+        let policy = RetryPolicy::default();
+        for _ in 0..100 {
+            let delay = policy.backoff(2);
+            assert!(delay >= Duration::from_millis(500) && delay <= Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn test_non_idempotent_requests_only_retry_when_not_sent() {
+        let policy = policy();
+        assert!(policy.should_retry(false, RequestFailure::NotSent, 1));
+        assert!(!policy.should_retry(false, RequestFailure::Network, 1));
+        assert!(!policy.should_retry(false, RequestFailure::Server, 1));
+        assert!(policy.should_retry(true, RequestFailure::Network, 1));
+        assert!(policy.should_retry(true, RequestFailure::Server, 3));
+        assert!(!policy.should_retry(true, RequestFailure::Server, 4));
+        assert!(!RetryPolicy::none().should_retry(true, RequestFailure::NotSent, 1));
+    }
+}
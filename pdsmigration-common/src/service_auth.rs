@@ -1,5 +1,5 @@
 use crate::agent::{get_service_auth, login_helper};
-use crate::{build_agent, MigrationError};
+use crate::{build_agent, MigrationError, MigrationOptions};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -15,9 +15,12 @@ pub struct ServiceAuthResponse {
     pub token: String,
 }
 
-#[tracing::instrument(skip(req))]
-pub async fn get_service_auth_api(req: ServiceAuthRequest) -> Result<String, MigrationError> {
-    let agent = build_agent().await?;
+#[tracing::instrument(skip(req, options))]
+pub async fn get_service_auth_api(
+    req: ServiceAuthRequest,
+    options: &MigrationOptions,
+) -> Result<String, MigrationError> {
+    let agent = build_agent(&options.retry).await?;
     login_helper(
         &agent,
         req.pds_host.as_str(),
@@ -0,0 +1,456 @@
+use crate::MigrationError;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::stream::BoxStream;
+use futures_util::StreamExt;
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::io::AsyncWriteExt;
+
+pub type StagingStream<'a> = BoxStream<'a, Result<Bytes, MigrationError>>;
+
+/// Where exported repos and blobs are kept between the export and import steps.
+///
+/// Keys are `/` separated relative paths, see [`repo_key`], [`repo_rev_key`], [`blob_dir`] and [`blob_key`].
+#[async_trait]
+pub trait StagingStore: Send + Sync {
+    /// Writes `stream` to `key`, replacing any existing entry, and returns the number of bytes
+    /// written. A failed write never leaves a partial entry behind.
+    async fn put_stream(&self, key: &str, stream: StagingStream<'_>)
+        -> Result<u64, MigrationError>;
+
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), MigrationError> {
+        let stream = futures_util::stream::once(async move { Ok(Bytes::from(bytes)) }).boxed();
+        self.put_stream(key, stream).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, MigrationError>;
+
+    async fn exists(&self, key: &str) -> Result<bool, MigrationError>;
+
+    /// Lists the keys stored directly under `dir`, in lexical order.
+    async fn list(&self, dir: &str) -> Result<Vec<String>, MigrationError>;
+
+    async fn delete(&self, key: &str) -> Result<(), MigrationError>;
+}
+
+fn did_dir(did: &str) -> String {
+    did.replace(":", "-")
+}
+
+/// Key of the exported repository CAR file for `did`.
+pub fn repo_key(did: &str) -> String {
+    did_dir(did) + ".car"
+}
+
+/// Key of the repo rev that was last imported into the destination for `did`, used as the
+/// `since` cursor when catching up on records written during the migration.
+pub fn repo_rev_key(did: &str) -> String {
+    did_dir(did) + ".rev"
+}
+
+/// Key of the `did.json` a did:web account publishes on its domain once it has moved.
+pub fn did_web_document_key(did: &str) -> String {
+    did_dir(did) + ".did.json"
+}
+
+/// Key of the exported PLC audit log of `did`.
+pub fn plc_audit_log_key(did: &str) -> String {
+    did_dir(did) + ".plc.json"
+}
+
+/// Directory holding the exported blobs of `did`.
+pub fn blob_dir(did: &str) -> String {
+    did_dir(did)
+}
+
+/// Key of the exported blob `cid` of `did`.
+pub fn blob_key(did: &str, cid: &str) -> String {
+    format!("{}/{}", blob_dir(did), cid)
+}
+
+fn validate_key(key: &str) -> Result<(), MigrationError> {
+    if key.is_empty()
+        || key.starts_with('/')
+        || key
+            .split('/')
+            .any(|segment| segment.is_empty() || segment == "." || segment == "..")
+    {
+        return Err(MigrationError::Validation {
+            field: format!("staging key {key}"),
+        });
+    }
+    Ok(())
+}
+
+/// Stores entries as files under a root directory.
+#[derive(Debug, Clone)]
+pub struct LocalStagingStore {
+    root: PathBuf,
+}
+
+impl LocalStagingStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// A store rooted at the process working directory, where exports were always written.
+    pub fn current_dir() -> Result<Self, MigrationError> {
+        let root = std::env::current_dir().map_err(|error| {
+            tracing::error!("Failed to get current directory: {}", error);
+            MigrationError::Runtime {
+                message: "Failed to get current directory".to_string(),
+            }
+        })?;
+        Ok(Self::new(root))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Filesystem path of `key`.
+    pub fn path(&self, key: &str) -> Result<PathBuf, MigrationError> {
+        validate_key(key)?;
+        Ok(key
+            .split('/')
+            .fold(self.root.clone(), |path, segment| path.join(segment)))
+    }
+}
+
+fn io_error(action: &str, path: &Path, error: std::io::Error) -> MigrationError {
+    tracing::error!("Failed to {} {}: {}", action, path.display(), error);
+    MigrationError::Runtime {
+        message: format!(
+            "Failed to {} {}, with error {}",
+            action,
+            path.display(),
+            error
+        ),
+    }
+}
+
+#[async_trait]
+impl StagingStore for LocalStagingStore {
+    async fn put_stream(
+        &self,
+        key: &str,
+        mut stream: StagingStream<'_>,
+    ) -> Result<u64, MigrationError> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|error| io_error("create directory", parent, error))?;
+        }
+        let mut partial = path.clone().into_os_string();
+        partial.push(".part");
+        let partial = PathBuf::from(partial);
+
+        let result: Result<u64, MigrationError> = async {
+            let mut file = tokio::fs::File::create(&partial)
+                .await
+                .map_err(|error| io_error("create file", &partial, error))?;
+            let mut written = 0;
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk?;
+                file.write_all(&chunk)
+                    .await
+                    .map_err(|error| io_error("write to", &partial, error))?;
+                written += chunk.len() as u64;
+            }
+            file.flush()
+                .await
+                .map_err(|error| io_error("flush", &partial, error))?;
+            Ok(written)
+        }
+        .await;
+
+        match result {
+            Ok(written) => {
+                tokio::fs::rename(&partial, &path)
+                    .await
+                    .map_err(|error| io_error("move into place", &path, error))?;
+                Ok(written)
+            }
+            Err(error) => {
+                let _ = tokio::fs::remove_file(&partial).await;
+                Err(error)
+            }
+        }
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, MigrationError> {
+        let path = self.path(key)?;
+        tokio::fs::read(&path)
+            .await
+            .map_err(|error| io_error("read", &path, error))
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, MigrationError> {
+        let path = self.path(key)?;
+        tokio::fs::try_exists(&path)
+            .await
+            .map_err(|error| io_error("check", &path, error))
+    }
+
+    async fn list(&self, dir: &str) -> Result<Vec<String>, MigrationError> {
+        let path = self.path(dir)?;
+        let mut entries = match tokio::fs::read_dir(&path).await {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(error) => return Err(io_error("read directory", &path, error)),
+        };
+        let mut keys = vec![];
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(|error| io_error("read directory", &path, error))?
+        {
+            let is_file = entry
+                .file_type()
+                .await
+                .map_err(|error| io_error("read directory", &path, error))?
+                .is_file();
+            let name = entry.file_name().to_string_lossy().to_string();
+            if is_file && !name.ends_with(".part") {
+                keys.push(format!("{dir}/{name}"));
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), MigrationError> {
+        let path = self.path(key)?;
+        match tokio::fs::remove_file(&path).await {
+            Ok(_) => Ok(()),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(()),
+            Err(error) => Err(io_error("delete", &path, error)),
+        }
+    }
+}
+
+/// A [`LocalStagingStore`] in a fresh directory under the system temp directory, removed again
+/// when the store is dropped. Gives every job its own workspace.
+#[derive(Debug)]
+pub struct TempStagingStore {
+    inner: LocalStagingStore,
+}
+
+impl TempStagingStore {
+    pub fn new() -> Result<Self, MigrationError> {
+        let mut root = std::env::temp_dir();
+        root.push(format!("pdsmigration-{:016x}", rand::random::<u64>()));
+        std::fs::create_dir_all(&root)
+            .map_err(|error| io_error("create directory", &root, error))?;
+        Ok(Self {
+            inner: LocalStagingStore::new(root),
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        self.inner.root()
+    }
+}
+
+impl Drop for TempStagingStore {
+    fn drop(&mut self) {
+        if let Err(error) = std::fs::remove_dir_all(self.inner.root()) {
+            tracing::error!(
+                "Failed to remove staging directory {}: {}",
+                self.inner.root().display(),
+                error
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl StagingStore for TempStagingStore {
+    async fn put_stream(
+        &self,
+        key: &str,
+        stream: StagingStream<'_>,
+    ) -> Result<u64, MigrationError> {
+        self.inner.put_stream(key, stream).await
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, MigrationError> {
+        self.inner.get(key).await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, MigrationError> {
+        self.inner.exists(key).await
+    }
+
+    async fn list(&self, dir: &str) -> Result<Vec<String>, MigrationError> {
+        self.inner.list(dir).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), MigrationError> {
+        self.inner.delete(key).await
+    }
+}
+
+/// Keeps every entry in memory. Useful for tests and for small repos that never need to touch
+/// the disk.
+#[derive(Debug, Default)]
+pub struct MemoryStagingStore {
+    entries: Mutex<BTreeMap<String, Vec<u8>>>,
+}
+
+impl MemoryStagingStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn entries(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, Vec<u8>>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl StagingStore for MemoryStagingStore {
+    async fn put_stream(
+        &self,
+        key: &str,
+        mut stream: StagingStream<'_>,
+    ) -> Result<u64, MigrationError> {
+        validate_key(key)?;
+        let mut buffer = vec![];
+        while let Some(chunk) = stream.next().await {
+            buffer.extend_from_slice(&chunk?);
+        }
+        let written = buffer.len() as u64;
+        self.entries().insert(key.to_string(), buffer);
+        Ok(written)
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, MigrationError> {
+        validate_key(key)?;
+        self.entries()
+            .get(key)
+            .cloned()
+            .ok_or(MigrationError::Runtime {
+                message: format!("Staging entry {key} not found"),
+            })
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, MigrationError> {
+        validate_key(key)?;
+        Ok(self.entries().contains_key(key))
+    }
+
+    async fn list(&self, dir: &str) -> Result<Vec<String>, MigrationError> {
+        validate_key(dir)?;
+        let prefix = format!("{dir}/");
+        Ok(self
+            .entries()
+            .keys()
+            .filter(|key| {
+                key.strip_prefix(&prefix)
+                    .is_some_and(|name| !name.contains('/'))
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), MigrationError> {
+        validate_key(key)?;
+        self.entries().remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn exercise_store(store: &dyn StagingStore) {
+        let did = "did:plc:example123";
+        store.put(&repo_key(did), b"repo".to_vec()).await.unwrap();
+        store
+            .put(&blob_key(did, "bafkreiblob2"), b"two".to_vec())
+            .await
+            .unwrap();
+        store
+            .put(&blob_key(did, "bafkreiblob1"), b"one".to_vec())
+            .await
+            .unwrap();
+
+        assert_eq!(store.get(&repo_key(did)).await.unwrap(), b"repo");
+        assert!(store.exists(&blob_key(did, "bafkreiblob1")).await.unwrap());
+        assert!(!store
+            .exists(&blob_key(did, "bafkreimissing"))
+            .await
+            .unwrap());
+        assert_eq!(
+            store.list(&blob_dir(did)).await.unwrap(),
+            vec![
+                "did-plc-example123/bafkreiblob1".to_string(),
+                "did-plc-example123/bafkreiblob2".to_string(),
+            ]
+        );
+
+        store.delete(&blob_key(did, "bafkreiblob1")).await.unwrap();
+        assert_eq!(store.list(&blob_dir(did)).await.unwrap().len(), 1);
+        assert!(store.list("did-plc-nobody").await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_keys() {
+        assert_eq!(repo_key("did:plc:example123"), "did-plc-example123.car");
+        assert_eq!(
+            blob_key("did:plc:example123", "bafkreiabc"),
+            "did-plc-example123/bafkreiabc"
+        );
+    }
+
+    #[test]
+    fn test_memory_store() {
+        tokio_test::block_on(exercise_store(&MemoryStagingStore::new()));
+    }
+
+    #[test]
+    fn test_temp_store_is_removed_on_drop() {
+        let store = TempStagingStore::new().unwrap();
+        let root = store.root().to_path_buf();
+        tokio_test::block_on(exercise_store(&store));
+        assert!(root.join("did-plc-example123.car").exists());
+        drop(store);
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn test_failed_write_leaves_no_partial_entry() {
+        let store = TempStagingStore::new().unwrap();
+        let stream = futures_util::stream::iter(vec![
+            Ok(Bytes::from_static(b"partial")),
+            Err(MigrationError::Runtime {
+                message: "connection reset".to_string(),
+            }),
+        ])
+        .boxed();
+        let result = tokio_test::block_on(store.put_stream("did-plc-example123.car", stream));
+        assert!(result.is_err());
+        assert!(!tokio_test::block_on(store.exists("did-plc-example123.car")).unwrap());
+        assert!(!store.root().join("did-plc-example123.car.part").exists());
+    }
+
+    #[test]
+    fn test_keys_cannot_escape_root() {
+        let store = MemoryStagingStore::new();
+        for key in ["", "/etc/passwd", "../outside", "a//b", "a/./b"] {
+            assert!(matches!(
+                tokio_test::block_on(store.exists(key)),
+                Err(MigrationError::Validation { .. })
+            ));
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use crate::agent::{download_blob, upload_blob};
+use crate::{
+    blob_key, check_uploaded_cid, count_bytes, fresh_session, put_verified_blob, verify_blob,
+    BlobCheck, GetBlobRequest, MigrationAgent, MigrationError, MigrationOptions, ProgressEvent,
+    StagingStore,
+};
+use bsky_sdk::api::types::string::Did;
+use bytes::Bytes;
+use futures_util::stream::{self, Stream, StreamExt};
+use std::future::{self, Future};
+use tokio_util::sync::CancellationToken;
+
+/// What happened to a single blob in a transfer.
+#[derive(Debug)]
+pub enum BlobOutcome {
+    Transferred,
+    /// Already staged (or already on the destination), nothing to do.
+    Skipped,
+    /// The bytes did not match the blob's CID.
+    Corrupt,
+    /// Stopped part way because the transfer was canceled; nothing was kept.
+    Canceled,
+    Failed(MigrationError),
+}
+
+/// Per-blob results of a transfer, by CID.
+#[derive(Debug, Default, PartialEq)]
+pub struct BlobTransferReport {
+    pub successful_blobs: Vec<String>,
+    pub invalid_blobs: Vec<String>,
+    pub corrupt_blobs: Vec<String>,
+}
+
+impl BlobTransferReport {
+    pub fn record(&mut self, cid: String, outcome: &BlobOutcome) {
+        match outcome {
+            BlobOutcome::Transferred => self.successful_blobs.push(cid),
+            BlobOutcome::Skipped | BlobOutcome::Canceled => {}
+            BlobOutcome::Corrupt => self.corrupt_blobs.push(cid),
+            BlobOutcome::Failed(_) => self.invalid_blobs.push(cid),
+        }
+    }
+}
+
+/// Runs `transfer` for every CID with at most `options.blob_concurrency` in flight,
+/// yielding each blob's outcome as it finishes and reporting it to `options.progress`.
+/// Rate limits are paced and retried by the governor underneath, so a rate-limited blob
+/// here has already been retried. Once `options.cancel` fires no further blobs are started.
+pub fn run_blob_transfers<'a, F, Fut>(
+    cids: Vec<String>,
+    options: &'a MigrationOptions,
+    transfer: F,
+) -> impl Stream<Item = (String, BlobOutcome)> + 'a
+where
+    F: Fn(String) -> Fut + 'a,
+    Fut: Future<Output = Result<Option<BlobCheck>, MigrationError>> + 'a,
+{
+    options.report(ProgressEvent::Total {
+        items: cids.len() as u64,
+    });
+    stream::iter(cids)
+        .take_while(move |_| future::ready(!options.cancel.is_cancelled()))
+        .map(move |cid| {
+            let transfer = transfer(cid.clone());
+            async move {
+                let outcome = match transfer.await {
+                    Ok(Some(BlobCheck::Verified)) => BlobOutcome::Transferred,
+                    Ok(Some(BlobCheck::Corrupt)) => BlobOutcome::Corrupt,
+                    Ok(None) => BlobOutcome::Skipped,
+                    Err(MigrationError::Canceled { .. }) => BlobOutcome::Canceled,
+                    Err(error) => {
+                        tracing::error!("Failed to transfer blob {}: {}", cid, error);
+                        BlobOutcome::Failed(error)
+                    }
+                };
+                options.report(ProgressEvent::for_outcome(cid.clone(), &outcome));
+                (cid, outcome)
+            }
+        })
+        .buffer_unordered(options.blob_concurrency.max(1))
+}
+
+/// Drives [`run_blob_transfers`] to completion and collects the results, or returns
+/// [`MigrationError::Canceled`] with the blobs that did finish if it was canceled.
+pub async fn run_all_blob_transfers<'a, F, Fut>(
+    cids: Vec<String>,
+    options: &'a MigrationOptions,
+    transfer: F,
+) -> Result<BlobTransferReport, MigrationError>
+where
+    F: Fn(String) -> Fut + 'a,
+    Fut: Future<Output = Result<Option<BlobCheck>, MigrationError>> + 'a,
+{
+    let mut report = BlobTransferReport::default();
+    let mut results = Box::pin(run_blob_transfers(cids, options, transfer));
+    while let Some((cid, outcome)) = results.next().await {
+        report.record(cid, &outcome);
+    }
+    if options.cancel.is_cancelled() {
+        tracing::info!(
+            "Blob transfer canceled after {} blobs",
+            report.successful_blobs.len()
+        );
+        return Err(MigrationError::Canceled {
+            finished: report.successful_blobs,
+        });
+    }
+    Ok(report)
+}
+
+/// Fails `stream` with [`MigrationError::Canceled`] at the next chunk once `cancel` fires, so
+/// whatever is consuming it throws the partial data away.
+pub fn until_canceled<S>(
+    stream: S,
+    cancel: &CancellationToken,
+) -> impl Stream<Item = Result<Bytes, MigrationError>>
+where
+    S: Stream<Item = Result<Bytes, MigrationError>>,
+{
+    let cancel = cancel.clone();
+    stream.map(move |chunk| {
+        if cancel.is_cancelled() {
+            Err(MigrationError::Canceled { finished: vec![] })
+        } else {
+            chunk
+        }
+    })
+}
+
+/// Downloads one blob from `pds_host` into `store`, or skips it if already staged.
+pub async fn stage_blob(
+    store: &dyn StagingStore,
+    pds_host: &str,
+    did: &Did,
+    token: &str,
+    cid: String,
+    options: &MigrationOptions,
+) -> Result<Option<BlobCheck>, MigrationError> {
+    let key = blob_key(did.as_str(), &cid);
+    if store.exists(&key).await? {
+        return Ok(None);
+    }
+    let request = GetBlobRequest {
+        did: did.clone(),
+        cid: cid.clone(),
+        token: token.to_string(),
+    };
+    let stream = download_blob(pds_host, &request, &options.retry).await?;
+    let stream = count_bytes(options.progress.clone(), stream).map(|chunk| {
+        chunk.map_err(|error| MigrationError::Runtime {
+            message: error.to_string(),
+        })
+    });
+    let stream = until_canceled(stream, &options.cancel).boxed();
+    put_verified_blob(store, &key, &cid, stream).await.map(Some)
+}
+
+/// Like [`stage_blob`], downloading from the PDS `agent` is logged in to with its
+/// session's current token.
+pub async fn stage_session_blob(
+    agent: &MigrationAgent,
+    store: &dyn StagingStore,
+    cid: String,
+    options: &MigrationOptions,
+) -> Result<Option<BlobCheck>, MigrationError> {
+    let session = fresh_session(agent, options).await?;
+    let endpoint = agent.get_endpoint().await;
+    stage_blob(
+        store,
+        &endpoint,
+        &session.did,
+        &session.access_jwt,
+        cid,
+        options,
+    )
+    .await
+}
+
+/// Uploads one staged blob through `agent`, checking the staged bytes and the CID the
+/// PDS hands back.
+pub async fn upload_staged_blob(
+    agent: &MigrationAgent,
+    store: &dyn StagingStore,
+    did: &str,
+    cid: String,
+    options: &MigrationOptions,
+) -> Result<Option<BlobCheck>, MigrationError> {
+    let file = store.get(&blob_key(did, &cid)).await.inspect_err(|error| {
+        tracing::error!("Failed to read next blob: {}", error);
+    })?;
+    if verify_blob(&cid, &file)? == BlobCheck::Corrupt {
+        tracing::error!("Staged blob {} does not match its CID, skipping", cid);
+        return Ok(Some(BlobCheck::Corrupt));
+    }
+    options.check_canceled()?;
+    fresh_session(agent, options).await?;
+    let size = file.len() as u64;
+    let uploaded = upload_blob(agent, file).await?;
+    options.report(ProgressEvent::BytesTransferred { bytes: size });
+    let check = check_uploaded_cid(&cid, &uploaded);
+    if check == BlobCheck::Corrupt {
+        tracing::error!("Uploaded blob {} came back as {}", cid, uploaded);
+    }
+    Ok(Some(check))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn options(concurrency: usize) -> MigrationOptions {
+        MigrationOptions {
+            blob_concurrency: concurrency,
+            ..MigrationOptions::default()
+        }
+    }
+
+    fn cids(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("cid{i}")).collect()
+    }
+
+    #[test]
+    fn test_concurrency_is_bounded() {
+        let in_flight = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let options = options(3);
+        let report = tokio_test::block_on(run_all_blob_transfers(cids(10), &options, |_| async {
+            let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(Some(BlobCheck::Verified))
+        }))
+        .unwrap();
+        assert_eq!(report.successful_blobs.len(), 10);
+        assert!(peak.load(Ordering::SeqCst) <= 3);
+        assert!(peak.load(Ordering::SeqCst) > 1);
+    }
+
+    #[test]
+    fn test_outcomes_are_reported_per_blob() {
+        let options = options(2);
+        let report = tokio_test::block_on(run_all_blob_transfers(
+            cids(4),
+            &options,
+            |cid| async move {
+                match cid.as_str() {
+                    "cid0" => Ok(Some(BlobCheck::Verified)),
+                    "cid1" => Ok(Some(BlobCheck::Corrupt)),
+                    "cid2" => Ok(None),
+                    _ => Err(MigrationError::Upstream {
+                        message: "gone".to_string(),
+                    }),
+                }
+            },
+        ))
+        .unwrap();
+        assert_eq!(
+            report,
+            BlobTransferReport {
+                successful_blobs: vec!["cid0".to_string()],
+                invalid_blobs: vec!["cid3".to_string()],
+                corrupt_blobs: vec!["cid1".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_cancel_stops_starting_blobs() {
+        let options = options(1);
+        let started = AtomicUsize::new(0);
+        let result = tokio_test::block_on(run_all_blob_transfers(cids(5), &options, |_| {
+            started.fetch_add(1, Ordering::SeqCst);
+            // The first blob finishes, then the caller cancels
+            options.cancel.cancel();
+            async { Ok(Some(BlobCheck::Verified)) }
+        }));
+        assert_eq!(started.load(Ordering::SeqCst), 1);
+        match result {
+            Err(MigrationError::Canceled { finished }) => assert_eq!(finished, vec!["cid0"]),
+            other => panic!("expected a cancellation, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_canceled_stream_leaves_no_partial_entry() {
+        let store = crate::MemoryStagingStore::new();
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let chunks = stream::iter(vec![Ok(Bytes::from_static(b"partial"))]);
+        let result =
+            tokio_test::block_on(store.put_stream("blob", until_canceled(chunks, &cancel).boxed()));
+        assert!(matches!(result, Err(MigrationError::Canceled { .. })));
+        assert!(!tokio_test::block_on(store.exists("blob")).unwrap());
+    }
+}
@@ -0,0 +1,357 @@
+use crate::agent::{
+    download_blob_with_mime_type, missing_blobs, resume_session, upload_blob_stream,
+};
+use crate::blob_verify::parse_cid;
+use crate::{
+    build_agent, check_uploaded_cid, count_bytes, fresh_session, run_all_blob_transfers,
+    until_canceled, BlobCheck, BlobHasher, GetBlobRequest, MigrationError, MigrationOptions,
+    RequestFailure,
+};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize)]
+pub struct TransferBlobsRequest {
+    pub origin: String,
+    pub destination: String,
+    pub did: String,
+    pub origin_token: String,
+    pub destination_token: String,
+    #[serde(default)]
+    pub origin_refresh_token: Option<String>,
+    #[serde(default)]
+    pub destination_refresh_token: Option<String>,
+}
+
+impl std::fmt::Debug for TransferBlobsRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TransferBlobsRequest")
+            .field("origin", &self.origin)
+            .field("destination", &self.destination)
+            .field("did", &self.did)
+            .field("origin_token", &"[REDACTED]")
+            .field("destination_token", &"[REDACTED]")
+            .field(
+                "origin_refresh_token",
+                &self.origin_refresh_token.as_ref().map(|_| "[REDACTED]"),
+            )
+            .field(
+                "destination_refresh_token",
+                &self
+                    .destination_refresh_token
+                    .as_ref()
+                    .map(|_| "[REDACTED]"),
+            )
+            .finish()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TransferBlobsResponse {
+    pub successful_blobs: Vec<String>,
+    pub invalid_blobs: Vec<String>,
+    pub corrupt_blobs: Vec<String>,
+}
+
+/// Copies every blob the destination is missing straight from the origin, piping each
+/// download into an `uploadBlob` request so nothing is staged on disk or held in memory.
+#[tracing::instrument(skip(options))]
+pub async fn transfer_blobs_api(
+    req: TransferBlobsRequest,
+    options: &MigrationOptions,
+) -> Result<TransferBlobsResponse, MigrationError> {
+    // One agent per side so both sessions can be refreshed while blobs are in flight.
+    let destination_agent = build_agent(&options.retry).await?;
+    resume_session(
+        &destination_agent,
+        req.destination.as_str(),
+        req.did.as_str(),
+        req.destination_token.as_str(),
+        req.destination_refresh_token.as_deref(),
+    )
+    .await?;
+    let missing_blobs = missing_blobs(&destination_agent).await?;
+    let origin_agent = build_agent(&options.retry).await?;
+    resume_session(
+        &origin_agent,
+        req.origin.as_str(),
+        req.did.as_str(),
+        req.origin_token.as_str(),
+        req.origin_refresh_token.as_deref(),
+    )
+    .await?;
+
+    let cids = missing_blobs
+        .iter()
+        .map(|missing_blob| missing_blob.cid.as_ref().to_string())
+        .collect();
+    let report = run_all_blob_transfers(cids, options, |cid| {
+        let origin_agent = &origin_agent;
+        let destination_agent = &destination_agent;
+        let origin = req.origin.as_str();
+        let destination = req.destination.as_str();
+        async move {
+            let origin_session = fresh_session(origin_agent, options).await?;
+            let destination_session = fresh_session(destination_agent, options).await?;
+            let request = GetBlobRequest {
+                did: origin_session.did.clone(),
+                cid,
+                token: origin_session.access_jwt.clone(),
+            };
+            stream_blob(
+                origin,
+                &request,
+                destination,
+                &destination_session.access_jwt,
+                options,
+            )
+            .await
+        }
+    })
+    .await?;
+    Ok(TransferBlobsResponse {
+        successful_blobs: report.successful_blobs,
+        invalid_blobs: report.invalid_blobs,
+        corrupt_blobs: report.corrupt_blobs,
+    })
+}
+
+/// Streams one blob from `origin` to `destination`, hashing it on the way through and
+/// checking the CID the destination assigns. A streamed body cannot be replayed, so if the
+/// destination turns it away with a status worth retrying, the blob is downloaded again and
+/// streamed once more.
+pub async fn stream_blob(
+    origin: &str,
+    request: &GetBlobRequest,
+    destination: &str,
+    destination_token: &str,
+    options: &MigrationOptions,
+) -> Result<Option<BlobCheck>, MigrationError> {
+    let expected = parse_cid(&request.cid)?;
+    let (hasher, uploaded) =
+        match pipe_blob(origin, request, destination, destination_token, options).await {
+            Err(error) if is_retryable(&error) => {
+                tracing::warn!(
+                    "Streaming upload of blob {} failed, streaming it again: {}",
+                    request.cid,
+                    error
+                );
+                pipe_blob(origin, request, destination, destination_token, options).await?
+            }
+            result => result?,
+        };
+
+    if hasher.check(&expected) == BlobCheck::Corrupt {
+        tracing::error!("Blob {} from origin does not match its CID", request.cid);
+        return Ok(Some(BlobCheck::Corrupt));
+    }
+    let check = check_uploaded_cid(&request.cid, &uploaded);
+    if check == BlobCheck::Corrupt {
+        tracing::error!("Uploaded blob {} came back as {}", request.cid, uploaded);
+    }
+    Ok(Some(check))
+}
+
+/// Downloads the blob and pipes it into an upload, returning the hash of what went through
+/// and the CID the destination assigned.
+async fn pipe_blob(
+    origin: &str,
+    request: &GetBlobRequest,
+    destination: &str,
+    destination_token: &str,
+    options: &MigrationOptions,
+) -> Result<(BlobHasher, String), MigrationError> {
+    let (mime_type, content_length, stream) =
+        download_blob_with_mime_type(origin, request, &options.retry).await?;
+    let hasher = BlobHasher::default();
+    let stream = count_bytes(options.progress.clone(), hasher.tap(stream)).map(|chunk| {
+        chunk.map_err(|error| MigrationError::Runtime {
+            message: error.to_string(),
+        })
+    });
+    // A canceled upload fails the request body, so the destination never stores a partial blob
+    let body = reqwest::Body::wrap_stream(until_canceled(stream, &options.cancel));
+    let uploaded = upload_blob_stream(
+        destination,
+        destination_token,
+        &mime_type,
+        content_length,
+        body,
+        &options.retry,
+    )
+    .await?;
+    Ok((hasher, uploaded))
+}
+
+/// Whether the destination turned an upload away with a rate limit or a server error.
+fn is_retryable(error: &MigrationError) -> bool {
+    match error {
+        MigrationError::RateLimitReached => true,
+        MigrationError::Xrpc { upstream } => RequestFailure::from_status(upstream.status).is_some(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{blob_cid, RetryPolicy};
+    use bsky_sdk::api::types::string::Did;
+    use wiremock::matchers::{body_bytes, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const BLOB: &[u8] = b"\x89PNG not really an image";
+
+    fn upload_response(cid: &str) -> serde_json::Value {
+        serde_json::json!({
+            "blob": {
+                "$type": "blob",
+                "ref": { "$link": cid },
+                "mimeType": "image/png",
+                "size": BLOB.len(),
+            }
+        })
+    }
+
+    fn options() -> MigrationOptions {
+        MigrationOptions {
+            retry: RetryPolicy::none(),
+            ..MigrationOptions::default()
+        }
+    }
+
+    fn request(cid: &str) -> GetBlobRequest {
+        GetBlobRequest {
+            did: Did::new("did:plc:example123".to_string()).unwrap(),
+            cid: cid.to_string(),
+            token: "origin-token".to_string(),
+        }
+    }
+
+    async fn servers(served: &'static [u8], returned_cid: &str) -> (MockServer, MockServer) {
+        let origin = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.sync.getBlob"))
+            .and(query_param("did", "did:plc:example123"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", "image/png")
+                    .set_body_bytes(served),
+            )
+            .mount(&origin)
+            .await;
+        let destination = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.repo.uploadBlob"))
+            .and(header("content-type", "image/png"))
+            .and(header("authorization", "Bearer destination-token"))
+            .and(header("content-length", served.len().to_string()))
+            .and(body_bytes(served))
+            .respond_with(ResponseTemplate::new(200).set_body_json(upload_response(returned_cid)))
+            .expect(1)
+            .mount(&destination)
+            .await;
+        (origin, destination)
+    }
+
+    #[test]
+    fn test_stream_blob_pipes_origin_into_destination() {
+        tokio_test::block_on(async {
+            let cid = blob_cid(BLOB).to_string();
+            let (origin, destination) = servers(BLOB, &cid).await;
+            let check = stream_blob(
+                &origin.uri(),
+                &request(&cid),
+                &destination.uri(),
+                "destination-token",
+                &options(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(check, Some(BlobCheck::Verified));
+        });
+    }
+
+    #[test]
+    fn test_stream_blob_reports_corrupt_origin_bytes() {
+        tokio_test::block_on(async {
+            let cid = blob_cid(BLOB).to_string();
+            let (origin, destination) = servers(b"truncated", &cid).await;
+            let check = stream_blob(
+                &origin.uri(),
+                &request(&cid),
+                &destination.uri(),
+                "destination-token",
+                &options(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(check, Some(BlobCheck::Corrupt));
+        });
+    }
+
+    #[test]
+    fn test_stream_blob_streams_retried_upload() {
+        tokio_test::block_on(async {
+            let cid = blob_cid(BLOB).to_string();
+            // Served without a length, so only a streamed upload goes out chunked
+            let origin = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/xrpc/com.atproto.sync.getBlob"))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .insert_header("content-type", "image/png")
+                        .insert_header("transfer-encoding", "chunked")
+                        .set_body_bytes(BLOB),
+                )
+                .expect(2)
+                .mount(&origin)
+                .await;
+            let destination = MockServer::start().await;
+            Mock::given(method("POST"))
+                .and(path("/xrpc/com.atproto.repo.uploadBlob"))
+                .respond_with(ResponseTemplate::new(503))
+                .up_to_n_times(1)
+                .with_priority(1)
+                .expect(1)
+                .mount(&destination)
+                .await;
+            Mock::given(method("POST"))
+                .and(path("/xrpc/com.atproto.repo.uploadBlob"))
+                .and(header("transfer-encoding", "chunked"))
+                .and(body_bytes(BLOB))
+                .respond_with(ResponseTemplate::new(200).set_body_json(upload_response(&cid)))
+                .expect(1)
+                .mount(&destination)
+                .await;
+            let check = stream_blob(
+                &origin.uri(),
+                &request(&cid),
+                &destination.uri(),
+                "destination-token",
+                &options(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(check, Some(BlobCheck::Verified));
+        });
+    }
+
+    #[test]
+    fn test_transfer_blobs_request_redacts_tokens() {
+        let request = TransferBlobsRequest {
+            origin: "https://origin.example.com".to_string(),
+            destination: "https://destination.example.com".to_string(),
+            did: "did:plc:example123".to_string(),
+            origin_token: "secret-origin-token".to_string(),
+            destination_token: "secret-destination-token".to_string(),
+            origin_refresh_token: None,
+            destination_refresh_token: Some("secret-refresh-token".to_string()),
+        };
+        let debug_output = format!("{:?}", request);
+        assert!(!debug_output.contains("secret-origin-token"));
+        assert!(!debug_output.contains("secret-destination-token"));
+        assert!(!debug_output.contains("secret-refresh-token"));
+        assert!(debug_output.contains("did:plc:example123"));
+    }
+}
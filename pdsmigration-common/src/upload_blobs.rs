@@ -1,54 +1,78 @@
-use crate::agent::{login_helper, upload_blob};
-use crate::{build_agent, MigrationError};
+use crate::agent::resume_session;
+use crate::{
+    blob_dir, build_agent, run_all_blob_transfers, upload_staged_blob, MigrationError,
+    MigrationOptions, StagingStore,
+};
 use bsky_sdk::api::agent::Configure;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Deserialize, Serialize)]
 pub struct UploadBlobsRequest {
     pub pds_host: String,
     pub did: String,
     pub token: String,
+    /// Lets the session outlive its access token on long uploads.
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+}
+
+impl std::fmt::Debug for UploadBlobsRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UploadBlobsRequest")
+            .field("pds_host", &self.pds_host)
+            .field("did", &self.did)
+            .field("token", &"[REDACTED]")
+            .field(
+                "refresh_token",
+                &self.refresh_token.as_ref().map(|_| "[REDACTED]"),
+            )
+            .finish()
+    }
 }
 
-#[tracing::instrument]
-pub async fn upload_blobs_api(req: UploadBlobsRequest) -> Result<(), MigrationError> {
-    let agent = build_agent().await?;
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UploadBlobsResponse {
+    pub successful_blobs: Vec<String>,
+    pub invalid_blobs: Vec<String>,
+    pub corrupt_blobs: Vec<String>,
+}
+
+#[tracing::instrument(skip(store, options))]
+pub async fn upload_blobs_api(
+    req: UploadBlobsRequest,
+    store: &dyn StagingStore,
+    options: &MigrationOptions,
+) -> Result<UploadBlobsResponse, MigrationError> {
+    let agent = build_agent(&options.retry).await?;
     agent.configure_endpoint(req.pds_host.clone());
-    let session = login_helper(
+    let session = resume_session(
         &agent,
         req.pds_host.as_str(),
         req.did.as_str(),
         req.token.as_str(),
+        req.refresh_token.as_deref(),
     )
     .await?;
 
-    let mut blob_dir;
-    let mut path = std::env::current_dir().unwrap();
-    path.push(session.did.as_str().replace(":", "-"));
-    match tokio::fs::read_dir(path.as_path()).await {
-        Ok(output) => blob_dir = output,
-        Err(error) => {
-            tracing::error!("{}", error.to_string());
-            return Err(MigrationError::Runtime {
-                message: "Failed to read blob directory".to_string(),
-            });
-        }
-    }
-
-    while let Some(blob) = blob_dir.next_entry().await.map_err(|error| {
-        tracing::error!("{}", error.to_string());
-        MigrationError::Runtime {
-            message: "Failed to get next blob".to_string(),
-        }
-    })? {
-        let file = tokio::fs::read(blob.path()).await.map_err(|error| {
-            tracing::error!("{}", error.to_string());
-            MigrationError::Runtime {
-                message: "Failed to read next blob".to_string(),
-            }
+    let blobs = store
+        .list(&blob_dir(session.did.as_str()))
+        .await
+        .inspect_err(|error| {
+            tracing::error!("Failed to read blob directory: {}", error);
         })?;
-        upload_blob(&agent, file).await?;
-    }
+    // Staged blobs are keyed by the CID they were requested by
+    let cids = blobs
+        .iter()
+        .map(|blob| blob.rsplit('/').next().unwrap_or(blob).to_string())
+        .collect();
+    let report = run_all_blob_transfers(cids, options, |cid| {
+        upload_staged_blob(&agent, store, session.did.as_str(), cid, options)
+    })
+    .await?;
 
-    Ok(())
+    Ok(UploadBlobsResponse {
+        successful_blobs: report.successful_blobs,
+        invalid_blobs: report.invalid_blobs,
+        corrupt_blobs: report.corrupt_blobs,
+    })
 }
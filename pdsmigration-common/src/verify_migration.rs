@@ -0,0 +1,398 @@
+use crate::agent::{export_preferences, login_helper, recommended_plc};
+use crate::{
+    build_agent, parse_did, IdentityResolver, MigrationAgent, MigrationError, MigrationOptions,
+};
+use bsky_sdk::api::com::atproto::server::check_account_status::OutputData as AccountStatus;
+use bsky_sdk::api::did_doc::DidDocument;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize)]
+pub struct VerifyMigrationRequest {
+    pub origin: String,
+    pub destination: String,
+    pub did: String,
+    pub origin_token: String,
+    pub destination_token: String,
+}
+
+impl std::fmt::Debug for VerifyMigrationRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VerifyMigrationRequest")
+            .field("origin", &self.origin)
+            .field("destination", &self.destination)
+            .field("did", &self.did)
+            .field("origin_token", &"[REDACTED]")
+            .field("destination_token", &"[REDACTED]")
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationCheck {
+    RepoRev,
+    Records,
+    Blobs,
+    PrivateState,
+    Preferences,
+    PdsEndpoint,
+    SigningKey,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct VerificationResult {
+    pub check: VerificationCheck,
+    pub passed: bool,
+    pub message: String,
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct VerificationReport {
+    pub results: Vec<VerificationResult>,
+}
+
+impl VerificationReport {
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|result| result.passed)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &VerificationResult> {
+        self.results.iter().filter(|result| !result.passed)
+    }
+
+    fn record(&mut self, check: VerificationCheck, passed: bool, message: String) {
+        self.results.push(VerificationResult {
+            check,
+            passed,
+            message,
+        });
+    }
+}
+
+/// Compares the account on both PDSes so the origin is only deactivated once the destination
+/// holds everything: the latest commit, every record, blob and preference, and a DID document
+/// that points at it. The DID document is the one `resolver` finds published, not the
+/// destination's copy, which may be ahead of the PLC directory or `did.json`.
+#[tracing::instrument(skip(resolver, options))]
+pub async fn verify_migration_api(
+    req: VerifyMigrationRequest,
+    resolver: &IdentityResolver,
+    options: &MigrationOptions,
+) -> Result<VerificationReport, MigrationError> {
+    let origin_agent = build_agent(&options.retry).await?;
+    login_helper(
+        &origin_agent,
+        req.origin.as_str(),
+        req.did.as_str(),
+        req.origin_token.as_str(),
+    )
+    .await?;
+    let destination_agent = build_agent(&options.retry).await?;
+    login_helper(
+        &destination_agent,
+        req.destination.as_str(),
+        req.did.as_str(),
+        req.destination_token.as_str(),
+    )
+    .await?;
+
+    let origin_status = account_status(&origin_agent).await?;
+    let destination_status = account_status(&destination_agent).await?;
+    let mut report = compare_account_status(&origin_status, &destination_status);
+
+    let origin_preferences = export_preferences(&origin_agent).await?;
+    let destination_preferences = export_preferences(&destination_agent).await?;
+    // Compared as JSON, which is how both PDSes store them
+    let preferences_match = serde_json::to_value(&origin_preferences).ok()
+        == serde_json::to_value(&destination_preferences).ok();
+    report.record(
+        VerificationCheck::Preferences,
+        preferences_match,
+        format!(
+            "{} preferences on the origin, {} on the destination",
+            origin_preferences.len(),
+            destination_preferences.len()
+        ),
+    );
+
+    let did_doc = resolver.resolve_did(&parse_did(&req.did)?).await?;
+    let recommended = recommended_plc(&destination_agent).await?;
+    let signing_key = recommended
+        .verification_methods
+        .as_ref()
+        .and_then(|methods| serde_json::to_value(methods).ok())
+        .and_then(|methods| methods.get("atproto")?.as_str().map(str::to_string));
+    compare_did_doc(
+        &mut report,
+        &did_doc,
+        &req.destination,
+        signing_key.as_deref(),
+    );
+
+    for failure in report.failures() {
+        tracing::error!("Verification failed: {:?}", failure);
+    }
+    Ok(report)
+}
+
+async fn account_status(agent: &MigrationAgent) -> Result<AccountStatus, MigrationError> {
+    let host = agent.get_endpoint().await;
+    agent
+        .api
+        .com
+        .atproto
+        .server
+        .check_account_status()
+        .await
+        .map(|output| output.data)
+        .map_err(|error| MigrationError::from_atrium(&host, error))
+}
+
+/// The destination may be ahead of the origin once the account is in use there, never behind.
+fn compare_account_status(
+    origin: &AccountStatus,
+    destination: &AccountStatus,
+) -> VerificationReport {
+    let mut report = VerificationReport::default();
+    // Revs are TIDs, which sort lexically in creation order
+    report.record(
+        VerificationCheck::RepoRev,
+        destination.repo_rev >= origin.repo_rev,
+        format!(
+            "Origin is at rev {}, destination at {}",
+            origin.repo_rev, destination.repo_rev
+        ),
+    );
+    report.record(
+        VerificationCheck::Records,
+        destination.indexed_records >= origin.indexed_records,
+        format!(
+            "{} records on the origin, {} on the destination",
+            origin.indexed_records, destination.indexed_records
+        ),
+    );
+    report.record(
+        VerificationCheck::Blobs,
+        destination.imported_blobs >= destination.expected_blobs
+            && destination.expected_blobs >= origin.expected_blobs,
+        format!(
+            "{} of {} blobs imported; the origin references {}",
+            destination.imported_blobs, destination.expected_blobs, origin.expected_blobs
+        ),
+    );
+    report.record(
+        VerificationCheck::PrivateState,
+        destination.private_state_values >= origin.private_state_values,
+        format!(
+            "{} private state values on the origin, {} on the destination",
+            origin.private_state_values, destination.private_state_values
+        ),
+    );
+    report
+}
+
+fn compare_did_doc(
+    report: &mut VerificationReport,
+    did_doc: &DidDocument,
+    destination: &str,
+    signing_key: Option<&str>,
+) {
+    let endpoint = did_doc.get_pds_endpoint();
+    report.record(
+        VerificationCheck::PdsEndpoint,
+        endpoint
+            .as_deref()
+            .map(|endpoint| endpoint.trim_end_matches('/'))
+            == Some(destination.trim_end_matches('/')),
+        format!(
+            "DID document points at {}",
+            endpoint.as_deref().unwrap_or("no PDS")
+        ),
+    );
+    let key = did_doc
+        .get_signing_key()
+        .and_then(|method| method.public_key_multibase.as_ref())
+        .map(|key| format!("did:key:{key}"));
+    report.record(
+        VerificationCheck::SigningKey,
+        key.is_some() && key.as_deref() == signing_key,
+        format!(
+            "DID document signing key is {}, the destination signs with {}",
+            key.as_deref().unwrap_or("missing"),
+            signing_key.unwrap_or("an unknown key")
+        ),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RetryPolicy;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn status(rev: &str, records: i64, expected_blobs: i64, imported_blobs: i64) -> AccountStatus {
+        AccountStatus {
+            activated: true,
+            expected_blobs,
+            imported_blobs,
+            indexed_records: records,
+            private_state_values: 1,
+            repo_blocks: 10,
+            repo_commit: "bafyreie5737gdxlw5i64vzichcalba3z2v5n6icifvx5xytvske7mr3hpm"
+                .parse()
+                .unwrap(),
+            repo_rev: rev.to_string(),
+            valid_did: true,
+        }
+    }
+
+    fn did_doc(endpoint: &str) -> DidDocument {
+        serde_json::from_value(serde_json::json!({
+            "id": "did:plc:example123",
+            "verificationMethod": [{
+                "id": "did:plc:example123#atproto",
+                "type": "Multikey",
+                "controller": "did:plc:example123",
+                "publicKeyMultibase": "zQ3shokFTS3brHcDQrn82RUDfCZESWL1ZdCEJwekUDPQiYBme",
+            }],
+            "service": [{
+                "id": "#atproto_pds",
+                "type": "AtprotoPersonalDataServer",
+                "serviceEndpoint": endpoint,
+            }],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_missing_blobs_and_stale_rev_fail() {
+        let report = compare_account_status(&status("3kbbb", 10, 4, 4), &status("3kaaa", 10, 4, 3));
+        let failed: Vec<_> = report.failures().map(|result| result.check).collect();
+        assert_eq!(
+            failed,
+            vec![VerificationCheck::RepoRev, VerificationCheck::Blobs]
+        );
+
+        let report = compare_account_status(&status("3kaaa", 10, 4, 4), &status("3kbbb", 12, 4, 4));
+        assert!(report.passed());
+    }
+
+    #[test]
+    fn test_did_doc_must_point_at_destination() {
+        let key = "did:key:zQ3shokFTS3brHcDQrn82RUDfCZESWL1ZdCEJwekUDPQiYBme";
+        let mut report = VerificationReport::default();
+        compare_did_doc(
+            &mut report,
+            &did_doc("https://destination.example.com/"),
+            "https://destination.example.com",
+            Some(key),
+        );
+        assert!(report.passed());
+
+        let mut report = VerificationReport::default();
+        compare_did_doc(
+            &mut report,
+            &did_doc("https://origin.example.com"),
+            "https://destination.example.com",
+            Some("did:key:zQ3shsomeotherkey"),
+        );
+        let failed: Vec<_> = report.failures().map(|result| result.check).collect();
+        assert_eq!(
+            failed,
+            vec![
+                VerificationCheck::PdsEndpoint,
+                VerificationCheck::SigningKey
+            ]
+        );
+    }
+
+    #[test]
+    fn test_did_doc_is_read_from_the_plc_directory() {
+        tokio_test::block_on(async {
+            let origin = MockServer::start().await;
+            let destination = MockServer::start().await;
+            let plc_directory = MockServer::start().await;
+            for server in [&origin, &destination] {
+                Mock::given(method("GET"))
+                    .and(path("/xrpc/com.atproto.server.checkAccountStatus"))
+                    .respond_with(
+                        ResponseTemplate::new(200).set_body_json(
+                            serde_json::to_value(status("3kaaa", 10, 4, 4)).unwrap(),
+                        ),
+                    )
+                    .mount(server)
+                    .await;
+                Mock::given(method("GET"))
+                    .and(path("/xrpc/app.bsky.actor.getPreferences"))
+                    .respond_with(
+                        ResponseTemplate::new(200)
+                            .set_body_json(serde_json::json!({ "preferences": [] })),
+                    )
+                    .mount(server)
+                    .await;
+            }
+            Mock::given(method("GET"))
+                .and(path("/xrpc/com.atproto.server.getSession"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(
+                    serde_json::json!({ "did": "did:plc:example123", "handle": "user.example.com" }),
+                ))
+                .mount(&origin)
+                .await;
+            // The destination already claims the new document before the PLC operation lands
+            Mock::given(method("GET"))
+                .and(path("/xrpc/com.atproto.server.getSession"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "did": "did:plc:example123",
+                    "handle": "user.example.com",
+                    "didDoc": serde_json::to_value(did_doc(&destination.uri())).unwrap(),
+                })))
+                .mount(&destination)
+                .await;
+            Mock::given(method("GET"))
+                .and(path(
+                    "/xrpc/com.atproto.identity.getRecommendedDidCredentials",
+                ))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "verificationMethods": {
+                        "atproto": "did:key:zQ3shokFTS3brHcDQrn82RUDfCZESWL1ZdCEJwekUDPQiYBme",
+                    },
+                })))
+                .mount(&destination)
+                .await;
+            Mock::given(method("GET"))
+                .and(path("/did:plc:example123"))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_json(serde_json::to_value(did_doc(&origin.uri())).unwrap()),
+                )
+                .mount(&plc_directory)
+                .await;
+            let resolver = IdentityResolver {
+                plc_directory: plc_directory.uri(),
+                retry: RetryPolicy::none(),
+                ..IdentityResolver::default()
+            };
+            let options = MigrationOptions {
+                retry: RetryPolicy::none(),
+                ..MigrationOptions::default()
+            };
+
+            let report = verify_migration_api(
+                VerifyMigrationRequest {
+                    origin: origin.uri(),
+                    destination: destination.uri(),
+                    did: "did:plc:example123".to_string(),
+                    origin_token: "origin-token".to_string(),
+                    destination_token: "destination-token".to_string(),
+                },
+                &resolver,
+                &options,
+            )
+            .await
+            .unwrap();
+            let failed: Vec<_> = report.failures().map(|result| result.check).collect();
+            assert_eq!(failed, vec![VerificationCheck::PdsEndpoint]);
+        });
+    }
+}
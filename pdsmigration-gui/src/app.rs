@@ -263,6 +263,10 @@ impl PdsMigrationApp {
                 self.error.clone(),
                 self.page.clone(),
             )),
+            ScreenType::RecoverPLC => Box::new(screens::recover_plc::RecoverPLC::new(
+                self.pds_session.clone(),
+                self.error.clone(),
+            )),
         };
 
         // Reassign the current_screen
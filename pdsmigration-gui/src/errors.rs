@@ -1,3 +1,4 @@
+use pdsmigration_common::{MigrationError, XrpcFailure};
 use std::fmt::Display;
 
 #[derive(Debug, Clone)]
@@ -9,6 +10,14 @@ pub enum GuiError {
     Other,
     Success,
     AuthFactorTokenRequired,
+    Canceled,
+    HandleUnavailable(String),
+    InvalidInviteCode(String),
+    AccountAlreadyExists,
+    ExpiredToken,
+    NotFound(String),
+    Upstream(String),
+    VerificationFailed(Vec<String>),
 }
 
 impl Display for GuiError {
@@ -27,6 +36,93 @@ impl Display for GuiError {
             Self::AuthFactorTokenRequired => {
                 __derive_more_f.write_fmt(format_args!("Auth Factor Token Required",))
             }
+            Self::Canceled => __derive_more_f.write_fmt(format_args!("Canceled",)),
+            Self::HandleUnavailable(message) => {
+                __derive_more_f.write_fmt(format_args!("Handle Unavailable: {message}",))
+            }
+            Self::InvalidInviteCode(message) => {
+                __derive_more_f.write_fmt(format_args!("Invalid Invite Code: {message}",))
+            }
+            Self::AccountAlreadyExists => {
+                __derive_more_f.write_fmt(format_args!("Account Already Exists on the New PDS",))
+            }
+            Self::ExpiredToken => {
+                __derive_more_f.write_fmt(format_args!("Session Expired, Please Log In Again",))
+            }
+            Self::NotFound(message) => {
+                __derive_more_f.write_fmt(format_args!("Not Found: {message}",))
+            }
+            Self::Upstream(message) => {
+                __derive_more_f.write_fmt(format_args!("PDS Error: {message}",))
+            }
+            Self::VerificationFailed(failed) => __derive_more_f.write_fmt(format_args!(
+                "New Account Is Not Complete Yet: {}",
+                failed.join("; ")
+            )),
         }
     }
 }
+
+impl From<MigrationError> for GuiError {
+    fn from(error: MigrationError) -> Self {
+        match error {
+            MigrationError::InvalidCredentials | MigrationError::AccountTakedown { .. } => {
+                Self::InvalidLogin
+            }
+            MigrationError::AuthFactorTokenRequired => Self::AuthFactorTokenRequired,
+            MigrationError::Canceled { .. } => Self::Canceled,
+            MigrationError::HandleUnavailable { upstream } => {
+                Self::HandleUnavailable(upstream_message(upstream))
+            }
+            MigrationError::InvalidInviteCode { upstream } => {
+                Self::InvalidInviteCode(upstream_message(upstream))
+            }
+            MigrationError::AccountAlreadyExists { .. } => Self::AccountAlreadyExists,
+            MigrationError::ExpiredToken { .. } => Self::ExpiredToken,
+            MigrationError::NotFound { upstream } => Self::NotFound(upstream_message(upstream)),
+            MigrationError::Xrpc { upstream } => Self::Upstream(upstream_message(upstream)),
+            MigrationError::VerificationFailed { failed } => Self::VerificationFailed(failed),
+            _ => Self::Runtime,
+        }
+    }
+}
+
+/// The PDS's own wording, which is what the user can act on; host and status go to the log.
+fn upstream_message(upstream: XrpcFailure) -> String {
+    if upstream.message.is_empty() {
+        upstream.error
+    } else {
+        upstream.message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upstream_errors_keep_the_pds_message() {
+        let error = GuiError::from(MigrationError::HandleUnavailable {
+            upstream: XrpcFailure {
+                host: "https://pds.example.com".to_string(),
+                status: 400,
+                error: "HandleNotAvailable".to_string(),
+                message: "Handle already taken".to_string(),
+            },
+        });
+        assert_eq!(
+            error.to_string(),
+            "Handle Unavailable: Handle already taken"
+        );
+
+        let error = GuiError::from(MigrationError::Xrpc {
+            upstream: XrpcFailure {
+                host: "https://pds.example.com".to_string(),
+                status: 500,
+                error: "InternalServerError".to_string(),
+                message: String::new(),
+            },
+        });
+        assert_eq!(error.to_string(), "PDS Error: InternalServerError");
+    }
+}
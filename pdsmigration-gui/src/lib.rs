@@ -1,30 +1,31 @@
 use crate::agent::login_helper2;
 use crate::app::PdsMigrationApp;
 use crate::errors::GuiError;
-use crate::ipld::cid_for_cbor;
+use crate::progress::TransferProgress;
 use crate::session::session_config::{PdsSession, SessionConfig};
 use base64ct::{Base64, Encoding};
 use bsky_sdk::api::agent::Configure;
-use bsky_sdk::api::types::string::Did;
-use bsky_sdk::BskyAgent;
+use bsky_sdk::api::types::string::{Datetime, Did};
 use hex::ToHex;
-use indexmap::IndexMap;
 use multibase::Base::Base58Btc;
 use pdsmigration_common::{
-    CreateAccountRequest, DeactivateAccountRequest, ExportAllBlobsRequest, ExportBlobsRequest,
-    ExportPDSRequest, ImportPDSRequest, MigratePlcRequest, MigratePreferencesRequest,
-    MigrationError, PlcOperation, RequestTokenRequest, ServiceAuthRequest, UploadBlobsRequest,
+    build_agent, recover_plc_with_rotation_key, recoverable_operations, verify_plc_audit_log,
+    CatchUpRequest, CreateAccountRequest, DeactivateAccountRequest, ExportAllBlobsRequest,
+    ExportBlobsRequest, ExportPDSRequest, IdentityResolver, ImportPDSRequest, LocalStagingStore,
+    MigratePlcRequest, MigratePreferencesRequest, MigrationError, MigrationOptions, PlcDirectory,
+    PlcLogAuditEntry, PlcOperationDiff, PreflightReport, PreflightRequest, PreviewPlcRequest,
+    RefreshedSession, RequestTokenRequest, RetryPolicy, ServiceAuthRequest, UploadBlobsRequest,
 };
 use rand::distr::Alphanumeric;
 use rand::Rng;
 use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 use sha2::{Digest, Sha256};
 use std::io::Write;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::SystemTime;
+use tokio::sync::RwLock;
 use zip::write::SimpleFileOptions;
 use zip::{AesMode, ZipWriter};
 
@@ -34,6 +35,7 @@ pub mod error_window;
 pub mod errors;
 pub mod ipld;
 pub mod log_viewer;
+pub mod progress;
 pub mod screens;
 pub mod session;
 pub mod styles;
@@ -54,6 +56,7 @@ pub enum ScreenType {
     CreateOrLoginAccount,
     ExportRepo,
     ImportRepo,
+    RecoverPLC,
 }
 
 #[tracing::instrument(skip(session_config))]
@@ -63,8 +66,13 @@ pub async fn activate_account(session_config: SessionConfig) -> Result<(), GuiEr
     let did = session_config.did().to_string();
 
     tracing::info!("Activating Account started");
-    match pdsmigration_common::activate_account(pds_host.as_str(), token.as_str(), did.as_str())
-        .await
+    match pdsmigration_common::activate_account(
+        pds_host.as_str(),
+        token.as_str(),
+        did.as_str(),
+        &MigrationOptions::default(),
+    )
+    .await
     {
         Ok(_) => {
             tracing::info!("Activating Account completed");
@@ -72,31 +80,43 @@ pub async fn activate_account(session_config: SessionConfig) -> Result<(), GuiEr
         }
         Err(pds_error) => {
             tracing::error!("Error activating account: {pds_error}");
-            Err(GuiError::Runtime)
+            Err(GuiError::from(pds_error))
         }
     }
 }
 
-#[tracing::instrument(skip(session_config))]
-pub async fn deactivate_account(session_config: SessionConfig) -> Result<(), GuiError> {
-    let pds_host = session_config.host().to_string();
-    let token = session_config.access_token().to_string();
-    let did = session_config.did().to_string();
+#[tracing::instrument(skip(old_session_config, new_session_config))]
+pub async fn deactivate_account(
+    old_session_config: SessionConfig,
+    new_session_config: SessionConfig,
+) -> Result<(), GuiError> {
+    let pds_host = old_session_config.host().to_string();
+    let token = old_session_config.access_token().to_string();
+    let did = old_session_config.did().to_string();
 
     tracing::info!("Deactivating Account started");
     let request = DeactivateAccountRequest {
         pds_host,
         did,
         token,
+        destination: Some(new_session_config.host().to_string()),
+        destination_token: Some(new_session_config.access_token().to_string()),
+        skip_verification: false,
     };
-    match pdsmigration_common::deactivate_account_api(request).await {
+    match pdsmigration_common::deactivate_account_api(
+        request,
+        &IdentityResolver::default(),
+        &MigrationOptions::default(),
+    )
+    .await
+    {
         Ok(_) => {
             tracing::info!("Deactivating Account completed");
             Ok(())
         }
         Err(pds_error) => {
             tracing::error!("Error deactivating account: {pds_error}");
-            Err(GuiError::Runtime)
+            Err(GuiError::from(pds_error))
         }
     }
 }
@@ -219,14 +239,14 @@ pub async fn request_token(session_config: SessionConfig) -> Result<(), GuiError
         did,
         token,
     };
-    match pdsmigration_common::request_token_api(request).await {
+    match pdsmigration_common::request_token_api(request, &MigrationOptions::default()).await {
         Ok(_) => {
             tracing::info!("Requesting Token completed");
             Ok(())
         }
         Err(pds_error) => {
             tracing::error!("Error requesting token: {pds_error}");
-            Err(GuiError::Runtime)
+            Err(GuiError::from(pds_error))
         }
     }
 }
@@ -267,14 +287,15 @@ pub async fn migrate_preferences(pds_session: PdsSession) -> Result<(), GuiError
         did,
         origin_token,
     };
-    match pdsmigration_common::migrate_preferences_api(request).await {
+    match pdsmigration_common::migrate_preferences_api(request, &MigrationOptions::default()).await
+    {
         Ok(_) => {
             tracing::info!("Migrating Preferences completed");
             Ok(())
         }
         Err(pds_error) => {
             tracing::error!("Error migrating Preferences: {pds_error}");
-            Err(GuiError::Runtime)
+            Err(GuiError::from(pds_error))
         }
     }
 }
@@ -294,6 +315,54 @@ pub fn multicodec_wrap(bytes: Vec<u8>) -> Vec<u8> {
     v
 }
 
+/// What migrating the PLC would change, for the user to confirm before anything is signed.
+#[tracing::instrument(skip(pds_session))]
+pub async fn preview_plc_via_pds(
+    pds_session: PdsSession,
+    user_recovery_key: Option<String>,
+) -> Result<PlcOperationDiff, GuiError> {
+    let did = match pds_session.did().clone() {
+        None => {
+            tracing::error!("No DID found");
+            return Err(GuiError::Other);
+        }
+        Some(did) => did.to_string(),
+    };
+    let (old_session_config, new_session_config) = match (
+        pds_session.old_session_config(),
+        pds_session.new_session_config(),
+    ) {
+        (Some(old_session_config), Some(new_session_config)) => {
+            (old_session_config, new_session_config)
+        }
+        _ => {
+            tracing::error!("No session config found");
+            return Err(GuiError::Other);
+        }
+    };
+    let request = PreviewPlcRequest {
+        destination: new_session_config.host().to_string(),
+        destination_token: new_session_config.access_token().to_string(),
+        origin: old_session_config.host().to_string(),
+        did,
+        origin_token: old_session_config.access_token().to_string(),
+        user_recovery_key,
+    };
+    match pdsmigration_common::preview_plc_api(
+        request,
+        &PlcDirectory::default(),
+        &MigrationOptions::default(),
+    )
+    .await
+    {
+        Ok(preview) => Ok(preview.diff),
+        Err(pds_error) => {
+            tracing::error!("Error previewing PLC: {pds_error}");
+            Err(GuiError::from(pds_error))
+        }
+    }
+}
+
 #[tracing::instrument(skip(pds_session))]
 pub async fn migrate_plc_via_pds(
     pds_session: PdsSession,
@@ -326,6 +395,32 @@ pub async fn migrate_plc_via_pds(
     let origin_token = old_session_config.access_token().to_string();
     let destination_token = new_session_config.access_token().to_string();
 
+    // Copy anything posted since the repo import before the DID points at the new PDS
+    let catch_up = CatchUpRequest {
+        origin: origin.clone(),
+        destination: destination.clone(),
+        did: did.clone(),
+        origin_token: origin_token.clone(),
+        destination_token: destination_token.clone(),
+        since: None,
+    };
+    match pdsmigration_common::catch_up_api(
+        catch_up,
+        &staging_store()?,
+        &MigrationOptions::default(),
+    )
+    .await
+    {
+        Ok(_) => tracing::info!("Repo catch-up completed"),
+        Err(MigrationError::Validation { field }) if field == "since" => {
+            tracing::warn!("No imported repo rev recorded, skipping catch-up");
+        }
+        Err(pds_error) => {
+            tracing::error!("Error catching up repo: {:?}", pds_error);
+            return Err(GuiError::from(pds_error));
+        }
+    }
+
     tracing::info!("Migrating PLC started");
     let request = MigratePlcRequest {
         destination,
@@ -336,20 +431,67 @@ pub async fn migrate_plc_via_pds(
         plc_signing_token,
         user_recovery_key,
     };
-    match pdsmigration_common::migrate_plc_api(request).await {
+    match pdsmigration_common::migrate_plc_api(request, &MigrationOptions::default()).await {
         Ok(_) => {
             tracing::info!("Migrating PLC completed");
             Ok(())
         }
-        Err(_pds_error) => {
-            tracing::error!("Error migrating PLC: {_pds_error}");
-            Err(GuiError::Runtime)
+        Err(pds_error) => {
+            tracing::error!("Error migrating PLC: {pds_error}");
+            Err(GuiError::from(pds_error))
         }
     }
 }
 
-#[tracing::instrument(skip(pds_session))]
-pub async fn upload_blobs(pds_session: PdsSession) -> Result<(), GuiError> {
+/// Operations of `did` a higher priority rotation key can still nullify, newest last.
+#[tracing::instrument]
+pub async fn recoverable_plc_operations(did: String) -> Result<Vec<PlcLogAuditEntry>, GuiError> {
+    let audit_log = match PlcDirectory::default().audit_log(did.as_str()).await {
+        Ok(audit_log) => audit_log,
+        Err(pds_error) => {
+            tracing::error!("Error fetching PLC audit log: {pds_error}");
+            return Err(GuiError::from(pds_error));
+        }
+    };
+    if let Err(audit_error) = verify_plc_audit_log(did.as_str(), &audit_log) {
+        tracing::error!("PLC audit log failed verification: {audit_error}");
+        return Err(GuiError::from(MigrationError::from(audit_error)));
+    }
+    Ok(recoverable_operations(&audit_log, &Datetime::now())
+        .into_iter()
+        .cloned()
+        .collect())
+}
+
+#[tracing::instrument(skip(rotation_key))]
+pub async fn recover_plc(did: String, rotation_key: String, cid: String) -> Result<(), GuiError> {
+    tracing::info!("PLC recovery started");
+    match recover_plc_with_rotation_key(
+        &PlcDirectory::default(),
+        did.as_str(),
+        rotation_key.as_str(),
+        cid.as_str(),
+        |operation| operation,
+    )
+    .await
+    {
+        Ok(_) => {
+            tracing::info!("PLC recovery completed");
+            Ok(())
+        }
+        Err(pds_error) => {
+            tracing::error!("Error recovering PLC: {pds_error}");
+            Err(GuiError::from(pds_error))
+        }
+    }
+}
+
+#[tracing::instrument(skip(pds_session_lock, progress))]
+pub async fn upload_blobs(
+    pds_session_lock: Arc<RwLock<PdsSession>>,
+    progress: Arc<TransferProgress>,
+) -> Result<(), GuiError> {
+    let pds_session = pds_session_lock.read().await.clone();
     let did = match pds_session.did().clone() {
         None => {
             return Err(GuiError::Other);
@@ -364,27 +506,43 @@ pub async fn upload_blobs(pds_session: PdsSession) -> Result<(), GuiError> {
     };
     let pds_host = new_session_config.host().to_string();
     let token = new_session_config.access_token().to_string();
+    let refresh_token = new_session_config.optional_refresh_token();
 
     tracing::info!("Uploading Blobs started");
     let request = UploadBlobsRequest {
         pds_host,
         did,
         token,
+        refresh_token,
     };
-    match pdsmigration_common::upload_blobs_api(request).await {
+    match pdsmigration_common::upload_blobs_api(
+        request,
+        &staging_store()?,
+        &session_migration_options(pds_session_lock, progress),
+    )
+    .await
+    {
         Ok(_) => {
             tracing::info!("Uploading Blobs completed");
             Ok(())
         }
-        Err(_pds_error) => {
-            tracing::error!("Error uploading blobs: {_pds_error}");
-            Err(GuiError::Runtime)
+        Err(MigrationError::Canceled { finished }) => {
+            tracing::info!("Uploading Blobs canceled after {} blobs", finished.len());
+            Err(GuiError::Canceled)
+        }
+        Err(pds_error) => {
+            tracing::error!("Error uploading blobs: {pds_error}");
+            Err(GuiError::from(pds_error))
         }
     }
 }
 
-#[tracing::instrument(skip(pds_session))]
-pub async fn export_all_blobs(pds_session: PdsSession) -> Result<(), GuiError> {
+#[tracing::instrument(skip(pds_session_lock, progress))]
+pub async fn export_all_blobs(
+    pds_session_lock: Arc<RwLock<PdsSession>>,
+    progress: Arc<TransferProgress>,
+) -> Result<(), GuiError> {
+    let pds_session = pds_session_lock.read().await.clone();
     let did = match pds_session.did().clone() {
         None => {
             tracing::error!("No DID found");
@@ -401,14 +559,22 @@ pub async fn export_all_blobs(pds_session: PdsSession) -> Result<(), GuiError> {
     };
     let old_pds_host = old_session_config.host().to_string();
     let old_token = old_session_config.access_token().to_string();
+    let old_refresh_token = old_session_config.optional_refresh_token();
 
     tracing::info!("Exporting All Blobs started");
     let request = ExportAllBlobsRequest {
         origin: old_pds_host,
         did,
         origin_token: old_token,
+        origin_refresh_token: old_refresh_token,
     };
-    match pdsmigration_common::export_all_blobs_api(request).await {
+    match pdsmigration_common::export_all_blobs_api(
+        request,
+        &staging_store()?,
+        &session_migration_options(pds_session_lock, progress),
+    )
+    .await
+    {
         Ok(_) => {
             tracing::info!("Exporting All Blobs completed");
             Ok(())
@@ -421,17 +587,28 @@ pub async fn export_all_blobs(pds_session: PdsSession) -> Result<(), GuiError> {
                 );
                 Err(GuiError::Other)
             }
+            MigrationError::Canceled { finished } => {
+                tracing::info!(
+                    "Exporting All Blobs canceled after {} blobs",
+                    finished.len()
+                );
+                Err(GuiError::Canceled)
+            }
             _ => {
                 tracing::error!("Error exporting all blobs: {:?}", pds_error);
-                Err(GuiError::Runtime)
+                Err(GuiError::from(pds_error))
             }
         },
     }
 }
 
-#[tracing::instrument(skip(pds_session))]
-pub async fn export_missing_blobs(pds_session: PdsSession) -> Result<(), GuiError> {
+#[tracing::instrument(skip(pds_session_lock, progress))]
+pub async fn export_missing_blobs(
+    pds_session_lock: Arc<RwLock<PdsSession>>,
+    progress: Arc<TransferProgress>,
+) -> Result<(), GuiError> {
     tracing::info!("Lib: Exporting Missing Blobs started");
+    let pds_session = pds_session_lock.read().await.clone();
     let did = match pds_session.did().clone() {
         None => {
             tracing::error!("No DID found");
@@ -457,6 +634,7 @@ pub async fn export_missing_blobs(pds_session: PdsSession) -> Result<(), GuiErro
     let new_pds_host = new_session_config.host().to_string();
     let old_token = old_session_config.access_token().to_string();
     let new_token = new_session_config.access_token().to_string();
+    let old_refresh_token = old_session_config.optional_refresh_token();
 
     tracing::info!("Exporting Missing Blobs started");
     let request = ExportBlobsRequest {
@@ -465,8 +643,15 @@ pub async fn export_missing_blobs(pds_session: PdsSession) -> Result<(), GuiErro
         did,
         origin_token: old_token,
         destination_token: new_token,
+        origin_refresh_token: old_refresh_token,
     };
-    match pdsmigration_common::export_blobs_api(request).await {
+    match pdsmigration_common::export_blobs_api(
+        request,
+        &staging_store()?,
+        &session_migration_options(pds_session_lock, progress),
+    )
+    .await
+    {
         Ok(_) => {
             tracing::info!("Exporting Missing Blobs completed");
             //TODO add a check for failed blobs
@@ -480,9 +665,16 @@ pub async fn export_missing_blobs(pds_session: PdsSession) -> Result<(), GuiErro
                 );
                 Err(GuiError::Other)
             }
+            MigrationError::Canceled { finished } => {
+                tracing::info!(
+                    "Exporting Missing Blobs canceled after {} blobs",
+                    finished.len()
+                );
+                Err(GuiError::Canceled)
+            }
             _ => {
                 tracing::error!("Error exporting missing blobs: {:?}", pds_error);
-                Err(GuiError::Runtime)
+                Err(GuiError::from(pds_error))
             }
         },
     }
@@ -513,14 +705,20 @@ pub async fn import_repo(pds_session: PdsSession) -> Result<(), GuiError> {
         did,
         token,
     };
-    match pdsmigration_common::import_pds_api(request).await {
+    match pdsmigration_common::import_pds_api(
+        request,
+        &staging_store()?,
+        &MigrationOptions::default(),
+    )
+    .await
+    {
         Ok(_) => {
             tracing::info!("Importing Repo completed");
             Ok(())
         }
         Err(pds_error) => {
             tracing::error!("Error importing repo: {:?}", pds_error);
-            Err(GuiError::Runtime)
+            Err(GuiError::from(pds_error))
         }
     }
 }
@@ -546,19 +744,32 @@ pub async fn export_repo(pds_session: PdsSession) -> Result<(), GuiError> {
     let token = old_session_config.access_token().to_string();
 
     tracing::info!("Exporting Repo started");
+    let store = staging_store()?;
     let request = ExportPDSRequest {
         pds_host,
-        did,
+        did: did.clone(),
         token,
     };
-    match pdsmigration_common::export_pds_api(request).await {
+    let result =
+        pdsmigration_common::export_pds_api(request, &store, &MigrationOptions::default()).await;
+    // The PLC history is what recovers the identity if both PDSes are lost, but the repo is
+    // still worth having without it
+    if result.is_ok() && did.starts_with("did:plc:") {
+        if let Err(error) = PlcDirectory::default()
+            .export_audit_log(did.as_str(), &store)
+            .await
+        {
+            tracing::warn!("Could not export the PLC audit log: {}", error);
+        }
+    }
+    match result {
         Ok(_res) => {
             tracing::info!("Exporting Repo completed");
             Ok(())
         }
         Err(pds_error) => {
             tracing::error!("Error exporting repo: {:?}", pds_error);
-            Err(GuiError::Other)
+            Err(GuiError::from(pds_error))
         }
     }
 }
@@ -589,14 +800,46 @@ pub async fn export_blobs(pds_session: PdsSession) -> Result<(), GuiError> {
         did,
         token,
     };
-    pdsmigration_common::export_pds_api(request)
+    pdsmigration_common::export_pds_api(request, &staging_store()?, &MigrationOptions::default())
         .await
         .map_err(|error| {
             tracing::error!("Error exporting repo: {:?}", error);
-            GuiError::Other
+            GuiError::from(error)
         })
 }
 
+/// The GUI stages exported repositories and blobs in the working directory.
+/// Options that write refreshed tokens back into the shared session, so later steps
+/// keep working after a long blob transfer.
+fn session_migration_options(
+    pds_session_lock: Arc<RwLock<PdsSession>>,
+    progress: Arc<TransferProgress>,
+) -> MigrationOptions {
+    MigrationOptions {
+        cancel: progress.cancel_token(),
+        progress: Some(progress),
+        on_session_refresh: Some(Arc::new(move |session: &RefreshedSession| {
+            let pds_session_lock = pds_session_lock.clone();
+            let session = session.clone();
+            tokio::spawn(async move {
+                pds_session_lock.write().await.update_tokens(
+                    &session.pds_host,
+                    &session.access_token,
+                    &session.refresh_token,
+                );
+            });
+        })),
+        ..MigrationOptions::default()
+    }
+}
+
+pub(crate) fn staging_store() -> Result<LocalStagingStore, GuiError> {
+    LocalStagingStore::current_dir().map_err(|error| {
+        tracing::error!("Error opening staging directory: {:?}", error);
+        GuiError::Runtime
+    })
+}
+
 pub struct DescribePDS {
     pub terms_of_service: Option<String>,
     pub privacy_policy: Option<String>,
@@ -608,7 +851,7 @@ pub struct DescribePDS {
 pub async fn check_did_exists(new_pds_host: &str, did: &str) -> Result<bool, GuiError> {
     tracing::info!("Checking if DID exists on new PDS: {new_pds_host} {did}");
     use bsky_sdk::api::com::atproto::sync::get_repo_status::{Parameters, ParametersData};
-    let bsky_agent = BskyAgent::builder().build().await.unwrap();
+    let bsky_agent = build_agent(&RetryPolicy::default()).await.unwrap();
     bsky_agent.configure_endpoint(new_pds_host.to_string());
     match bsky_agent
         .api
@@ -628,13 +871,59 @@ pub async fn check_did_exists(new_pds_host: &str, did: &str) -> Result<bool, Gui
     }
 }
 
+/// Checks the old account and the new PDS before an account is created there.
+#[tracing::instrument(skip(pds_session, invite_code))]
+pub async fn preflight(
+    pds_session: PdsSession,
+    new_pds_host: String,
+    new_handle: String,
+    invite_code: String,
+) -> Result<PreflightReport, GuiError> {
+    let did = match pds_session.did().clone() {
+        None => return Err(GuiError::Other),
+        Some(did) => did.to_string(),
+    };
+    let old_session_config = match &pds_session.old_session_config() {
+        None => return Err(GuiError::Other),
+        Some(session_config) => session_config.clone(),
+    };
+    let request = PreflightRequest {
+        origin: old_session_config.host().to_string(),
+        destination: new_pds_host,
+        did,
+        origin_token: old_session_config.access_token().to_string(),
+        destination_token: None,
+        handle: Some(new_handle.trim().to_string()).filter(|handle| !handle.is_empty()),
+        invite_code: Some(invite_code.trim().to_string()).filter(|code| !code.is_empty()),
+    };
+    pdsmigration_common::preflight_api(request, &MigrationOptions::default())
+        .await
+        .map_err(|error| {
+            tracing::error!("Error running preflight checks: {error}");
+            GuiError::from(error)
+        })
+}
+
+/// Finds the PDS an account lives on from its handle or DID.
+#[tracing::instrument]
+pub async fn resolve_pds_host(identifier: String) -> Result<String, GuiError> {
+    IdentityResolver::default()
+        .resolve(&identifier)
+        .await
+        .map(|identity| identity.pds_host)
+        .map_err(|error| {
+            tracing::error!("Error resolving {identifier}: {error}");
+            GuiError::from(error)
+        })
+}
+
 #[tracing::instrument]
 pub async fn fetch_tos_and_privacy_policy(new_pds_host: String) -> Result<DescribePDS, GuiError> {
     tracing::info!(
         "Fetching TOS and Privacy Policy from new PDS: {}",
         new_pds_host
     );
-    let bsky_agent = BskyAgent::builder().build().await.unwrap();
+    let bsky_agent = build_agent(&RetryPolicy::default()).await.unwrap();
     bsky_agent.configure_endpoint(new_pds_host);
     match bsky_agent.api.com.atproto.server.describe_server().await {
         Ok(result) => match result.links.clone() {
@@ -696,12 +985,16 @@ pub async fn create_account(parameters: CreateAccountParameters) -> Result<PdsSe
         did: did.clone(),
         token: old_session_config.access_token().to_string(),
     };
-    let service_token = match pdsmigration_common::get_service_auth_api(service_auth_request).await
+    let service_token = match pdsmigration_common::get_service_auth_api(
+        service_auth_request,
+        &MigrationOptions::default(),
+    )
+    .await
     {
         Ok(res) => res,
-        Err(_pds_error) => {
-            tracing::error!("Error getting service auth token");
-            return Err(GuiError::Runtime);
+        Err(pds_error) => {
+            tracing::error!("Error getting service auth token: {pds_error}");
+            return Err(GuiError::from(pds_error));
         }
     };
 
@@ -725,11 +1018,16 @@ pub async fn create_account(parameters: CreateAccountParameters) -> Result<PdsSe
         plc_op: None,
         token: Some(service_token.clone()),
     };
-    match pdsmigration_common::create_account(new_pds_host.as_str(), &create_account_request).await
+    match pdsmigration_common::create_account(
+        new_pds_host.as_str(),
+        &create_account_request,
+        &RetryPolicy::default(),
+    )
+    .await
     {
         Ok(_) => {
             tracing::info!("Creating Account completed");
-            let bsky_agent = BskyAgent::builder().build().await.unwrap();
+            let bsky_agent = build_agent(&RetryPolicy::default()).await.unwrap();
             match login_helper2(
                 &bsky_agent,
                 new_pds_host.as_str(),
@@ -764,7 +1062,7 @@ pub async fn create_account(parameters: CreateAccountParameters) -> Result<PdsSe
         }
         Err(pds_error) => {
             tracing::error!("Error creating account: {pds_error}");
-            Err(GuiError::Runtime)
+            Err(GuiError::from(pds_error))
         }
     }
 }
@@ -892,49 +1190,6 @@ pub async fn create_service_jwt(params: ServiceJwtParams) -> String {
     )
 }
 
-pub async fn create_update_op<G>(last_op: PlcOperation, signer: &SecretKey, func: G) -> PlcOperation
-where
-    G: Fn(PlcOperation) -> PlcOperation,
-{
-    let last_op_json = serde_json::to_string(&last_op).unwrap();
-    let last_op_index_map: IndexMap<String, Value> = serde_json::from_str(&last_op_json).unwrap();
-    let prev = cid_for_cbor(&last_op_index_map);
-    // omit sig so it doesn't accidentally make its way into the next operation
-    let mut normalized = last_op;
-    normalized.sig = None;
-
-    let mut unsigned = func(normalized);
-    unsigned.prev = Some(prev.to_string());
-
-    add_signature(unsigned, signer).await
-}
-
-pub async fn add_signature(mut obj: PlcOperation, key: &SecretKey) -> PlcOperation {
-    let sig = atproto_sign(&obj, key).to_vec();
-    obj.sig = Some(base64_url::encode(&sig).replace("=", ""));
-    obj
-}
-
-pub fn atproto_sign<T: Serialize>(obj: &T, key: &SecretKey) -> [u8; 64] {
-    // Encode object to json before dag-cbor because serde_ipld_dagcbor doesn't properly
-    // sort by keys
-    let json = serde_json::to_string(obj).unwrap();
-    // Deserialize to IndexMap with preserve key order enabled. serde_ipld_dagcbor does not sort nested
-    // objects properly by keys
-    let map_unsigned: IndexMap<String, Value> = serde_json::from_str(&json).unwrap();
-    let unsigned_bytes = serde_ipld_dagcbor::to_vec(&map_unsigned).unwrap();
-    // Hash dag_cbor to sha256
-    let hash = Sha256::digest(&*unsigned_bytes);
-    // Sign sha256 hash using private key
-    #[allow(deprecated)]
-    let message = Message::from_digest_slice(hash.as_ref()).unwrap();
-    let mut sig = key.sign_ecdsa(message);
-    // Convert to low-s
-    sig.normalize_s();
-    // ASN.1 encoded per decode_dss_signature
-    sig.serialize_compact()
-}
-
 pub fn get_keys_from_private_key_str(private_key: String) -> (SecretKey, PublicKey) {
     let secp = Secp256k1::new();
     let decoded_key = hex::decode(private_key.as_bytes()).unwrap();
@@ -944,25 +1199,6 @@ pub fn get_keys_from_private_key_str(private_key: String) -> (SecretKey, PublicK
     (secret_key, public_key)
 }
 
-pub fn decode_did_secret_key(private_key: &str) -> (SecretKey, PublicKey) {
-    let secp = Secp256k1::new();
-    let decoded_key = hex::decode(private_key.as_bytes())
-        .map_err(|_error| {
-            let _context = format!("Issue decoding hex '{private_key}'");
-            panic!()
-        })
-        .unwrap();
-    #[allow(deprecated)]
-    let secret_key = SecretKey::from_slice(&decoded_key)
-        .map_err(|_error| {
-            let _context = format!("Issue creating secret key from input '{private_key}'");
-            panic!()
-        })
-        .unwrap();
-    let public_key = secret_key.public_key(&secp);
-    (secret_key, public_key)
-}
-
 pub fn extract_multikey(did: &String) -> String {
     if !did.starts_with(DID_KEY_PREFIX) {
         panic!("Incorrect prefix for did:key: {did}")
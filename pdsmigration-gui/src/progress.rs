@@ -0,0 +1,110 @@
+use pdsmigration_common::{CancellationToken, ProgressEvent, ProgressSink};
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TransferCounts {
+    pub total: Option<u64>,
+    pub succeeded: u64,
+    pub skipped: u64,
+    pub failed: u64,
+    pub corrupt: u64,
+    pub bytes: u64,
+}
+
+impl TransferCounts {
+    pub fn finished(&self) -> u64 {
+        self.succeeded + self.skipped + self.failed + self.corrupt
+    }
+}
+
+/// Collects progress events from a running transfer so a screen can draw them, and lets the
+/// screen cancel it.
+#[derive(Debug, Default)]
+pub struct TransferProgress {
+    counts: Mutex<TransferCounts>,
+    cancel: CancellationToken,
+}
+
+impl TransferProgress {
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    pub fn counts(&self) -> TransferCounts {
+        self.counts.lock().unwrap().clone()
+    }
+
+    pub fn render(&self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let counts = self.counts();
+        let Some(total) = counts.total else {
+            ui.spinner();
+            ctx.request_repaint_after(Duration::from_millis(250));
+            return;
+        };
+        let fraction = if total == 0 {
+            1.0
+        } else {
+            counts.finished() as f32 / total as f32
+        };
+        ui.add(egui::ProgressBar::new(fraction).text(format!(
+            "{} of {} blobs",
+            counts.finished(),
+            total
+        )));
+        ui.label(format!(
+            "{} transferred, {} failed, {} corrupt, {:.1} MB",
+            counts.succeeded,
+            counts.failed,
+            counts.corrupt,
+            counts.bytes as f64 / 1_000_000.0
+        ));
+        if counts.finished() < total {
+            if self.cancel.is_cancelled() {
+                ui.label("Stopping after the blobs in flight…");
+            } else if ui.button("Cancel").clicked() {
+                self.cancel.cancel();
+            }
+            ctx.request_repaint_after(Duration::from_millis(250));
+        }
+    }
+}
+
+impl ProgressSink for TransferProgress {
+    fn report(&self, event: ProgressEvent) {
+        let mut counts = self.counts.lock().unwrap();
+        match event {
+            ProgressEvent::Total { items } => counts.total = Some(items),
+            ProgressEvent::ItemSucceeded { .. } => counts.succeeded += 1,
+            ProgressEvent::ItemSkipped { .. } => counts.skipped += 1,
+            ProgressEvent::ItemCorrupt { .. } => counts.corrupt += 1,
+            ProgressEvent::ItemFailed { .. } => counts.failed += 1,
+            ProgressEvent::ItemCanceled { .. } => {}
+            ProgressEvent::BytesTransferred { bytes } => counts.bytes += bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_are_counted() {
+        let progress = TransferProgress::default();
+        progress.report(ProgressEvent::Total { items: 3 });
+        progress.report(ProgressEvent::ItemSucceeded {
+            id: "cid0".to_string(),
+        });
+        progress.report(ProgressEvent::ItemFailed {
+            id: "cid1".to_string(),
+            message: "gone".to_string(),
+        });
+        progress.report(ProgressEvent::BytesTransferred { bytes: 42 });
+
+        let counts = progress.counts();
+        assert_eq!(counts.total, Some(3));
+        assert_eq!(counts.finished(), 2);
+        assert_eq!(counts.bytes, 42);
+    }
+}
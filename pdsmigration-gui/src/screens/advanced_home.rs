@@ -1,11 +1,11 @@
 use crate::errors::GuiError;
 use crate::screens::Screen;
 use crate::session::session_config::PdsSession;
-use crate::{activate_account, deactivate_account, export_repo, styles, ScreenType};
-use bsky_sdk::BskyAgent;
+use crate::{activate_account, deactivate_account, export_repo, staging_store, styles, ScreenType};
 use egui::{ScrollArea, Ui};
 use pdsmigration_common::{
-    build_agent, login_helper, missing_blobs, upload_blobs_api, UploadBlobsRequest,
+    build_agent, login_helper, missing_blobs, upload_blobs_api, MigrationOptions, RetryPolicy,
+    UploadBlobsRequest,
 };
 use std::sync::Arc;
 use tokio::fs::File;
@@ -16,19 +16,19 @@ use tokio::sync::RwLock;
 pub struct AdvancedHome {
     pds_session: Arc<RwLock<PdsSession>>,
     error: Arc<RwLock<Vec<GuiError>>>,
-    _page: Arc<RwLock<ScreenType>>,
+    page: Arc<RwLock<ScreenType>>,
 }
 
 impl AdvancedHome {
     pub fn new(
         pds_session: Arc<RwLock<PdsSession>>,
         error: Arc<RwLock<Vec<GuiError>>>,
-        _page: Arc<RwLock<ScreenType>>,
+        page: Arc<RwLock<ScreenType>>,
     ) -> Self {
         Self {
             pds_session,
             error,
-            _page,
+            page,
         }
     }
 }
@@ -88,7 +88,15 @@ impl Screen for AdvancedHome {
                     value.clone()
                 };
                 let error = self.error.clone();
-                let new_session_config = match pds_session.old_session_config() {
+                let old_session_config = match pds_session.old_session_config() {
+                    None => {
+                        let mut error_write = error.blocking_write();
+                        error_write.push(GuiError::Other);
+                        return;
+                    }
+                    Some(config) => config.clone(),
+                };
+                let new_session_config = match pds_session.new_session_config() {
                     None => {
                         let mut error_write = error.blocking_write();
                         error_write.push(GuiError::Other);
@@ -98,7 +106,7 @@ impl Screen for AdvancedHome {
                 };
                 tokio::spawn(async move {
                     tracing::info!("Deactivating account");
-                    match deactivate_account(new_session_config).await {
+                    match deactivate_account(old_session_config, new_session_config).await {
                         Ok(_) => {
                             tracing::info!("Deactivated account");
                         }
@@ -125,7 +133,7 @@ impl Screen for AdvancedHome {
                     Some(config) => config.clone(),
                 };
                 tokio::spawn(async move {
-                    let agent = build_agent().await.unwrap();
+                    let agent = build_agent(&RetryPolicy::default()).await.unwrap();
                     let _session = login_helper(
                         &agent,
                         new_session_config.host(),
@@ -169,7 +177,7 @@ impl Screen for AdvancedHome {
                     Some(config) => config.clone(),
                 };
                 tokio::spawn(async move {
-                    let agent = BskyAgent::builder().build().await.unwrap();
+                    let agent = build_agent(&RetryPolicy::default()).await.unwrap();
                     let session = login_helper(
                         &agent,
                         new_session_config.host(),
@@ -183,8 +191,23 @@ impl Screen for AdvancedHome {
                         pds_host: new_session_config.host().to_string(),
                         did: new_session_config.did().to_string(),
                         token: session.access_jwt.clone(),
+                        refresh_token: None,
                     };
-                    match upload_blobs_api(upload_blob_request).await {
+                    let store = match staging_store() {
+                        Ok(store) => store,
+                        Err(e) => {
+                            let mut error_write = error.write().await;
+                            error_write.push(e);
+                            return;
+                        }
+                    };
+                    match upload_blobs_api(
+                        upload_blob_request,
+                        &store,
+                        &MigrationOptions::default(),
+                    )
+                    .await
+                    {
                         Ok(_) => {
                             tracing::info!("Uploaded blobs");
                         }
@@ -196,6 +219,10 @@ impl Screen for AdvancedHome {
                     }
                 });
             });
+            styles::render_button(ui, ctx, "Recover PLC", || {
+                let mut page_write = self.page.blocking_write();
+                *page_write = ScreenType::RecoverPLC;
+            });
         });
     }
 
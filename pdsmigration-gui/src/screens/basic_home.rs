@@ -1,4 +1,5 @@
 use crate::errors::GuiError;
+use crate::progress::TransferProgress;
 use crate::screens::Screen;
 use crate::session::session_config::PdsSession;
 use crate::{export_all_blobs, export_repo, styles, ScreenType};
@@ -12,6 +13,7 @@ pub struct BasicHome {
     error: Arc<RwLock<Vec<GuiError>>>,
     page: Arc<RwLock<ScreenType>>,
     pds_migration_step: Arc<RwLock<bool>>,
+    media_progress: Option<Arc<TransferProgress>>,
 }
 
 impl BasicHome {
@@ -26,10 +28,11 @@ impl BasicHome {
             error,
             page,
             pds_migration_step,
+            media_progress: None,
         }
     }
 
-    pub fn show_logged_in(&self, ui: &mut Ui, ctx: &egui::Context) {
+    pub fn show_logged_in(&mut self, ui: &mut Ui, ctx: &egui::Context) {
         ScrollArea::both().show(ui, |ui| {
             styles::render_button(ui, ctx, "Migrate to new PDS", || {
                 let pds_migration_step = self.pds_migration_step.clone();
@@ -61,14 +64,12 @@ impl BasicHome {
                 });
             });
             styles::render_button(ui, ctx, "Backup Media", || {
-                let pds_session = {
-                    let pds_session_lock = self.pds_session.clone();
-                    let value = pds_session_lock.blocking_read();
-                    value.clone()
-                };
+                let pds_session = self.pds_session.clone();
                 let error = self.error.clone();
+                let progress = Arc::new(TransferProgress::default());
+                self.media_progress = Some(progress.clone());
                 tokio::spawn(async move {
-                    match export_all_blobs(pds_session).await {
+                    match export_all_blobs(pds_session, progress).await {
                         Ok(_) => {}
                         Err(e) => {
                             let mut error = error.write().await;
@@ -77,6 +78,9 @@ impl BasicHome {
                     }
                 });
             });
+            if let Some(progress) = &self.media_progress {
+                progress.render(ui, ctx);
+            }
         });
     }
 
@@ -3,11 +3,11 @@ use crate::errors::GuiError;
 use crate::screens::Screen;
 use crate::session::session_config::PdsSession;
 use crate::{
-    check_did_exists, create_account, fetch_tos_and_privacy_policy, styles,
+    check_did_exists, create_account, fetch_tos_and_privacy_policy, preflight, styles,
     CreateAccountParameters, ScreenType,
 };
-use bsky_sdk::BskyAgent;
-use egui::Ui;
+use egui::{Color32, Ui};
+use pdsmigration_common::{build_agent, PreflightReport, RetryPolicy};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -28,6 +28,7 @@ pub struct CreateOrLoginAccount {
     page: Arc<RwLock<ScreenType>>,
     pds_migration_step: Arc<RwLock<bool>>,
     ui_mode: Arc<RwLock<UiMode>>,
+    preflight_report: Arc<RwLock<Option<PreflightReport>>>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -61,6 +62,7 @@ impl CreateOrLoginAccount {
             page,
             pds_migration_step,
             ui_mode: Arc::new(RwLock::from(UiMode::SelectPds)),
+            preflight_report: Arc::new(Default::default()),
         }
     }
 
@@ -133,6 +135,11 @@ impl CreateOrLoginAccount {
         let new_password = self.new_password.clone();
         let new_handle = self.new_handle.clone();
         let invite_code = self.invite_code.clone();
+        let preflight_pds_session = pds_session.clone();
+        let preflight_pds_host = new_pds_host.clone();
+        let preflight_handle = new_handle.clone();
+        let preflight_invite_code = invite_code.clone();
+        let preflight_report = self.preflight_report.clone();
         let params = CreateAccountParameters {
             pds_session,
             new_email,
@@ -147,6 +154,31 @@ impl CreateOrLoginAccount {
         let pds_migration_step = self.pds_migration_step.clone();
 
         tokio::spawn(async move {
+            // Nothing is created on the new PDS until every blocker has been cleared
+            let report = match preflight(
+                preflight_pds_session,
+                preflight_pds_host,
+                preflight_handle,
+                preflight_invite_code,
+            )
+            .await
+            {
+                Ok(report) => report,
+                Err(e) => {
+                    let mut errors = error.write().await;
+                    errors.push(e);
+                    return;
+                }
+            };
+            let ready = report.ready();
+            {
+                let mut preflight_report_write = preflight_report.write().await;
+                *preflight_report_write = Some(report);
+            }
+            if !ready {
+                tracing::error!("Preflight checks found blockers, not creating the account");
+                return;
+            }
             match create_account(params).await {
                 Ok(pds_session) => {
                     {
@@ -340,6 +372,7 @@ impl CreateOrLoginAccount {
                         }
                     });
                 }
+                self.render_preflight_report(ui);
                 styles::render_button(ui, ctx, "Submit", || {
                     if self.validate_create_inputs() {
                         self.submit();
@@ -349,6 +382,22 @@ impl CreateOrLoginAccount {
         });
     }
 
+    fn render_preflight_report(&self, ui: &mut Ui) {
+        let preflight_report = self.preflight_report.blocking_read();
+        let Some(report) = preflight_report.as_ref() else {
+            return;
+        };
+        if report.ready() {
+            ui.label("Ready to migrate");
+        }
+        for blocker in &report.blockers {
+            ui.colored_label(Color32::RED, &blocker.message);
+        }
+        for warning in &report.warnings {
+            ui.colored_label(Color32::from_rgb(200, 130, 0), &warning.message);
+        }
+    }
+
     #[tracing::instrument(skip(self))]
     fn new_session_login(&mut self) {
         let new_pds_host = self.new_pds_host.to_string();
@@ -360,7 +409,7 @@ impl CreateOrLoginAccount {
         let pds_migration_step_lock = self.pds_migration_step.clone();
 
         tokio::spawn(async move {
-            let bsky_agent = BskyAgent::builder().build().await.unwrap();
+            let bsky_agent = build_agent(&RetryPolicy::default()).await.unwrap();
             match login_helper2(
                 &bsky_agent,
                 new_pds_host.as_str(),
@@ -54,7 +54,7 @@ impl DeactivateAndActivate {
         let page = self.page.clone();
         tokio::spawn(async move {
             tracing::info!("Deactivating old account, and activating new account");
-            match activate_account(new_session_config).await {
+            match activate_account(new_session_config.clone()).await {
                 Ok(_) => {
                     tracing::info!("Activated new account");
                 }
@@ -63,7 +63,7 @@ impl DeactivateAndActivate {
                     error_write.push(e);
                 }
             }
-            match deactivate_account(old_session_config).await {
+            match deactivate_account(old_session_config, new_session_config).await {
                 Ok(_) => {
                     tracing::info!("Deactivated old account");
                 }
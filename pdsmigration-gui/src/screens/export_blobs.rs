@@ -1,4 +1,5 @@
 use crate::errors::GuiError;
+use crate::progress::TransferProgress;
 use crate::screens::Screen;
 use crate::session::session_config::PdsSession;
 use crate::{export_missing_blobs, styles, ScreenType};
@@ -12,6 +13,7 @@ pub struct ExportBlobs {
     task_started: bool,
     page: Arc<RwLock<ScreenType>>,
     pds_migration_step: Arc<RwLock<bool>>,
+    progress: Arc<TransferProgress>,
 }
 
 impl ExportBlobs {
@@ -27,6 +29,7 @@ impl ExportBlobs {
             task_started: false,
             page,
             pds_migration_step,
+            progress: Arc::new(TransferProgress::default()),
         }
     }
 }
@@ -35,22 +38,12 @@ impl Screen for ExportBlobs {
     fn ui(&mut self, ui: &mut Ui, ctx: &egui::Context) {
         styles::render_subtitle(ui, ctx, "Exporting blobs from old PDS");
         if self.task_started {
+            self.progress.render(ui, ctx);
             return;
         }
         self.task_started = true;
 
-        let pds_session = {
-            let lock = self.pds_session.clone();
-            let value = lock.try_read();
-            match value {
-                Ok(value) => value.clone(),
-                Err(_) => {
-                    tracing::debug!("Unable to read pds session");
-                    self.task_started = false;
-                    return;
-                }
-            }
-        };
+        let pds_session = self.pds_session.clone();
         let error = self.error.clone();
         let pds_migration_step = {
             let lock = self.pds_migration_step.clone();
@@ -65,9 +58,10 @@ impl Screen for ExportBlobs {
             }
         };
         let page = self.page.clone();
+        let progress = self.progress.clone();
         tokio::spawn(async move {
             tracing::info!("Exporting blobs from old PDS");
-            match export_missing_blobs(pds_session).await {
+            match export_missing_blobs(pds_session, progress).await {
                 Ok(_) => {
                     tracing::info!("Blobs exported from old PDS");
                     if pds_migration_step {
@@ -1,4 +1,5 @@
 use crate::errors::GuiError;
+use crate::progress::TransferProgress;
 use crate::screens::Screen;
 use crate::session::session_config::PdsSession;
 use crate::{styles, upload_blobs, ScreenType};
@@ -12,6 +13,7 @@ pub struct ImportBlobs {
     task_started: bool,
     page: Arc<RwLock<ScreenType>>,
     pds_migration_step: Arc<RwLock<bool>>,
+    progress: Arc<TransferProgress>,
 }
 
 impl ImportBlobs {
@@ -27,6 +29,7 @@ impl ImportBlobs {
             task_started: false,
             page,
             pds_migration_step,
+            progress: Arc::new(TransferProgress::default()),
         }
     }
 }
@@ -34,24 +37,22 @@ impl Screen for ImportBlobs {
     fn ui(&mut self, ui: &mut Ui, ctx: &egui::Context) {
         styles::render_subtitle(ui, ctx, "Uploading Blobs to new PDS");
         if self.task_started {
+            self.progress.render(ui, ctx);
             return;
         }
         self.task_started = true;
         let error = self.error.clone();
-        let pds_session = {
-            let lock = self.pds_session.clone();
-            let value = lock.blocking_read();
-            value.clone()
-        };
+        let pds_session = self.pds_session.clone();
         let pds_migration_step = {
             let lock = self.pds_migration_step.clone();
             let value = lock.blocking_read();
             *value
         };
         let page = self.page.clone();
+        let progress = self.progress.clone();
         tokio::spawn(async move {
             tracing::info!("Importing blobs to new PDS");
-            match upload_blobs(pds_session).await {
+            match upload_blobs(pds_session, progress).await {
                 Ok(_) => {
                     tracing::info!("Importing blobs successful");
                     if pds_migration_step {
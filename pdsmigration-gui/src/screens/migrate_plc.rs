@@ -1,11 +1,18 @@
 use crate::errors::GuiError;
 use crate::screens::Screen;
 use crate::session::session_config::PdsSession;
-use crate::{generate_recovery_key, migrate_plc_via_pds, request_token, styles, ScreenType};
-use egui::Ui;
+use crate::{
+    generate_recovery_key, migrate_plc_via_pds, preview_plc_via_pds, request_token, styles,
+    ScreenType,
+};
+use egui::{Color32, RichText, Ui};
+use pdsmigration_common::PlcOperationDiff;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+/// A recovery key with the diff previewed for it, or why the preview failed.
+type PlcPreview = (String, Result<PlcOperationDiff, String>);
+
 pub struct MigratePLC {
     user_recovery_key_password: String,
     pds_session: Arc<RwLock<PdsSession>>,
@@ -14,6 +21,11 @@ pub struct MigratePLC {
     user_recovery_key: String,
     generated_user_recovery_key: Arc<RwLock<Option<String>>>,
     plc_token: String,
+    /// The changes previewed for a recovery key, which must be shown before submitting.
+    /// Holds the error instead when the preview could not be built.
+    preview: Arc<RwLock<Option<PlcPreview>>>,
+    /// Whether the user chose to submit without a preview of the changes.
+    confirm_unpreviewed: bool,
     page: Arc<RwLock<ScreenType>>,
 }
 
@@ -32,6 +44,8 @@ impl MigratePLC {
             user_recovery_key: "".to_string(),
             generated_user_recovery_key: Arc::new(Default::default()),
             plc_token: "".to_string(),
+            preview: Arc::new(Default::default()),
+            confirm_unpreviewed: false,
             page,
         }
     }
@@ -121,16 +135,55 @@ impl MigratePLC {
                 });
             });
         });
+        ui.horizontal(|ui| {
+            styles::render_button(ui, ctx, "Preview Changes", || {
+                if self.user_recovery_key.is_empty() {
+                    tracing::error!("User Recovery Key is empty");
+                    return;
+                }
+                self.confirm_unpreviewed = false;
+                self.start_preview();
+            });
+        });
+
+        let preview = {
+            let lock = self.preview.blocking_read();
+            lock.clone()
+        };
+        // A preview only counts for the recovery key it was made with
+        let Some((_, diff)) = preview.filter(|(key, _)| *key == self.user_recovery_key) else {
+            return;
+        };
+        styles::render_subtitle(ui, ctx, "Changes to your DID");
+        match diff {
+            Ok(diff) => {
+                for line in diff.to_string().lines() {
+                    if line.starts_with('!') {
+                        ui.label(RichText::new(line).color(Color32::RED));
+                    } else {
+                        ui.label(line);
+                    }
+                }
+            }
+            Err(e) => {
+                // Older accounts may have histories the preview can't handle, so they can still
+                // submit once they acknowledge that the changes are unseen
+                ui.label(RichText::new(format!("Preview unavailable: {e}")).color(Color32::RED));
+                ui.checkbox(
+                    &mut self.confirm_unpreviewed,
+                    "I understand the changes could not be previewed and want to submit anyway",
+                );
+                if !self.confirm_unpreviewed {
+                    return;
+                }
+            }
+        }
         ui.horizontal(|ui| {
             styles::render_button(ui, ctx, "Submit", || {
                 if self.plc_token.is_empty() {
                     tracing::error!("PLC Signing Token is empty");
                     return;
                 }
-                if self.user_recovery_key.is_empty() {
-                    tracing::error!("User Recovery Key is empty");
-                    return;
-                }
 
                 self.task_started = true;
                 self.start_migration();
@@ -138,6 +191,29 @@ impl MigratePLC {
         });
     }
 
+    fn start_preview(&mut self) {
+        let pds_session = {
+            let lock = self.pds_session.clone();
+            let value = lock.blocking_read();
+            value.clone()
+        };
+        let user_recovery_key = self.user_recovery_key.clone();
+        let preview = self.preview.clone();
+        tokio::spawn(async move {
+            match preview_plc_via_pds(pds_session, Some(user_recovery_key.clone())).await {
+                Ok(diff) => {
+                    let mut preview_write = preview.write().await;
+                    *preview_write = Some((user_recovery_key, Ok(diff)));
+                }
+                Err(e) => {
+                    tracing::error!("Error previewing PLC update: {}", e);
+                    let mut preview_write = preview.write().await;
+                    *preview_write = Some((user_recovery_key, Err(e.to_string())));
+                }
+            }
+        });
+    }
+
     fn start_migration(&mut self) {
         let pds_session = {
             let lock = self.pds_session.clone();
@@ -14,6 +14,7 @@ pub mod migrate_plc;
 pub mod migrate_preferences;
 pub mod migrate_without_pds;
 pub mod old_login;
+pub mod recover_plc;
 pub mod success;
 
 pub trait Screen {
@@ -3,9 +3,9 @@ use crate::errors::GuiError;
 use crate::screens::Screen;
 use crate::session::session_config::PdsSession;
 use crate::styles::WIDGET_SPACING_BASE;
-use crate::{styles, ScreenType};
-use bsky_sdk::BskyAgent;
+use crate::{resolve_pds_host, styles, ScreenType};
 use egui::Ui;
+use pdsmigration_common::{build_agent, RetryPolicy};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -54,7 +54,15 @@ impl OldLogin {
 
         tokio::spawn(async move {
             tracing::info!("Confirming email token");
-            let bsky_agent = BskyAgent::builder().build().await.unwrap();
+            let old_pds_host = match pds_host_or_resolve(old_pds_host, &username).await {
+                Ok(old_pds_host) => old_pds_host,
+                Err(e) => {
+                    let mut error = error_lock.write().await;
+                    error.push(e);
+                    return;
+                }
+            };
+            let bsky_agent = build_agent(&RetryPolicy::default()).await.unwrap();
 
             match confirm_email_token(
                 &bsky_agent,
@@ -114,7 +122,15 @@ impl OldLogin {
 
         tokio::spawn(async move {
             tracing::info!("Logging in to old PDS");
-            let bsky_agent = BskyAgent::builder().build().await.unwrap();
+            let old_pds_host = match pds_host_or_resolve(old_pds_host, &username).await {
+                Ok(old_pds_host) => old_pds_host,
+                Err(e) => {
+                    let mut error = error_lock.write().await;
+                    error.push(e);
+                    return;
+                }
+            };
+            let bsky_agent = build_agent(&RetryPolicy::default()).await.unwrap();
 
             match login_helper2(
                 &bsky_agent,
@@ -166,7 +182,9 @@ impl OldLogin {
         let username = self.username.to_string();
         let password = self.password.to_string();
 
+        // Left empty, the host is looked up from the handle
         match reqwest::Url::parse(old_pds_host.as_str()) {
+            _ if old_pds_host.trim().is_empty() => {}
             Ok(url) if url.scheme() == "https" && url.host_str().is_some() => {}
             Ok(_) => {
                 tracing::error!("PDS host must use HTTPS protocol");
@@ -195,6 +213,14 @@ impl OldLogin {
     }
 }
 
+/// The host as typed in, or the PDS the handle resolves to when it was left empty.
+async fn pds_host_or_resolve(old_pds_host: String, username: &str) -> Result<String, GuiError> {
+    if !old_pds_host.trim().is_empty() {
+        return Ok(old_pds_host);
+    }
+    resolve_pds_host(username.to_string()).await
+}
+
 impl Screen for OldLogin {
     fn ui(&mut self, ui: &mut Ui, ctx: &egui::Context) {
         let email_token_page = {
@@ -206,7 +232,7 @@ impl Screen for OldLogin {
             ui.vertical_centered(|ui| {
                 styles::render_input(
                     ui,
-                    "Current PDS Host",
+                    "Current PDS Host (optional)",
                     &mut self.old_pds_host,
                     false,
                     Some("https://bsky.social"),
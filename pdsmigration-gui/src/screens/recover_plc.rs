@@ -0,0 +1,147 @@
+use crate::errors::GuiError;
+use crate::screens::Screen;
+use crate::session::session_config::PdsSession;
+use crate::{recover_plc, recoverable_plc_operations, styles, ScreenType};
+use egui::{ScrollArea, Ui};
+use pdsmigration_common::PlcLogAuditEntry;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub struct RecoverPLC {
+    error: Arc<RwLock<Vec<GuiError>>>,
+    did: String,
+    rotation_key: String,
+    operations: Arc<RwLock<Vec<PlcLogAuditEntry>>>,
+    selected_cid: Option<String>,
+    task_started: Arc<RwLock<bool>>,
+}
+
+impl RecoverPLC {
+    pub fn new(pds_session: Arc<RwLock<PdsSession>>, error: Arc<RwLock<Vec<GuiError>>>) -> Self {
+        let did = {
+            let value = pds_session.blocking_read();
+            value
+                .did()
+                .as_ref()
+                .map(|did| did.to_string())
+                .unwrap_or_default()
+        };
+        Self {
+            error,
+            did,
+            rotation_key: "".to_string(),
+            operations: Arc::new(Default::default()),
+            selected_cid: None,
+            task_started: Arc::new(RwLock::new(false)),
+        }
+    }
+
+    fn load_operations(&mut self) {
+        let did = self.did.trim().to_string();
+        let operations = self.operations.clone();
+        let error = self.error.clone();
+        self.selected_cid = None;
+        tokio::spawn(async move {
+            match recoverable_plc_operations(did).await {
+                Ok(recoverable) => {
+                    if recoverable.is_empty() {
+                        tracing::info!("No operations within the 72 hour recovery window");
+                    }
+                    let mut operations_write = operations.write().await;
+                    *operations_write = recoverable;
+                }
+                Err(e) => {
+                    let mut error_write = error.write().await;
+                    error_write.push(e);
+                }
+            }
+        });
+    }
+
+    fn start_recovery(&mut self, cid: String) {
+        let did = self.did.trim().to_string();
+        let rotation_key = self.rotation_key.clone();
+        let operations = self.operations.clone();
+        let task_started = self.task_started.clone();
+        let error = self.error.clone();
+        *task_started.blocking_write() = true;
+        tokio::spawn(async move {
+            match recover_plc(did, rotation_key, cid).await {
+                Ok(_) => {
+                    let mut operations_write = operations.write().await;
+                    operations_write.clear();
+                }
+                Err(e) => {
+                    let mut error_write = error.write().await;
+                    error_write.push(e);
+                }
+            }
+            let mut task_started_write = task_started.write().await;
+            *task_started_write = false;
+        });
+    }
+}
+
+impl Screen for RecoverPLC {
+    fn ui(&mut self, ui: &mut Ui, ctx: &egui::Context) {
+        styles::render_subtitle(ui, ctx, "Recover PLC");
+        ui.label(
+            "Nullify an unwanted PLC operation within 72 hours of it, using a rotation key \
+             that ranks above the key that signed it.",
+        );
+        if *self.task_started.blocking_read() {
+            ui.label("Submitting recovery operation...");
+            return;
+        }
+        styles::render_input(ui, "DID", &mut self.did, false, Some("did:plc:..."));
+        styles::render_input(ui, "Rotation Key", &mut self.rotation_key, true, None);
+        styles::render_button(ui, ctx, "Load Operations", || {
+            if !self.did.trim().starts_with("did:plc:") {
+                tracing::error!("DID must be a did:plc");
+                return;
+            }
+            self.load_operations();
+        });
+
+        let operations = { self.operations.blocking_read().clone() };
+        if operations.is_empty() {
+            return;
+        }
+        ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+            for entry in &operations {
+                let summary = match entry.operation.normalized() {
+                    Some(operation) => format!(
+                        "{}  {}",
+                        operation
+                            .services
+                            .get("atproto_pds")
+                            .map(|service| service.endpoint.as_str())
+                            .unwrap_or("no PDS"),
+                        operation.also_known_as.join(", ")
+                    ),
+                    None => "tombstone".to_string(),
+                };
+                let label = format!("{}  {}  ({})", entry.created_at, summary, entry.cid);
+                let selected = self.selected_cid.as_deref() == Some(entry.cid.as_str());
+                if ui.selectable_label(selected, label).clicked() {
+                    self.selected_cid = Some(entry.cid.clone());
+                }
+            }
+        });
+        let Some(cid) = self.selected_cid.clone() else {
+            return;
+        };
+        ui.label("The selected operation and every operation after it will be nullified.");
+        styles::render_button(ui, ctx, "Nullify Operation", || {
+            if self.rotation_key.is_empty() {
+                tracing::error!("Rotation Key is empty");
+                return;
+            }
+            self.start_recovery(cid);
+        });
+    }
+
+    fn name(&self) -> ScreenType {
+        ScreenType::RecoverPLC
+    }
+}
@@ -45,6 +45,11 @@ impl SessionConfig {
     pub fn did(&self) -> &str {
         &self.did
     }
+
+    /// The refresh token, if the session was created with one.
+    pub fn optional_refresh_token(&self) -> Option<String> {
+        Some(self.refresh_token.clone()).filter(|token| !token.is_empty())
+    }
 }
 
 impl PdsSession {
@@ -124,6 +129,19 @@ impl PdsSession {
         }
     }
 
+    /// Replaces the tokens of whichever session lives on `host`, after they were refreshed.
+    pub fn update_tokens(&mut self, host: &str, access_token: &str, refresh_token: &str) {
+        for config in [&mut self.old_session_config, &mut self.new_session_config]
+            .into_iter()
+            .flatten()
+        {
+            if config.host == host {
+                config.access_token = access_token.to_string();
+                config.refresh_token = refresh_token.to_string();
+            }
+        }
+    }
+
     pub fn did(&self) -> &Option<String> {
         &self.did
     }
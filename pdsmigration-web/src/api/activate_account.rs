@@ -1,6 +1,7 @@
+use crate::config::AppConfig;
 use crate::errors::{ApiError, ApiErrorBody};
 use crate::post;
-use actix_web::web::Json;
+use actix_web::web::{Data, Json};
 use actix_web::HttpResponse;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
@@ -15,7 +16,7 @@ pub struct ActivateAccountApiRequest {
     pub token: String,
 }
 
-#[tracing::instrument(skip(req), fields(did = %req.did, pds_host = %req.pds_host))]
+#[tracing::instrument(skip(req, config), fields(did = %req.did, pds_host = %req.pds_host))]
 #[utoipa::path(
     post,
     path = "/activate-account",
@@ -31,12 +32,19 @@ pub struct ActivateAccountApiRequest {
 #[post("/activate-account")]
 pub async fn activate_account_api(
     req: Json<ActivateAccountApiRequest>,
+    config: Data<AppConfig>,
 ) -> Result<HttpResponse, ApiError> {
     tracing::info!("Activate account request received");
     let req = req.into_inner();
     let did = req.did.clone();
     let token = req.token.clone();
     let pds_host = req.pds_host.clone();
-    pdsmigration_common::activate_account(pds_host.as_str(), did.as_str(), token.as_str()).await?;
+    pdsmigration_common::activate_account(
+        pds_host.as_str(),
+        did.as_str(),
+        token.as_str(),
+        &config.server.migration_options(),
+    )
+    .await?;
     Ok(HttpResponse::Ok().finish())
 }
@@ -0,0 +1,130 @@
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiErrorBody};
+use crate::post;
+use actix_web::web::{Data, Json};
+use actix_web::HttpResponse;
+use pdsmigration_common::{repo_rev_key, CatchUpRequest, MemoryStagingStore, StagingStore};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CatchUpRepoApiRequest {
+    #[schema(example = "https://sourcePDS.example.com")]
+    pub origin: String,
+    #[schema(example = "https://destinationPDS.example.com")]
+    pub destination: String,
+    #[schema(example = "did:plc:abcd1234efgh5678ijkl")]
+    pub did: String,
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
+    pub origin_token: String,
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
+    pub destination_token: String,
+    #[serde(default, skip_serializing_if = "core::option::Option::is_none")]
+    #[schema(example = "3kabcdefghi2a")]
+    pub since: Option<String>,
+}
+
+impl From<CatchUpRepoApiRequest> for CatchUpRequest {
+    fn from(req: CatchUpRepoApiRequest) -> Self {
+        Self {
+            origin: req.origin,
+            destination: req.destination,
+            did: req.did,
+            origin_token: req.origin_token,
+            destination_token: req.destination_token,
+            since: req.since,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CatchUpRepoApiResponse {
+    #[schema(example = "3kabcdefghi2a")]
+    pub since: String,
+    #[schema(example = "3kabcdefzzz2a")]
+    pub rev: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/catch-up-repo",
+    request_body = CatchUpRepoApiRequest,
+    responses(
+        (status = 200, description = "Records written since the import were applied", body = CatchUpRepoApiResponse, content_type = "application/json"),
+        (status = 400, description = "Invalid request", body = ApiErrorBody, content_type = "application/json"),
+        (status = 401, description = "Authentication error", body = ApiErrorBody, content_type = "application/json"),
+        (status = 429, description = "Rate limit exceeded", body = ApiErrorBody, content_type = "application/json"),
+    ),
+    tag = "pdsmigration-web"
+)]
+#[tracing::instrument(skip(req, config))]
+#[post("/catch-up-repo")]
+pub async fn catch_up_repo_api(
+    req: Json<CatchUpRepoApiRequest>,
+    config: Data<AppConfig>,
+) -> Result<HttpResponse, ApiError> {
+    tracing::info!("Catch-up repository request received");
+    let endpoint_url = config.external_services.s3_endpoint.clone();
+    let aws_config = aws_config::from_env()
+        .region("auto")
+        .endpoint_url(&endpoint_url)
+        .load()
+        .await;
+    let client = aws_sdk_s3::Client::new(&aws_config);
+
+    let req_inner = req.into_inner();
+    let did = req_inner.did.clone();
+    let bucket_name = "migration".to_string();
+    let key = "migration/".to_string() + &repo_rev_key(&did);
+
+    let store = MemoryStagingStore::new();
+    if req_inner.since.is_none() {
+        // Start from the rev recorded by /import-repo
+        let s3_response = client
+            .get_object()
+            .bucket(&bucket_name)
+            .key(&key)
+            .send()
+            .await
+            .map_err(|error| ApiError::Runtime {
+                message: error.to_string(),
+            })?;
+        let rev = s3_response
+            .body
+            .collect()
+            .await
+            .map_err(|error| ApiError::Runtime {
+                message: error.to_string(),
+            })?;
+        store
+            .put(&repo_rev_key(&did), rev.into_bytes().to_vec())
+            .await?;
+    }
+
+    let response = pdsmigration_common::catch_up_api(
+        req_inner.into(),
+        &store,
+        &config.server.migration_options(),
+    )
+    .await?;
+
+    if response.rev.is_some() {
+        let rev = store.get(&repo_rev_key(&did)).await?;
+        client
+            .put_object()
+            .bucket(&bucket_name)
+            .key(&key)
+            .body(aws_sdk_s3::primitives::ByteStream::from(rev))
+            .send()
+            .await
+            .map_err(|error| ApiError::Runtime {
+                message: error.to_string(),
+            })?;
+    }
+    tracing::info!("Repository caught up successfully");
+
+    Ok(HttpResponse::Ok().json(CatchUpRepoApiResponse {
+        since: response.since,
+        rev: response.rev,
+    }))
+}
@@ -1,6 +1,7 @@
+use crate::config::AppConfig;
 use crate::errors::{ApiError, ApiErrorBody};
 use crate::post;
-use actix_web::web::Json;
+use actix_web::web::{Data, Json};
 use actix_web::HttpResponse;
 use pdsmigration_common::{create_account, CreateAccountRequest};
 use serde::{Deserialize, Serialize};
@@ -60,6 +61,10 @@ impl fmt::Debug for CreateAccountApiRequest {
             "code": "AUTHENTICATION_ERROR",
             "message": "Invalid or expired token"
         })),
+        (status = 409, description = "Handle taken or account already exists", body = ApiErrorBody, content_type = "application/json", example = json!({
+            "code": "HANDLE_UNAVAILABLE",
+            "message": "https://pds.example.com answered 400 HandleNotAvailable: Handle already taken"
+        })),
         (status = 429, description = "Rate limit exceeded", body = ApiErrorBody, content_type = "application/json", example = json!({
             "code": "RATE_LIMIT",
             "message": "Rate limit reached. Please try again later."
@@ -67,7 +72,7 @@ impl fmt::Debug for CreateAccountApiRequest {
     ),
     tag = "pdsmigration-web"
 )]
-#[tracing::instrument(skip(req), fields(
+#[tracing::instrument(skip(req, config), fields(
     email = %req.email,
     handle = %req.handle,
     pds_host = %req.pds_host,
@@ -76,6 +81,7 @@ impl fmt::Debug for CreateAccountApiRequest {
 #[post("/create-account")]
 pub async fn create_account_api(
     req: Json<CreateAccountApiRequest>,
+    config: Data<AppConfig>,
 ) -> Result<HttpResponse, ApiError> {
     tracing::info!("Create account request received");
     let req = req.into_inner();
@@ -102,6 +108,7 @@ pub async fn create_account_api(
             plc_op: None,
             token: Some(req.token.clone()),
         },
+        &config.server.migration_options().retry,
     )
     .await
     .map_err(ApiError::from)?;
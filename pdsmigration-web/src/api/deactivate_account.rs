@@ -1,12 +1,14 @@
+use crate::config::AppConfig;
 use crate::errors::{ApiError, ApiErrorBody};
 use crate::post;
-use actix_web::web::Json;
+use actix_web::web::{Data, Json};
 use actix_web::HttpResponse;
 use pdsmigration_common::DeactivateAccountRequest;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use utoipa::ToSchema;
 
-#[derive(Debug, Deserialize, Serialize, ToSchema)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct DeactivateAccountApiRequest {
     #[schema(example = "https://pds.example.com")]
     pub pds_host: String,
@@ -14,6 +16,31 @@ pub struct DeactivateAccountApiRequest {
     pub did: String,
     #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
     pub token: String,
+    #[serde(default, skip_serializing_if = "core::option::Option::is_none")]
+    #[schema(example = "https://destinationPDS.example.com")]
+    pub destination: Option<String>,
+    #[serde(default, skip_serializing_if = "core::option::Option::is_none")]
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
+    pub destination_token: Option<String>,
+    #[serde(default)]
+    #[schema(example = false)]
+    pub skip_verification: bool,
+}
+
+impl fmt::Debug for DeactivateAccountApiRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeactivateAccountApiRequest")
+            .field("pds_host", &self.pds_host)
+            .field("did", &self.did)
+            .field("token", &"[REDACTED]")
+            .field("destination", &self.destination)
+            .field(
+                "destination_token",
+                &self.destination_token.as_ref().map(|_| "[REDACTED]"),
+            )
+            .field("skip_verification", &self.skip_verification)
+            .finish()
+    }
 }
 
 impl From<DeactivateAccountApiRequest> for DeactivateAccountRequest {
@@ -22,6 +49,9 @@ impl From<DeactivateAccountApiRequest> for DeactivateAccountRequest {
             pds_host: req.pds_host,
             did: req.did,
             token: req.token,
+            destination: req.destination,
+            destination_token: req.destination_token,
+            skip_verification: req.skip_verification,
         }
     }
 }
@@ -34,17 +64,24 @@ impl From<DeactivateAccountApiRequest> for DeactivateAccountRequest {
         (status = 200, description = "Account deactivated successfully"),
         (status = 400, description = "Invalid request", body = ApiErrorBody, content_type = "application/json"),
         (status = 401, description = "Authentication error", body = ApiErrorBody, content_type = "application/json"),
+        (status = 409, description = "The destination does not hold everything the origin does yet", body = ApiErrorBody, content_type = "application/json"),
         (status = 429, description = "Rate limit exceeded", body = ApiErrorBody, content_type = "application/json")
     ),
     tag = "pdsmigration-web"
 )]
-#[tracing::instrument(skip(req), fields(did = %req.did, pds_host = %req.pds_host))]
+#[tracing::instrument(skip(req, config), fields(did = %req.did, pds_host = %req.pds_host))]
 #[post("/deactivate-account")]
 pub async fn deactivate_account_api(
     req: Json<DeactivateAccountApiRequest>,
+    config: Data<AppConfig>,
 ) -> Result<HttpResponse, ApiError> {
     tracing::info!("Deactivate account request received");
     let req = req.into_inner();
-    pdsmigration_common::deactivate_account_api(req.into()).await?;
+    pdsmigration_common::deactivate_account_api(
+        req.into(),
+        &config.server.identity_resolver(),
+        &config.server.migration_options(),
+    )
+    .await?;
     Ok(HttpResponse::Ok().finish())
 }
@@ -1,10 +1,13 @@
+use crate::config::{remove_staging, AppConfig};
 use crate::errors::{ApiError, ApiErrorBody};
 use crate::post;
 use crate::Json;
+use actix_web::web::Data;
 use actix_web::HttpResponse;
 use pdsmigration_common::{ExportBlobsRequest, ExportBlobsResponse};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
+use uuid::Uuid;
 
 #[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct ExportBlobsApiRequest {
@@ -18,6 +21,9 @@ pub struct ExportBlobsApiRequest {
     pub origin_token: String,
     #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
     pub destination_token: String,
+    #[serde(default)]
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
+    pub origin_refresh_token: Option<String>,
 }
 
 impl From<ExportBlobsApiRequest> for ExportBlobsRequest {
@@ -28,21 +34,28 @@ impl From<ExportBlobsApiRequest> for ExportBlobsRequest {
             did: req.did,
             origin_token: req.origin_token,
             destination_token: req.destination_token,
+            origin_refresh_token: req.origin_refresh_token,
         }
     }
 }
 
 #[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct ExportBlobsApiResponse {
+    /// Pass to `/upload-blobs` to upload the blobs this export staged.
+    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
+    pub staging_id: String,
     pub successful_blobs: Vec<String>,
     pub invalid_blobs: Vec<String>,
+    pub corrupt_blobs: Vec<String>,
 }
 
-impl From<ExportBlobsResponse> for ExportBlobsApiResponse {
-    fn from(req: ExportBlobsResponse) -> Self {
+impl ExportBlobsApiResponse {
+    fn new(staging_id: Uuid, req: ExportBlobsResponse) -> Self {
         Self {
+            staging_id: staging_id.to_string(),
             successful_blobs: req.successful_blobs,
             invalid_blobs: req.invalid_blobs,
+            corrupt_blobs: req.corrupt_blobs,
         }
     }
 }
@@ -59,13 +72,31 @@ impl From<ExportBlobsResponse> for ExportBlobsApiResponse {
     ),
     tag = "pdsmigration-web"
 )]
-#[tracing::instrument(skip(req))]
+#[tracing::instrument(skip(req, config))]
 #[post("/export-blobs")]
-pub async fn export_blobs_api(req: Json<ExportBlobsApiRequest>) -> Result<HttpResponse, ApiError> {
+pub async fn export_blobs_api(
+    req: Json<ExportBlobsApiRequest>,
+    config: Data<AppConfig>,
+) -> Result<HttpResponse, ApiError> {
     tracing::info!("Export blobs request received");
     let req = req.into_inner();
-    let result = pdsmigration_common::export_blobs_api(req.into()).await?;
+    let staging_id = Uuid::new_v4();
+    let store = config.server.staging_store(&staging_id);
+    let result = match pdsmigration_common::export_blobs_api(
+        req.into(),
+        &store,
+        &config.server.migration_options(),
+    )
+    .await
+    {
+        Ok(result) => result,
+        Err(error) => {
+            // Nothing can upload from a failed export, so its staging goes right away
+            remove_staging(&store).await;
+            return Err(error.into());
+        }
+    };
     tracing::info!("Blobs exported successfully");
-    let result: ExportBlobsApiResponse = result.into();
+    let result = ExportBlobsApiResponse::new(staging_id, result);
     Ok(HttpResponse::Ok().json(result))
 }
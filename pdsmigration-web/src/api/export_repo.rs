@@ -1,8 +1,9 @@
+use crate::config::AppConfig;
 use crate::errors::{ApiError, ApiErrorBody};
 use crate::post;
-use actix_web::web::Json;
+use actix_web::web::{Data, Json};
 use actix_web::HttpResponse;
-use pdsmigration_common::ExportPDSRequest;
+use pdsmigration_common::{repo_key, ExportPDSRequest, StagingStore, TempStagingStore};
 use serde::{Deserialize, Serialize};
 use std::env;
 use utoipa::ToSchema;
@@ -39,21 +40,27 @@ impl From<ExportPDSApiRequest> for ExportPDSRequest {
     ),
     tag = "pdsmigration-web"
 )]
-#[tracing::instrument(skip(req))]
+#[tracing::instrument(skip(req, config))]
 #[post("/export-repo")]
-pub async fn export_pds_api(req: Json<ExportPDSApiRequest>) -> Result<HttpResponse, ApiError> {
+pub async fn export_pds_api(
+    req: Json<ExportPDSApiRequest>,
+    config: Data<AppConfig>,
+) -> Result<HttpResponse, ApiError> {
     tracing::info!("Export repository request received");
-    // Download the repository file locally
+    // Download the repository into a workspace private to this request
     let req_inner = req.into_inner();
     let did = req_inner.did.clone();
-    pdsmigration_common::export_pds_api(req_inner.into())
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to export repository: {}", e);
-            ApiError::Runtime {
-                message: e.to_string(),
-            }
-        })?;
+    let store = TempStagingStore::new()?;
+    pdsmigration_common::export_pds_api(
+        req_inner.into(),
+        &store,
+        &config.server.migration_options(),
+    )
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to export repository: {}", e);
+        ApiError::from(e)
+    })?;
 
     // Upload the downloaded file to AWS S3
     let endpoint_url = env::var("ENDPOINT").map_err(|e| {
@@ -64,16 +71,16 @@ pub async fn export_pds_api(req: Json<ExportPDSApiRequest>) -> Result<HttpRespon
     })?;
 
     tracing::debug!("Loading AWS config with endpoint: {}", endpoint_url);
-    let config = aws_config::from_env()
+    let aws_config = aws_config::from_env()
         .region("auto")
         .endpoint_url(&endpoint_url)
         .load()
         .await;
-    let client = aws_sdk_s3::Client::new(&config);
+    let client = aws_sdk_s3::Client::new(&aws_config);
 
     let bucket_name = "migration".to_string();
-    let file_name = did.replace(":", "-") + ".car";
-    let key = "migration/".to_string() + &did.replace(":", "-") + ".car";
+    let file_name = repo_key(&did);
+    let key = "migration/".to_string() + &file_name;
 
     tracing::debug!(
         "Uploading file {} to S3 bucket {} with key {}",
@@ -82,19 +89,13 @@ pub async fn export_pds_api(req: Json<ExportPDSApiRequest>) -> Result<HttpRespon
         key
     );
 
-    let body = match aws_sdk_s3::primitives::ByteStream::from_path(std::path::Path::new(&file_name))
-        .await
-    {
+    let body = match store.get(&file_name).await {
         Ok(body) => {
-            tracing::debug!("Successfully created ByteStream from file");
-            body
+            tracing::debug!("Successfully read exported repository");
+            aws_sdk_s3::primitives::ByteStream::from(body)
         }
         Err(e) => {
-            tracing::error!(
-                "Failed to create ByteStream from file {}: {:?}",
-                file_name,
-                e
-            );
+            tracing::error!("Failed to read exported repository {}: {:?}", file_name, e);
             return Err(ApiError::Runtime {
                 message: e.to_string(),
             });
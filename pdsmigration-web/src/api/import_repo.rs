@@ -3,7 +3,9 @@ use crate::errors::{ApiError, ApiErrorBody};
 use crate::post;
 use actix_web::web::{Data, Json};
 use actix_web::HttpResponse;
-use pdsmigration_common::ImportPDSRequest;
+use pdsmigration_common::{
+    repo_key, repo_rev_key, ImportPDSRequest, MemoryStagingStore, StagingStore,
+};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
@@ -39,7 +41,7 @@ impl From<ImportPDSApiRequest> for ImportPDSRequest {
     ),
     tag = "pdsmigration-web"
 )]
-#[tracing::instrument(skip(req))]
+#[tracing::instrument(skip(req, config))]
 #[post("/import-repo")]
 pub async fn import_pds_api(
     req: Json<ImportPDSApiRequest>,
@@ -47,19 +49,18 @@ pub async fn import_pds_api(
 ) -> Result<HttpResponse, ApiError> {
     tracing::info!("Import repository request received");
     let endpoint_url = config.external_services.s3_endpoint.clone();
-    let config = aws_config::from_env()
+    let aws_config = aws_config::from_env()
         .region("auto")
         .endpoint_url(&endpoint_url)
         .load()
         .await;
-    let client = aws_sdk_s3::Client::new(&config);
+    let client = aws_sdk_s3::Client::new(&aws_config);
 
     let req_inner = req.into_inner();
     let did = req_inner.did.clone();
 
     let bucket_name = "migration".to_string();
-    let file_name = did.replace(":", "-") + ".car";
-    let key = "migration/".to_string() + &did.replace(":", "-") + ".car";
+    let key = "migration/".to_string() + &repo_key(&did);
 
     // Download the file from S3
     let s3_response = client
@@ -72,7 +73,7 @@ pub async fn import_pds_api(
             message: error.to_string(),
         })?;
 
-    // Save the file locally using AWS SDK's built-in method
+    // Stage the repository in memory for this request only
     let body_bytes = s3_response
         .body
         .collect()
@@ -81,11 +82,30 @@ pub async fn import_pds_api(
             message: error.to_string(),
         })?;
 
-    std::fs::write(&file_name, body_bytes.into_bytes()).map_err(|error| ApiError::Runtime {
-        message: error.to_string(),
-    })?;
-    pdsmigration_common::import_pds_api(req_inner.into()).await?;
+    let store = MemoryStagingStore::new();
+    store
+        .put(&repo_key(&did), body_bytes.into_bytes().to_vec())
+        .await?;
+    pdsmigration_common::import_pds_api(
+        req_inner.into(),
+        &store,
+        &config.server.migration_options(),
+    )
+    .await?;
     tracing::info!("Repository imported successfully");
 
+    // Keep the imported rev next to the repo so /catch-up-repo knows where to start
+    let rev = store.get(&repo_rev_key(&did)).await?;
+    client
+        .put_object()
+        .bucket(&bucket_name)
+        .key("migration/".to_string() + &repo_rev_key(&did))
+        .body(aws_sdk_s3::primitives::ByteStream::from(rev))
+        .send()
+        .await
+        .map_err(|error| ApiError::Runtime {
+            message: error.to_string(),
+        })?;
+
     Ok(HttpResponse::Ok().finish())
 }
@@ -1,10 +1,12 @@
 use crate::api::ExportBlobsApiRequest;
 use crate::background_jobs::{JobManager, JobRecord};
+use crate::config::AppConfig;
 use crate::errors::{ApiError, ApiErrorBody};
 use crate::{post, Json};
 use actix_web::{get, web, HttpResponse};
 use pdsmigration_common::ExportBlobsRequest;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
@@ -26,15 +28,22 @@ pub struct EnqueueJobResponse {
     ),
     tag = "pdsmigration-web"
 )]
-#[tracing::instrument(skip(jobs, req))]
+#[tracing::instrument(skip(jobs, req, config))]
 #[post("/jobs/export-blobs")]
 pub async fn enqueue_export_blobs_job_api(
     jobs: web::Data<JobManager>,
     req: Json<ExportBlobsApiRequest>,
+    config: web::Data<AppConfig>,
 ) -> Result<HttpResponse, ApiError> {
-    let id = jobs
-        .spawn_export_blobs(ExportBlobsRequest::from(req.into_inner()))
-        .await?;
+    let id = Uuid::new_v4();
+    let store = Arc::new(config.server.staging_store(&id));
+    jobs.spawn_export_blobs(
+        id,
+        ExportBlobsRequest::from(req.into_inner()),
+        store,
+        config.server.migration_options(),
+    )
+    .await?;
     Ok(HttpResponse::Accepted().json(EnqueueJobResponse {
         job_id: id.to_string(),
     }))
@@ -98,7 +107,10 @@ pub async fn cancel_job_api(
                     "successful_blobs_ids": ["550e8400-e29b-41d4-a716-446655440000"],
                     "invalid_blobs": 1,
                     "invalid_blob_ids": ["550e8400-e29b-41d4-a716-446655440001"],
-                    "total": 2
+                    "corrupt_blobs": 0,
+                    "corrupt_blob_ids": [],
+                    "total": 2,
+                    "bytes_transferred": 2048
                 }
             })
         ),
@@ -124,7 +136,9 @@ pub async fn get_job_api(
                     job_status = ?job.status,
                     successful_blobs = progress.successful_blobs,
                     invalid_blobs = progress.invalid_blobs,
+                    corrupt_blobs = progress.corrupt_blobs,
                     total = progress.total,
+                    bytes_transferred = progress.bytes_transferred,
                     "Job found, logging progress"
                 );
             }
@@ -0,0 +1,96 @@
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiErrorBody};
+use crate::post;
+use actix_web::web::{Data, Json};
+use actix_web::HttpResponse;
+use pdsmigration_common::{build_agent, login_with_password, LoginRequest};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct LoginApiRequest {
+    #[schema(example = "https://pds.example.com")]
+    pub pds_host: String,
+    #[schema(example = "alice.bsky.social")]
+    pub identifier: String,
+    #[schema(example = "app-password-or-password")]
+    pub password: String,
+    #[serde(default)]
+    #[schema(example = "ABCDE-12345")]
+    pub auth_factor_token: Option<String>,
+    #[serde(default)]
+    #[schema(example = false)]
+    pub allow_takendown: bool,
+}
+
+impl std::fmt::Debug for LoginApiRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoginApiRequest")
+            .field("pds_host", &self.pds_host)
+            .field("identifier", &self.identifier)
+            .field("password", &"[REDACTED]")
+            .field(
+                "auth_factor_token",
+                &self.auth_factor_token.as_ref().map(|_| "[REDACTED]"),
+            )
+            .field("allow_takendown", &self.allow_takendown)
+            .finish()
+    }
+}
+
+impl From<LoginApiRequest> for LoginRequest {
+    fn from(req: LoginApiRequest) -> Self {
+        Self {
+            pds_host: req.pds_host,
+            identifier: req.identifier,
+            password: req.password,
+            auth_factor_token: req.auth_factor_token,
+            allow_takendown: req.allow_takendown,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct LoginApiResponse {
+    #[schema(example = "did:plc:abcd1234efgh5678ijkl")]
+    pub did: String,
+    #[schema(example = "alice.bsky.social")]
+    pub handle: String,
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
+    pub access_token: String,
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
+    pub refresh_token: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/login",
+    request_body = LoginApiRequest,
+    responses(
+        (status = 200, description = "Logged in successfully", body = LoginApiResponse, content_type = "application/json"),
+        (status = 400, description = "Invalid request", body = ApiErrorBody, content_type = "application/json"),
+        (status = 401, description = "Invalid credentials, account taken down, or auth factor token required", body = ApiErrorBody, content_type = "application/json"),
+        (status = 429, description = "Rate limit exceeded", body = ApiErrorBody, content_type = "application/json")
+    ),
+    tag = "pdsmigration-web"
+)]
+#[tracing::instrument(skip(req, config))]
+#[post("/login")]
+pub async fn login_api(
+    req: Json<LoginApiRequest>,
+    config: Data<AppConfig>,
+) -> Result<HttpResponse, ApiError> {
+    tracing::info!("Login request received");
+    let req: LoginRequest = req.into_inner().into();
+    let agent = build_agent(&config.server.migration_options().retry).await?;
+    let session = login_with_password(&agent, &req).await.map_err(|e| {
+        tracing::error!("Failed to log in: {}", e);
+        ApiError::from(e)
+    })?;
+    Ok(HttpResponse::Ok().json(LoginApiResponse {
+        did: session.did.as_str().to_string(),
+        handle: session.handle.as_str().to_string(),
+        access_token: session.access_jwt.clone(),
+        refresh_token: session.refresh_jwt.clone(),
+    }))
+}
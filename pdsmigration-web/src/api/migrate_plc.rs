@@ -1,6 +1,7 @@
+use crate::config::AppConfig;
 use crate::errors::{ApiError, ApiErrorBody};
 use crate::post;
-use actix_web::web::Json;
+use actix_web::web::{Data, Json};
 use actix_web::HttpResponse;
 use pdsmigration_common::MigratePlcRequest;
 use serde::{Deserialize, Serialize};
@@ -50,11 +51,14 @@ impl From<MigratePlcApiRequest> for MigratePlcRequest {
     ),
     tag = "pdsmigration-web"
 )]
-#[tracing::instrument(skip(req))]
+#[tracing::instrument(skip(req, config))]
 #[post("/migrate-plc")]
-pub async fn migrate_plc_api(req: Json<MigratePlcApiRequest>) -> Result<HttpResponse, ApiError> {
+pub async fn migrate_plc_api(
+    req: Json<MigratePlcApiRequest>,
+    config: Data<AppConfig>,
+) -> Result<HttpResponse, ApiError> {
     tracing::info!("Migrate PLC request received");
     let req = req.into_inner();
-    pdsmigration_common::migrate_plc_api(req.into()).await?;
+    pdsmigration_common::migrate_plc_api(req.into(), &config.server.migration_options()).await?;
     Ok(HttpResponse::Ok().finish())
 }
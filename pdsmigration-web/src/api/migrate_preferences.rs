@@ -1,6 +1,7 @@
+use crate::config::AppConfig;
 use crate::errors::{ApiError, ApiErrorBody};
 use crate::post;
-use actix_web::web::Json;
+use actix_web::web::{Data, Json};
 use actix_web::HttpResponse;
 use pdsmigration_common::MigratePreferencesRequest;
 use serde::{Deserialize, Serialize};
@@ -44,13 +45,15 @@ impl From<MigratePreferencesApiRequest> for MigratePreferencesRequest {
     ),
     tag = "pdsmigration-web"
 )]
-#[tracing::instrument(skip(req))]
+#[tracing::instrument(skip(req, config))]
 #[post("/migrate-preferences")]
 pub async fn migrate_preferences_api(
     req: Json<MigratePreferencesApiRequest>,
+    config: Data<AppConfig>,
 ) -> Result<HttpResponse, ApiError> {
     tracing::info!("Migrate preferences request received");
     let req = req.into_inner();
-    pdsmigration_common::migrate_preferences_api(req.into()).await?;
+    pdsmigration_common::migrate_preferences_api(req.into(), &config.server.migration_options())
+        .await?;
     Ok(HttpResponse::Ok().finish())
 }
@@ -1,6 +1,7 @@
+use crate::config::AppConfig;
 use crate::errors::{ApiError, ApiErrorBody};
 use crate::{post, APPLICATION_JSON};
-use actix_web::web::Json;
+use actix_web::web::{Data, Json};
 use actix_web::HttpResponse;
 use pdsmigration_common::{MissingBlobsRequest, MissingBlobsResponse};
 use serde::{Deserialize, Serialize};
@@ -51,14 +52,17 @@ impl From<MissingBlobsResponse> for MissingBlobsApiResponse {
     ),
     tag = "pdsmigration-web"
 )]
-#[tracing::instrument(skip(req))]
+#[tracing::instrument(skip(req, config))]
 #[post("/missing-blobs")]
 pub async fn missing_blobs_api(
     req: Json<MissingBlobsApiRequest>,
+    config: Data<AppConfig>,
 ) -> Result<HttpResponse, ApiError> {
     tracing::info!("Missing blobs request received");
     let req = req.into_inner();
-    let response = pdsmigration_common::missing_blobs_api(req.into()).await?;
+    let response =
+        pdsmigration_common::missing_blobs_api(req.into(), &config.server.migration_options())
+            .await?;
     let response: MissingBlobsApiResponse = response.into();
     Ok(HttpResponse::Ok()
         .content_type(APPLICATION_JSON)
@@ -1,4 +1,5 @@
 mod activate_account;
+mod catch_up_repo;
 mod create_account;
 mod deactivate_account;
 mod export_blobs;
@@ -6,14 +7,21 @@ mod export_repo;
 mod health;
 mod import_repo;
 mod jobs;
+mod login;
 mod migrate_plc;
 mod migrate_preferences;
 mod missing_blobs;
+mod preflight;
+mod preview_plc;
 mod request_token;
+mod resolve_identity;
 mod service_auth;
+mod transfer_blobs;
 mod upload_blobs;
+mod verify_migration;
 
 pub use activate_account::*;
+pub use catch_up_repo::*;
 pub use create_account::*;
 pub use deactivate_account::*;
 pub use export_blobs::*;
@@ -21,9 +29,15 @@ pub use export_repo::*;
 pub use health::*;
 pub use import_repo::*;
 pub use jobs::*;
+pub use login::*;
 pub use migrate_plc::*;
 pub use migrate_preferences::*;
 pub use missing_blobs::*;
+pub use preflight::*;
+pub use preview_plc::*;
 pub use request_token::*;
+pub use resolve_identity::*;
 pub use service_auth::*;
+pub use transfer_blobs::*;
 pub use upload_blobs::*;
+pub use verify_migration::*;
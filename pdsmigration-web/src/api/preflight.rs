@@ -0,0 +1,136 @@
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiErrorBody};
+use crate::post;
+use actix_web::web::{Data, Json};
+use actix_web::HttpResponse;
+use pdsmigration_common::{PreflightIssue, PreflightReport, PreflightRequest};
+use serde::{Deserialize, Serialize};
+#[allow(unused_imports)] // Used in schema attribute macros
+use serde_json::json;
+use std::fmt;
+use utoipa::ToSchema;
+
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct PreflightApiRequest {
+    #[schema(example = "https://sourcePDS.example.com")]
+    pub origin: String,
+    #[schema(example = "https://destinationPDS.example.com")]
+    pub destination: String,
+    #[schema(example = "did:plc:abcd1234efgh5678ijkl")]
+    pub did: String,
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
+    pub origin_token: String,
+    #[serde(default, skip_serializing_if = "core::option::Option::is_none")]
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
+    pub destination_token: Option<String>,
+    #[serde(default, skip_serializing_if = "core::option::Option::is_none")]
+    #[schema(example = "alice.destinationPDS.example.com")]
+    pub handle: Option<String>,
+    #[serde(default, skip_serializing_if = "core::option::Option::is_none")]
+    #[schema(example = "bsky-invite-abc123-xyz789")]
+    pub invite_code: Option<String>,
+}
+
+impl fmt::Debug for PreflightApiRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PreflightApiRequest")
+            .field("origin", &self.origin)
+            .field("destination", &self.destination)
+            .field("did", &self.did)
+            .field("origin_token", &"[REDACTED]")
+            .field(
+                "destination_token",
+                &self.destination_token.as_ref().map(|_| "[REDACTED]"),
+            )
+            .field("handle", &self.handle)
+            .field(
+                "invite_code",
+                &self.invite_code.as_ref().map(|_| "[REDACTED]"),
+            )
+            .finish()
+    }
+}
+
+impl From<PreflightApiRequest> for PreflightRequest {
+    fn from(req: PreflightApiRequest) -> Self {
+        Self {
+            origin: req.origin,
+            destination: req.destination,
+            did: req.did,
+            origin_token: req.origin_token,
+            destination_token: req.destination_token,
+            handle: req.handle,
+            invite_code: req.invite_code,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct PreflightApiIssue {
+    #[schema(example = "invite_code")]
+    pub check: String,
+    #[schema(example = "The destination requires an invite code")]
+    pub message: String,
+}
+
+impl From<PreflightIssue> for PreflightApiIssue {
+    fn from(issue: PreflightIssue) -> Self {
+        Self {
+            check: serde_json::to_value(issue.check)
+                .ok()
+                .and_then(|check| check.as_str().map(str::to_string))
+                .unwrap_or_default(),
+            message: issue.message,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct PreflightApiResponse {
+    #[schema(example = false)]
+    pub ready: bool,
+    pub blockers: Vec<PreflightApiIssue>,
+    pub warnings: Vec<PreflightApiIssue>,
+    #[schema(example = true)]
+    pub invite_code_required: bool,
+    #[schema(example = json!([".destinationPDS.example.com"]))]
+    pub available_user_domains: Vec<String>,
+}
+
+impl From<PreflightReport> for PreflightApiResponse {
+    fn from(report: PreflightReport) -> Self {
+        Self {
+            ready: report.ready(),
+            blockers: report.blockers.into_iter().map(Into::into).collect(),
+            warnings: report.warnings.into_iter().map(Into::into).collect(),
+            invite_code_required: report.invite_code_required,
+            available_user_domains: report.available_user_domains,
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/preflight",
+    request_body = PreflightApiRequest,
+    responses(
+        (status = 200, description = "Readiness report; blockers are returned here, not as errors", body = PreflightApiResponse, content_type = "application/json"),
+        (status = 400, description = "Invalid request", body = ApiErrorBody, content_type = "application/json"),
+        (status = 429, description = "Rate limit exceeded", body = ApiErrorBody, content_type = "application/json"),
+    ),
+    tag = "pdsmigration-web"
+)]
+#[tracing::instrument(skip(req, config))]
+#[post("/preflight")]
+pub async fn preflight_api(
+    req: Json<PreflightApiRequest>,
+    config: Data<AppConfig>,
+) -> Result<HttpResponse, ApiError> {
+    tracing::info!("Preflight request received");
+    let report = pdsmigration_common::preflight_api(
+        req.into_inner().into(),
+        &config.server.migration_options(),
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(PreflightApiResponse::from(report)))
+}
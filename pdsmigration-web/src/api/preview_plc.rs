@@ -0,0 +1,139 @@
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiErrorBody};
+use crate::post;
+use actix_web::web::{Data, Json};
+use actix_web::HttpResponse;
+use pdsmigration_common::{PlcDirectory, PlcFieldChange, PlcPreview, PreviewPlcRequest};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use utoipa::ToSchema;
+
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct PreviewPlcApiRequest {
+    #[schema(example = "https://destinationPDS.example.com")]
+    pub destination: String,
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
+    pub destination_token: String,
+    #[schema(example = "https://sourcePDS.example.com")]
+    pub origin: String,
+    #[schema(example = "did:plc:abcd1234efgh5678ijkl")]
+    pub did: String,
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
+    pub origin_token: String,
+    #[serde(skip_serializing_if = "core::option::Option::is_none")]
+    pub user_recovery_key: Option<String>,
+}
+
+impl fmt::Debug for PreviewPlcApiRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PreviewPlcApiRequest")
+            .field("destination", &self.destination)
+            .field("destination_token", &"[REDACTED]")
+            .field("origin", &self.origin)
+            .field("did", &self.did)
+            .field("origin_token", &"[REDACTED]")
+            .field("user_recovery_key", &self.user_recovery_key)
+            .finish()
+    }
+}
+
+impl From<PreviewPlcApiRequest> for PreviewPlcRequest {
+    fn from(req: PreviewPlcApiRequest) -> Self {
+        Self {
+            destination: req.destination,
+            destination_token: req.destination_token,
+            origin: req.origin,
+            did: req.did,
+            origin_token: req.origin_token,
+            user_recovery_key: req.user_recovery_key,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct PlcFieldApiChange {
+    #[schema(example = "https://sourcePDS.example.com")]
+    pub from: Option<String>,
+    #[schema(example = "https://destinationPDS.example.com")]
+    pub to: Option<String>,
+}
+
+impl From<PlcFieldChange> for PlcFieldApiChange {
+    fn from(change: PlcFieldChange) -> Self {
+        Self {
+            from: change.from,
+            to: change.to,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct PreviewPlcApiResponse {
+    #[schema(example = json!(["did:key:zQ3shZc2QzApp2oymGvQbzP8eKheVshBHbU4ZYjeXqwSKEn6N"]))]
+    pub rotation_keys_added: Vec<String>,
+    #[schema(example = json!(["did:key:zQ3shhCGUqDKjStzuDxPkTxN6ujddP4RkEKJJouJGRRkaLGbg"]))]
+    pub rotation_keys_removed: Vec<String>,
+    #[schema(example = false)]
+    pub rotation_keys_reordered: bool,
+    #[serde(skip_serializing_if = "core::option::Option::is_none")]
+    pub signing_key: Option<PlcFieldApiChange>,
+    #[serde(skip_serializing_if = "core::option::Option::is_none")]
+    pub also_known_as: Option<PlcFieldApiChange>,
+    #[serde(skip_serializing_if = "core::option::Option::is_none")]
+    pub pds_endpoint: Option<PlcFieldApiChange>,
+    /// Changes that could cost the user control of their DID.
+    #[schema(example = json!(["Rotation key did:key:zQ3shhCGUqDKjStzuDxPkTxN6ujddP4RkEKJJouJGRRkaLGbg is removed and can no longer recover the DID"]))]
+    pub warnings: Vec<String>,
+    /// The diff as text, one change per line.
+    #[schema(
+        example = "~ PDS: https://sourcePDS.example.com -> https://destinationPDS.example.com\n"
+    )]
+    pub summary: String,
+}
+
+impl From<PlcPreview> for PreviewPlcApiResponse {
+    fn from(preview: PlcPreview) -> Self {
+        let summary = preview.diff.to_string();
+        let diff = preview.diff;
+        Self {
+            rotation_keys_added: diff.rotation_keys_added,
+            rotation_keys_removed: diff.rotation_keys_removed,
+            rotation_keys_reordered: diff.rotation_keys_reordered,
+            signing_key: diff.signing_key.map(Into::into),
+            also_known_as: diff.also_known_as.map(Into::into),
+            pds_endpoint: diff.pds_endpoint.map(Into::into),
+            warnings: diff.warnings.iter().map(ToString::to_string).collect(),
+            summary,
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/preview-plc",
+    request_body = PreviewPlcApiRequest,
+    responses(
+        (status = 200, description = "Changes migrating the PLC would make; nothing is signed", body = PreviewPlcApiResponse, content_type = "application/json"),
+        (status = 400, description = "Invalid request", body = ApiErrorBody, content_type = "application/json"),
+        (status = 401, description = "Authentication error", body = ApiErrorBody, content_type = "application/json"),
+        (status = 409, description = "The DID's audit log failed verification", body = ApiErrorBody, content_type = "application/json"),
+        (status = 429, description = "Rate limit exceeded", body = ApiErrorBody, content_type = "application/json"),
+    ),
+    tag = "pdsmigration-web"
+)]
+#[tracing::instrument(skip(req, config), fields(did = %req.did))]
+#[post("/preview-plc")]
+pub async fn preview_plc_api(
+    req: Json<PreviewPlcApiRequest>,
+    config: Data<AppConfig>,
+) -> Result<HttpResponse, ApiError> {
+    tracing::info!("Preview PLC request received");
+    let options = config.server.migration_options();
+    let directory = PlcDirectory {
+        retry: options.retry.clone(),
+        ..PlcDirectory::default()
+    };
+    let preview =
+        pdsmigration_common::preview_plc_api(req.into_inner().into(), &directory, &options).await?;
+    Ok(HttpResponse::Ok().json(PreviewPlcApiResponse::from(preview)))
+}
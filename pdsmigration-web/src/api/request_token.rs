@@ -1,6 +1,7 @@
+use crate::config::AppConfig;
 use crate::errors::{ApiError, ApiErrorBody};
 use crate::post;
-use actix_web::web::Json;
+use actix_web::web::{Data, Json};
 use actix_web::HttpResponse;
 use pdsmigration_common::RequestTokenRequest;
 use serde::{Deserialize, Serialize};
@@ -38,14 +39,15 @@ impl From<RequestTokenApiRequest> for RequestTokenRequest {
     ),
     tag = "pdsmigration-web"
 )]
-#[tracing::instrument(skip(req))]
+#[tracing::instrument(skip(req, config))]
 #[post("/request-token")]
 pub async fn request_token_api(
     req: Json<RequestTokenApiRequest>,
+    config: Data<AppConfig>,
 ) -> Result<HttpResponse, ApiError> {
     tracing::info!("Request token request received");
     let req = req.into_inner();
-    pdsmigration_common::request_token_api(req.into())
+    pdsmigration_common::request_token_api(req.into(), &config.server.migration_options())
         .await
         .map_err(|e| {
             tracing::error!("Failed to request token: {}", e);
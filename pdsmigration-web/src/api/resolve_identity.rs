@@ -0,0 +1,63 @@
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiErrorBody};
+use crate::post;
+use actix_web::web::{Data, Json};
+use actix_web::HttpResponse;
+use pdsmigration_common::ResolvedIdentity;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ResolveIdentityApiRequest {
+    /// A handle or a DID.
+    #[schema(example = "alice.example.com")]
+    pub identifier: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ResolveIdentityApiResponse {
+    #[schema(example = "did:plc:abcd1234efgh5678ijkl")]
+    pub did: String,
+    #[serde(skip_serializing_if = "core::option::Option::is_none")]
+    #[schema(example = "alice.example.com")]
+    pub handle: Option<String>,
+    #[schema(example = "https://pds.example.com")]
+    pub pds_host: String,
+}
+
+impl From<ResolvedIdentity> for ResolveIdentityApiResponse {
+    fn from(identity: ResolvedIdentity) -> Self {
+        Self {
+            did: identity.did.as_str().to_string(),
+            handle: identity.handle,
+            pds_host: identity.pds_host,
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/resolve-identity",
+    request_body = ResolveIdentityApiRequest,
+    responses(
+        (status = 200, description = "DID and PDS host of the account", body = ResolveIdentityApiResponse, content_type = "application/json"),
+        (status = 400, description = "Invalid request or unconfirmed handle", body = ApiErrorBody, content_type = "application/json"),
+        (status = 404, description = "Handle or DID does not resolve", body = ApiErrorBody, content_type = "application/json"),
+        (status = 429, description = "Rate limit exceeded", body = ApiErrorBody, content_type = "application/json"),
+    ),
+    tag = "pdsmigration-web"
+)]
+#[tracing::instrument(skip(config))]
+#[post("/resolve-identity")]
+pub async fn resolve_identity_api(
+    req: Json<ResolveIdentityApiRequest>,
+    config: Data<AppConfig>,
+) -> Result<HttpResponse, ApiError> {
+    tracing::info!("Resolve identity request received");
+    let identity = config
+        .server
+        .identity_resolver()
+        .resolve(&req.identifier)
+        .await?;
+    Ok(HttpResponse::Ok().json(ResolveIdentityApiResponse::from(identity)))
+}
@@ -1,6 +1,7 @@
+use crate::config::AppConfig;
 use crate::errors::{ApiError, ApiErrorBody};
 use crate::post;
-use actix_web::web::Json;
+use actix_web::web::{Data, Json};
 use actix_web::HttpResponse;
 use pdsmigration_common::ServiceAuthRequest;
 use serde::{Deserialize, Serialize};
@@ -46,14 +47,17 @@ struct ServiceAuthResponse {
     ),
     tag = "pdsmigration-web"
 )]
-#[tracing::instrument(skip(req), fields(did = %req.did, pds_host = %req.pds_host, aud = %req.aud))]
+#[tracing::instrument(skip(req, config), fields(did = %req.did, pds_host = %req.pds_host, aud = %req.aud))]
 #[post("/service-auth")]
 pub async fn get_service_auth_api(
     req: Json<ServiceAuthApiRequest>,
+    config: Data<AppConfig>,
 ) -> Result<HttpResponse, ApiError> {
     tracing::info!("Service auth request received");
     let req = req.into_inner();
-    let response = pdsmigration_common::get_service_auth_api(req.into()).await?;
+    let response =
+        pdsmigration_common::get_service_auth_api(req.into(), &config.server.migration_options())
+            .await?;
     let response = ServiceAuthResponse { token: response };
     Ok(HttpResponse::Ok().json(response))
 }
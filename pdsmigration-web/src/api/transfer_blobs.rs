@@ -0,0 +1,88 @@
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiErrorBody};
+use crate::post;
+use crate::Json;
+use actix_web::web::Data;
+use actix_web::HttpResponse;
+use pdsmigration_common::{TransferBlobsRequest, TransferBlobsResponse};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct TransferBlobsApiRequest {
+    #[schema(example = "https://sourcePDS.example.com")]
+    pub origin: String,
+    #[schema(example = "https://destinationPDS.example.com")]
+    pub destination: String,
+    #[schema(example = "did:plc:abcd1234efgh5678ijkl")]
+    pub did: String,
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
+    pub origin_token: String,
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
+    pub destination_token: String,
+    #[serde(default)]
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
+    pub origin_refresh_token: Option<String>,
+    #[serde(default)]
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
+    pub destination_refresh_token: Option<String>,
+}
+
+impl From<TransferBlobsApiRequest> for TransferBlobsRequest {
+    fn from(req: TransferBlobsApiRequest) -> Self {
+        Self {
+            origin: req.origin,
+            destination: req.destination,
+            did: req.did,
+            origin_token: req.origin_token,
+            destination_token: req.destination_token,
+            origin_refresh_token: req.origin_refresh_token,
+            destination_refresh_token: req.destination_refresh_token,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct TransferBlobsApiResponse {
+    pub successful_blobs: Vec<String>,
+    pub invalid_blobs: Vec<String>,
+    pub corrupt_blobs: Vec<String>,
+}
+
+impl From<TransferBlobsResponse> for TransferBlobsApiResponse {
+    fn from(res: TransferBlobsResponse) -> Self {
+        Self {
+            successful_blobs: res.successful_blobs,
+            invalid_blobs: res.invalid_blobs,
+            corrupt_blobs: res.corrupt_blobs,
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/transfer-blobs",
+    request_body = TransferBlobsApiRequest,
+    responses(
+        (status = 200, description = "Transfer Blobs completed successfully", body = TransferBlobsApiResponse, content_type = "application/json"),
+        (status = 400, description = "Invalid request", body = ApiErrorBody, content_type = "application/json"),
+        (status = 401, description = "Authentication error", body = ApiErrorBody, content_type = "application/json"),
+        (status = 429, description = "Rate limit exceeded", body = ApiErrorBody, content_type = "application/json"),
+    ),
+    tag = "pdsmigration-web"
+)]
+#[tracing::instrument(skip(req, config))]
+#[post("/transfer-blobs")]
+pub async fn transfer_blobs_api(
+    req: Json<TransferBlobsApiRequest>,
+    config: Data<AppConfig>,
+) -> Result<HttpResponse, ApiError> {
+    tracing::info!("Transfer blobs request received");
+    let req = req.into_inner();
+    let result =
+        pdsmigration_common::transfer_blobs_api(req.into(), &config.server.migration_options())
+            .await?;
+    tracing::info!("Blobs transferred successfully");
+    let result: TransferBlobsApiResponse = result.into();
+    Ok(HttpResponse::Ok().json(result))
+}
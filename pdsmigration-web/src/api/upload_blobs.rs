@@ -1,10 +1,12 @@
+use crate::config::{remove_staging, AppConfig};
 use crate::errors::{ApiError, ApiErrorBody};
 use crate::post;
-use actix_web::web::Json;
+use actix_web::web::{Data, Json};
 use actix_web::HttpResponse;
-use pdsmigration_common::{MigrationError, UploadBlobsRequest};
+use pdsmigration_common::{UploadBlobsRequest, UploadBlobsResponse};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
+use uuid::Uuid;
 
 #[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct UploadBlobsApiRequest {
@@ -14,6 +16,12 @@ pub struct UploadBlobsApiRequest {
     pub did: String,
     #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
     pub token: String,
+    #[serde(default)]
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
+    pub refresh_token: Option<String>,
+    /// The `staging_id` returned by `/export-blobs`, or the job id of an export job.
+    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
+    pub staging_id: String,
 }
 
 impl From<UploadBlobsApiRequest> for UploadBlobsRequest {
@@ -22,6 +30,24 @@ impl From<UploadBlobsApiRequest> for UploadBlobsRequest {
             pds_host: req.pds_host,
             did: req.did,
             token: req.token,
+            refresh_token: req.refresh_token,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct UploadBlobsApiResponse {
+    pub successful_blobs: Vec<String>,
+    pub invalid_blobs: Vec<String>,
+    pub corrupt_blobs: Vec<String>,
+}
+
+impl From<UploadBlobsResponse> for UploadBlobsApiResponse {
+    fn from(res: UploadBlobsResponse) -> Self {
+        Self {
+            successful_blobs: res.successful_blobs,
+            invalid_blobs: res.invalid_blobs,
+            corrupt_blobs: res.corrupt_blobs,
         }
     }
 }
@@ -31,37 +57,37 @@ impl From<UploadBlobsApiRequest> for UploadBlobsRequest {
     path = "/upload-blobs",
     request_body = UploadBlobsApiRequest,
     responses(
-        (status = 200, description = "Upload exported blobs successful"),
+        (status = 200, description = "Upload exported blobs successful", body = UploadBlobsApiResponse, content_type = "application/json"),
         (status = 400, description = "Invalid request", body = ApiErrorBody, content_type = "application/json"),
         (status = 401, description = "Authentication error", body = ApiErrorBody, content_type = "application/json"),
         (status = 429, description = "Rate limit exceeded", body = ApiErrorBody, content_type = "application/json")
     ),
     tag = "pdsmigration-web"
 )]
-#[tracing::instrument(skip(req))]
+#[tracing::instrument(skip(req, config))]
 #[post("/upload-blobs")]
-pub async fn upload_blobs_api(req: Json<UploadBlobsApiRequest>) -> Result<HttpResponse, ApiError> {
+pub async fn upload_blobs_api(
+    req: Json<UploadBlobsApiRequest>,
+    config: Data<AppConfig>,
+) -> Result<HttpResponse, ApiError> {
     tracing::info!("Upload blobs request received");
     let req = req.into_inner();
-    pdsmigration_common::upload_blobs_api(req.into())
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to upload blobs: {}", e);
-            match e {
-                MigrationError::Validation { .. } => ApiError::Runtime {
-                    message: "Unexpected error occurred".to_string(),
-                },
-                MigrationError::Upstream { .. } => ApiError::Runtime {
-                    message: "Unexpected error occurred".to_string(),
-                },
-                MigrationError::Runtime { .. } => ApiError::Runtime {
-                    message: "Unexpected error occurred".to_string(),
-                },
-                MigrationError::RateLimitReached => ApiError::Runtime {
-                    message: "Unexpected error occurred".to_string(),
-                },
-                MigrationError::Authentication { message } => ApiError::Authentication { message },
-            }
-        })?;
-    Ok(HttpResponse::Ok().finish())
+    let staging_id = Uuid::parse_str(&req.staging_id).map_err(|_| ApiError::Validation {
+        field: "staging_id".to_string(),
+    })?;
+    let store = config.server.staging_store(&staging_id);
+    let result = pdsmigration_common::upload_blobs_api(
+        req.into(),
+        &store,
+        &config.server.migration_options(),
+    )
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to upload blobs: {}", e);
+        ApiError::from(e)
+    })?;
+    // A failed upload keeps its staging so it can be retried until the sweep removes it
+    remove_staging(&store).await;
+    let result: UploadBlobsApiResponse = result.into();
+    Ok(HttpResponse::Ok().json(result))
 }
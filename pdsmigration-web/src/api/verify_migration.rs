@@ -0,0 +1,114 @@
+use crate::config::AppConfig;
+use crate::errors::{ApiError, ApiErrorBody};
+use crate::post;
+use actix_web::web::{Data, Json};
+use actix_web::HttpResponse;
+use pdsmigration_common::{VerificationReport, VerificationResult, VerifyMigrationRequest};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use utoipa::ToSchema;
+
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct VerifyMigrationApiRequest {
+    #[schema(example = "https://sourcePDS.example.com")]
+    pub origin: String,
+    #[schema(example = "https://destinationPDS.example.com")]
+    pub destination: String,
+    #[schema(example = "did:plc:abcd1234efgh5678ijkl")]
+    pub did: String,
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
+    pub origin_token: String,
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
+    pub destination_token: String,
+}
+
+impl fmt::Debug for VerifyMigrationApiRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VerifyMigrationApiRequest")
+            .field("origin", &self.origin)
+            .field("destination", &self.destination)
+            .field("did", &self.did)
+            .field("origin_token", &"[REDACTED]")
+            .field("destination_token", &"[REDACTED]")
+            .finish()
+    }
+}
+
+impl From<VerifyMigrationApiRequest> for VerifyMigrationRequest {
+    fn from(req: VerifyMigrationApiRequest) -> Self {
+        Self {
+            origin: req.origin,
+            destination: req.destination,
+            did: req.did,
+            origin_token: req.origin_token,
+            destination_token: req.destination_token,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct VerificationApiResult {
+    #[schema(example = "blobs")]
+    pub check: String,
+    #[schema(example = false)]
+    pub passed: bool,
+    #[schema(example = "41 of 42 blobs imported; the origin references 42")]
+    pub message: String,
+}
+
+impl From<VerificationResult> for VerificationApiResult {
+    fn from(result: VerificationResult) -> Self {
+        Self {
+            check: serde_json::to_value(result.check)
+                .ok()
+                .and_then(|check| check.as_str().map(str::to_string))
+                .unwrap_or_default(),
+            passed: result.passed,
+            message: result.message,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct VerifyMigrationApiResponse {
+    #[schema(example = false)]
+    pub passed: bool,
+    pub results: Vec<VerificationApiResult>,
+}
+
+impl From<VerificationReport> for VerifyMigrationApiResponse {
+    fn from(report: VerificationReport) -> Self {
+        Self {
+            passed: report.passed(),
+            results: report.results.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/verify-migration",
+    request_body = VerifyMigrationApiRequest,
+    responses(
+        (status = 200, description = "Comparison of origin and destination; failed checks are returned here, not as errors", body = VerifyMigrationApiResponse, content_type = "application/json"),
+        (status = 400, description = "Invalid request", body = ApiErrorBody, content_type = "application/json"),
+        (status = 401, description = "Authentication error", body = ApiErrorBody, content_type = "application/json"),
+        (status = 429, description = "Rate limit exceeded", body = ApiErrorBody, content_type = "application/json"),
+    ),
+    tag = "pdsmigration-web"
+)]
+#[tracing::instrument(skip(req, config), fields(did = %req.did))]
+#[post("/verify-migration")]
+pub async fn verify_migration_api(
+    req: Json<VerifyMigrationApiRequest>,
+    config: Data<AppConfig>,
+) -> Result<HttpResponse, ApiError> {
+    tracing::info!("Verify migration request received");
+    let report = pdsmigration_common::verify_migration_api(
+        req.into_inner().into(),
+        &config.server.identity_resolver(),
+        &config.server.migration_options(),
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(VerifyMigrationApiResponse::from(report)))
+}
@@ -1,19 +1,16 @@
+use crate::config::remove_staging;
 use crate::errors::ApiError;
-use futures_util::StreamExt;
 use pdsmigration_common::{
-    build_agent, download_blob, login_helper, missing_blobs, ExportBlobsRequest, GetBlobRequest,
-    MigrationError,
+    export_blobs_api, CancellationToken, ExportBlobsRequest, LocalStagingStore, MigrationError,
+    MigrationOptions, ProgressEvent, RefreshedSession, StagingStore,
 };
 use serde::{Deserialize, Serialize};
 #[allow(unused_imports)] // Used in schema attribute macros
 use serde_json::json;
-use std::collections::HashMap;
-use std::io::ErrorKind;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::io::AsyncWriteExt;
-use tokio::sync::RwLock;
-use tokio::task::JoinHandle;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc, RwLock};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
@@ -50,8 +47,69 @@ pub struct JobProgress {
     pub invalid_blobs: u64,
     #[schema(example = json!(["550e8400-e29b-41d4-a716-446655440001"]))]
     pub invalid_blob_ids: Vec<String>,
+    #[schema(example = 0)]
+    pub corrupt_blobs: u64,
+    #[schema(example = json!([]))]
+    pub corrupt_blob_ids: Vec<String>,
     #[schema(example = 2)]
     pub total: Option<u64>,
+    #[serde(default)]
+    #[schema(example = 1048576)]
+    pub bytes_transferred: u64,
+}
+
+impl JobProgress {
+    pub fn record(&mut self, event: ProgressEvent) {
+        match event {
+            ProgressEvent::Total { items } => self.total = Some(items),
+            ProgressEvent::ItemSucceeded { id } => {
+                self.successful_blobs += 1;
+                self.successful_blobs_ids.push(id);
+            }
+            ProgressEvent::ItemSkipped { .. } | ProgressEvent::ItemCanceled { .. } => {}
+            ProgressEvent::ItemCorrupt { id } => {
+                self.corrupt_blobs += 1;
+                self.corrupt_blob_ids.push(id);
+            }
+            ProgressEvent::ItemFailed { id, .. } => {
+                self.invalid_blobs += 1;
+                self.invalid_blob_ids.push(id);
+            }
+            ProgressEvent::BytesTransferred { bytes } => self.bytes_transferred += bytes,
+        }
+    }
+}
+
+/// Tokens a job was issued when it refreshed its session, so the caller can keep using
+/// the account after the job finishes.
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct JobSession {
+    #[schema(example = "https://sourcePDS.example.com")]
+    pub pds_host: String,
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
+    pub access_token: String,
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature")]
+    pub refresh_token: String,
+}
+
+impl std::fmt::Debug for JobSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JobSession")
+            .field("pds_host", &self.pds_host)
+            .field("access_token", &"[REDACTED]")
+            .field("refresh_token", &"[REDACTED]")
+            .finish()
+    }
+}
+
+impl From<&RefreshedSession> for JobSession {
+    fn from(session: &RefreshedSession) -> Self {
+        Self {
+            pds_host: session.pds_host.clone(),
+            access_token: session.access_token.clone(),
+            refresh_token: session.refresh_token.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -78,14 +136,21 @@ pub struct JobRecord {
             "successful_blobs_ids": ["550e8400-e29b-41d4-a716-446655440000"],
             "invalid_blobs": 1,
             "invalid_blob_ids": ["550e8400-e29b-41d4-a716-446655440001"],
-            "total": 100
+            "corrupt_blobs": 0,
+            "corrupt_blob_ids": [],
+            "total": 100,
+            "bytes_transferred": 1048576
         }))]
     pub progress: Option<JobProgress>,
+    /// Latest tokens for the job's origin session, once it has been refreshed. Returned
+    /// by the first fetch of the job by id after each refresh, and then dropped.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<JobSession>,
 }
 
 #[derive(Debug)]
 struct RunningJob {
-    handle: JoinHandle<()>,
+    cancel: CancellationToken,
 }
 
 #[derive(Clone)]
@@ -108,31 +173,67 @@ impl JobManager {
 
     pub async fn list(&self) -> Vec<JobRecord> {
         let st = self.state.read().await;
-        st.records.values().cloned().collect()
+        st.records
+            .values()
+            .cloned()
+            .map(|record| JobRecord {
+                session: None,
+                ..record
+            })
+            .collect()
     }
 
+    /// Returns the job, handing over any refreshed session tokens it holds so they can be
+    /// read only once.
     pub async fn get(&self, id: Uuid) -> Option<JobRecord> {
+        let mut st = self.state.write().await;
+        let record = st.records.get_mut(&id)?;
+        let session = record.session.take();
+        Some(JobRecord {
+            session,
+            ..record.clone()
+        })
+    }
+
+    /// Ids of the jobs still running, whose staging must be left alone.
+    pub async fn running_ids(&self) -> HashSet<Uuid> {
         let st = self.state.read().await;
-        st.records.get(&id).cloned()
+        st.running.keys().copied().collect()
     }
 
+    /// Asks a running job to stop at its next safe point. The job keeps its status until it
+    /// has cleaned up, then moves to [`JobStatus::Canceled`].
     pub async fn cancel(&self, id: Uuid) -> bool {
-        let mut st = self.state.write().await;
-        if let Some(running) = st.running.remove(&id) {
-            running.handle.abort();
-            if let Some(rec) = st.records.get_mut(&id) {
-                rec.status = JobStatus::Canceled;
-                rec.finished_at = Some(now_millis());
-            }
+        let st = self.state.read().await;
+        if let Some(running) = st.running.get(&id) {
+            running.cancel.cancel();
             true
         } else {
             false
         }
     }
 
-    #[tracing::instrument(skip(self))]
-    pub async fn spawn_export_blobs(&self, request: ExportBlobsRequest) -> Result<Uuid, ApiError> {
-        let id = Uuid::new_v4();
+    /// Starts exporting blobs as job `id`; the id doubles as the staging id of `store`. A job
+    /// that fails or is canceled removes its staging, since nothing will upload from it.
+    #[tracing::instrument(skip(self, store, options))]
+    pub async fn spawn_export_blobs(
+        &self,
+        id: Uuid,
+        request: ExportBlobsRequest,
+        store: Arc<LocalStagingStore>,
+        mut options: MigrationOptions,
+    ) -> Result<Uuid, ApiError> {
+        let session_state = self.state.clone();
+        options.on_session_refresh = Some(Arc::new(move |session: &RefreshedSession| {
+            let state = session_state.clone();
+            let session = JobSession::from(session);
+            tokio::spawn(async move {
+                let mut st = state.write().await;
+                if let Some(r) = st.records.get_mut(&id) {
+                    r.session = Some(session);
+                }
+            });
+        }));
         let rec = JobRecord {
             id: id.to_string(),
             kind: JobKind::ExportBlobs,
@@ -146,17 +247,27 @@ impl JobManager {
                 successful_blobs_ids: vec![],
                 invalid_blobs: 0,
                 invalid_blob_ids: vec![],
+                corrupt_blobs: 0,
+                corrupt_blob_ids: vec![],
                 total: None,
+                bytes_transferred: 0,
             }),
+            session: None,
         };
 
         {
             let mut st = self.state.write().await;
             st.records.insert(id, rec);
+            st.running.insert(
+                id,
+                RunningJob {
+                    cancel: options.cancel.clone(),
+                },
+            );
         }
 
         let state = self.state.clone();
-        let handle = tokio::spawn(async move {
+        tokio::spawn(async move {
             {
                 let mut st = state.write().await;
                 if let Some(r) = st.records.get_mut(&id) {
@@ -165,7 +276,11 @@ impl JobManager {
                 }
             }
 
-            let result = export_blobs_api_job(id, state.clone(), request).await;
+            let result =
+                export_blobs_api_job(id, state.clone(), request, store.as_ref(), options).await;
+            if result.is_err() {
+                remove_staging(&store).await;
+            }
 
             match result {
                 Ok(_) => {
@@ -176,6 +291,14 @@ impl JobManager {
                     }
                     st.running.remove(&id);
                 }
+                Err(MigrationError::Canceled { .. }) => {
+                    let mut st = state.write().await;
+                    if let Some(r) = st.records.get_mut(&id) {
+                        r.status = JobStatus::Canceled;
+                        r.finished_at = Some(now_millis());
+                    }
+                    st.running.remove(&id);
+                }
                 Err(e) => {
                     let mut st = state.write().await;
                     if let Some(r) = st.records.get_mut(&id) {
@@ -188,11 +311,6 @@ impl JobManager {
             }
         });
 
-        {
-            let mut st = self.state.write().await;
-            st.running.insert(id, RunningJob { handle });
-        }
-
         Ok(id)
     }
 }
@@ -203,155 +321,28 @@ impl Default for JobManager {
     }
 }
 
-#[tracing::instrument(skip(state))]
+#[tracing::instrument(skip(state, store, options))]
 async fn export_blobs_api_job(
     id: Uuid,
     state: Arc<RwLock<JobState>>,
     req: ExportBlobsRequest,
+    store: &dyn StagingStore,
+    mut options: MigrationOptions,
 ) -> Result<(), MigrationError> {
-    let agent = build_agent().await?;
-    login_helper(
-        &agent,
-        req.destination.as_str(),
-        req.did.as_str(),
-        req.destination_token.as_str(),
-    )
-    .await?;
-    let missing_blobs = missing_blobs(&agent).await?;
-    {
-        let mut st = state.write().await;
-        if let Some(r) = st.records.get_mut(&id) {
-            if let Some(progress) = r.progress.as_mut() {
-                progress.total = Some(missing_blobs.len() as u64);
+    // Events arrive from blobs in flight, so they are queued and applied to the record in order
+    let (sender, mut events) = mpsc::unbounded_channel();
+    options.progress = Some(Arc::new(move |event: ProgressEvent| {
+        let _ = sender.send(event);
+    }));
+    let export = async move { export_blobs_api(req, store, &options).await };
+    let record = async {
+        while let Some(event) = events.recv().await {
+            let mut st = state.write().await;
+            if let Some(progress) = st.records.get_mut(&id).and_then(|r| r.progress.as_mut()) {
+                progress.record(event);
             }
         }
-    }
-    let session = login_helper(
-        &agent,
-        req.origin.as_str(),
-        req.did.as_str(),
-        req.origin_token.as_str(),
-    )
-    .await?;
-
-    // Initialize collections to track successful and failed blob IDs
-    let mut successful_blobs = Vec::new();
-    let mut invalid_blobs = Vec::new();
-    let mut path = match std::env::current_dir() {
-        Ok(path) => path,
-        Err(e) => {
-            return Err(MigrationError::Runtime {
-                message: e.to_string(),
-            })
-        }
     };
-    path.push(session.did.as_str().replace(":", "-"));
-    match tokio::fs::create_dir(path.as_path()).await {
-        Ok(_) => {
-            tracing::info!("Successfully created directory");
-        }
-        Err(e) => {
-            if e.kind() != ErrorKind::AlreadyExists {
-                return Err(MigrationError::Runtime {
-                    message: format!("{}", e),
-                });
-            }
-        }
-    }
-    for missing_blob in &missing_blobs {
-        tracing::debug!("Missing blob: {:?}", missing_blob);
-        let session = match agent.get_session().await {
-            Some(session) => session,
-            None => {
-                return Err(MigrationError::Runtime {
-                    message: "Failed to get session".to_string(),
-                });
-            }
-        };
-        let mut filepath = match std::env::current_dir() {
-            Ok(res) => res,
-            Err(e) => {
-                return Err(MigrationError::Runtime {
-                    message: e.to_string(),
-                });
-            }
-        };
-        filepath.push(session.did.as_str().replace(":", "-"));
-        filepath.push(
-            missing_blob
-                .record_uri
-                .as_str()
-                .split("/")
-                .last()
-                .unwrap_or("fallback"),
-        );
-        if !tokio::fs::try_exists(filepath).await.unwrap() {
-            let missing_blob_cid = missing_blob.cid.clone();
-            let blob_cid_str = format!("{missing_blob_cid:?}")
-                .strip_prefix("Cid(Cid(")
-                .unwrap()
-                .strip_suffix("))")
-                .unwrap()
-                .to_string();
-            let get_blob_request = GetBlobRequest {
-                did: session.did.clone(),
-                cid: blob_cid_str.clone(),
-                token: session.access_jwt.clone(),
-            };
-            match download_blob(agent.get_endpoint().await.as_str(), &get_blob_request).await {
-                Ok(mut stream) => {
-                    tracing::info!("Successfully fetched missing blob");
-                    let mut path = std::env::current_dir().unwrap();
-                    path.push(session.did.as_str().replace(":", "-"));
-                    path.push(&blob_cid_str);
-                    let mut file = tokio::fs::File::create(path.as_path()).await.unwrap();
-
-                    while let Some(chunk) = stream.next().await {
-                        let chunk = chunk.unwrap();
-                        file.write_all(&chunk).await.unwrap();
-                    }
-
-                    file.flush().await.unwrap();
-                    successful_blobs.push(blob_cid_str.clone());
-
-                    {
-                        let mut st = state.write().await;
-                        if let Some(r) = st.records.get_mut(&id) {
-                            if let Some(progress) = r.progress.as_mut() {
-                                progress.successful_blobs += 1;
-                                progress.successful_blobs_ids.push(blob_cid_str.clone());
-                            }
-                        }
-                    }
-                }
-                Err(e) => {
-                    match e {
-                        MigrationError::RateLimitReached => {
-                            tracing::error!("Rate limit reached, waiting 5 minutes");
-                            let five_minutes = Duration::from_secs(300);
-                            tokio::time::sleep(five_minutes).await;
-                        }
-                        _ => {
-                            tracing::error!(
-                                "Unexpected error when downloading blob: {}",
-                                e.to_string()
-                            );
-                        }
-                    }
-                    tracing::error!("Failed to download missing blob with cid: {}", blob_cid_str);
-                    invalid_blobs.push(blob_cid_str.clone());
-                    {
-                        let mut st = state.write().await;
-                        if let Some(r) = st.records.get_mut(&id) {
-                            if let Some(progress) = r.progress.as_mut() {
-                                progress.invalid_blobs += 1;
-                                progress.invalid_blob_ids.push(blob_cid_str.clone());
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-    Ok(())
+    let (result, ()) = tokio::join!(export, record);
+    result.map(|_| ())
 }
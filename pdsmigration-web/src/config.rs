@@ -1,5 +1,11 @@
+use pdsmigration_common::{IdentityResolver, LocalStagingStore, MigrationOptions, RetryPolicy};
 use serde::Deserialize;
+use std::collections::HashSet;
 use std::env;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::Duration;
+use uuid::Uuid;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct AppConfig {
@@ -14,6 +20,11 @@ pub struct ServerConfig {
     pub rate_limit_window_secs: u64,
     pub rate_limit_max_requests: u64,
     pub auth_token: Option<String>,
+    pub staging_dir: PathBuf,
+    /// How long staged blobs wait for an upload before the sweep removes them.
+    pub staging_ttl_secs: u64,
+    pub blob_concurrency: usize,
+    pub retry_max_attempts: u32,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -21,6 +32,86 @@ pub struct ExternalServices {
     pub s3_endpoint: String,
 }
 
+impl ServerConfig {
+    pub fn migration_options(&self) -> MigrationOptions {
+        MigrationOptions {
+            blob_concurrency: self.blob_concurrency,
+            retry: RetryPolicy {
+                max_attempts: self.retry_max_attempts,
+                ..RetryPolicy::default()
+            },
+            ..MigrationOptions::default()
+        }
+    }
+
+    /// Resolves handles and DIDs with the same retries as the migration calls.
+    pub fn identity_resolver(&self) -> IdentityResolver {
+        IdentityResolver {
+            retry: self.migration_options().retry,
+            ..IdentityResolver::default()
+        }
+    }
+
+    /// Staging for a single migration, so two migrations of the same DID never share blobs.
+    pub fn staging_store(&self, staging_id: &Uuid) -> LocalStagingStore {
+        LocalStagingStore::new(self.staging_dir.join(staging_id.to_string()))
+    }
+
+    /// Removes staging that no upload picked up within `staging_ttl_secs`, skipping the ids in
+    /// `in_use`. Only directories named by a staging id are touched, since `staging_dir` may
+    /// hold other files. Returns how many were removed.
+    pub async fn sweep_staging(&self, in_use: &HashSet<Uuid>) -> usize {
+        let ttl = Duration::from_secs(self.staging_ttl_secs);
+        let mut entries = match tokio::fs::read_dir(&self.staging_dir).await {
+            Ok(entries) => entries,
+            Err(error) => {
+                tracing::warn!("Failed to read staging directory: {}", error);
+                return 0;
+            }
+        };
+        let mut removed = 0;
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let Some(staging_id) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| Uuid::parse_str(name).ok())
+            else {
+                continue;
+            };
+            if in_use.contains(&staging_id) {
+                continue;
+            }
+            let expired = entry
+                .metadata()
+                .await
+                .ok()
+                .filter(|metadata| metadata.is_dir())
+                .and_then(|metadata| metadata.modified().ok())
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age >= ttl);
+            if expired {
+                tracing::info!("Removing abandoned staging {}", staging_id);
+                remove_staging(&LocalStagingStore::new(entry.path())).await;
+                removed += 1;
+            }
+        }
+        removed
+    }
+}
+
+/// Removes everything staged in `store` once nothing will read it again.
+pub async fn remove_staging(store: &LocalStagingStore) {
+    match tokio::fs::remove_dir_all(store.root()).await {
+        Ok(()) => tracing::debug!("Removed staging {}", store.root().display()),
+        Err(error) if error.kind() == ErrorKind::NotFound => {}
+        Err(error) => tracing::warn!(
+            "Failed to remove staging {}: {}",
+            store.root().display(),
+            error
+        ),
+    }
+}
+
 impl AppConfig {
     pub fn from_env() -> Self {
         let server_port = env::var("SERVER_PORT").unwrap_or("9090".to_string());
@@ -29,6 +120,9 @@ impl AppConfig {
         let rate_limit_window_secs = env::var("RATE_LIMIT_WINDOW_SECS").unwrap_or("60".to_string());
         let rate_limit_max_requests =
             env::var("RATE_LIMIT_MAX_REQUESTS").unwrap_or("60".to_string());
+        let blob_concurrency = env::var("BLOB_CONCURRENCY").unwrap_or("4".to_string());
+        let retry_max_attempts = env::var("RETRY_MAX_ATTEMPTS").unwrap_or("4".to_string());
+        let staging_ttl_secs = env::var("STAGING_TTL_SECS").unwrap_or("86400".to_string());
 
         Self {
             server: ServerConfig {
@@ -37,6 +131,12 @@ impl AppConfig {
                 rate_limit_window_secs: rate_limit_window_secs.parse().unwrap(),
                 rate_limit_max_requests: rate_limit_max_requests.parse().unwrap(),
                 auth_token: env::var("AUTH_TOKEN").ok(),
+                staging_dir: env::var("STAGING_DIR")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|_| env::current_dir().unwrap_or_default()),
+                staging_ttl_secs: staging_ttl_secs.parse().unwrap(),
+                blob_concurrency: blob_concurrency.parse().unwrap(),
+                retry_max_attempts: retry_max_attempts.parse().unwrap(),
             },
             external_services: ExternalServices { s3_endpoint },
         }
@@ -18,6 +18,20 @@ pub enum ApiErrorCode {
     Authentication,
     #[display("RATE_LIMIT")]
     RateLimit,
+    #[display("AUTH_FACTOR_TOKEN_REQUIRED")]
+    AuthFactorTokenRequired,
+    #[display("HANDLE_UNAVAILABLE")]
+    HandleUnavailable,
+    #[display("INVALID_INVITE_CODE")]
+    InvalidInviteCode,
+    #[display("ACCOUNT_ALREADY_EXISTS")]
+    AccountAlreadyExists,
+    #[display("EXPIRED_TOKEN")]
+    ExpiredToken,
+    #[display("NOT_FOUND")]
+    NotFound,
+    #[display("VERIFICATION_FAILED")]
+    VerificationFailed,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -45,6 +59,27 @@ pub enum ApiError {
     #[display("Too many requests: {message}")]
     #[schema(title = "Rate limit")]
     RateLimit { message: String },
+    #[display("Auth factor token required")]
+    #[schema(title = "Auth factor token required")]
+    AuthFactorTokenRequired,
+    #[display("Handle is not available: {message}")]
+    #[schema(title = "Handle unavailable")]
+    HandleUnavailable { message: String },
+    #[display("Invite code was rejected: {message}")]
+    #[schema(title = "Invalid invite code")]
+    InvalidInviteCode { message: String },
+    #[display("Account already exists: {message}")]
+    #[schema(title = "Account already exists")]
+    AccountAlreadyExists { message: String },
+    #[display("Token has expired: {message}")]
+    #[schema(title = "Expired token")]
+    ExpiredToken { message: String },
+    #[display("Not found: {message}")]
+    #[schema(title = "Not found")]
+    NotFound { message: String },
+    #[display("Migration verification failed: {message}")]
+    #[schema(title = "Verification failed")]
+    VerificationFailed { message: String },
 }
 
 impl ResponseError for ApiError {
@@ -55,6 +90,13 @@ impl ResponseError for ApiError {
             ApiError::Runtime { .. } => StatusCode::INTERNAL_SERVER_ERROR,
             ApiError::Authentication { .. } => StatusCode::UNAUTHORIZED,
             ApiError::RateLimit { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::AuthFactorTokenRequired => StatusCode::UNAUTHORIZED,
+            ApiError::HandleUnavailable { .. } => StatusCode::CONFLICT,
+            ApiError::InvalidInviteCode { .. } => StatusCode::BAD_REQUEST,
+            ApiError::AccountAlreadyExists { .. } => StatusCode::CONFLICT,
+            ApiError::ExpiredToken { .. } => StatusCode::UNAUTHORIZED,
+            ApiError::NotFound { .. } => StatusCode::NOT_FOUND,
+            ApiError::VerificationFailed { .. } => StatusCode::CONFLICT,
         }
     }
 
@@ -67,6 +109,24 @@ impl ResponseError for ApiError {
                 (ApiErrorCode::Authentication, message.to_string())
             }
             ApiError::RateLimit { message } => (ApiErrorCode::RateLimit, message.to_string()),
+            ApiError::AuthFactorTokenRequired => (
+                ApiErrorCode::AuthFactorTokenRequired,
+                "A sign in code was sent to the account's email".to_string(),
+            ),
+            ApiError::HandleUnavailable { message } => {
+                (ApiErrorCode::HandleUnavailable, message.to_string())
+            }
+            ApiError::InvalidInviteCode { message } => {
+                (ApiErrorCode::InvalidInviteCode, message.to_string())
+            }
+            ApiError::AccountAlreadyExists { message } => {
+                (ApiErrorCode::AccountAlreadyExists, message.to_string())
+            }
+            ApiError::ExpiredToken { message } => (ApiErrorCode::ExpiredToken, message.to_string()),
+            ApiError::NotFound { message } => (ApiErrorCode::NotFound, message.to_string()),
+            ApiError::VerificationFailed { message } => {
+                (ApiErrorCode::VerificationFailed, message.to_string())
+            }
         };
 
         HttpResponse::build(self.status_code())
@@ -87,6 +147,37 @@ impl From<MigrationError> for ApiError {
             MigrationError::Authentication { .. } => ApiError::Authentication {
                 message: "Authentication failed".to_string(),
             },
+            MigrationError::InvalidCredentials => ApiError::Authentication {
+                message: "Invalid identifier or password".to_string(),
+            },
+            MigrationError::AccountTakedown { .. } => ApiError::Authentication {
+                message: "Account has been taken down".to_string(),
+            },
+            MigrationError::AuthFactorTokenRequired => ApiError::AuthFactorTokenRequired,
+            error @ MigrationError::Canceled { .. } => ApiError::Runtime {
+                message: error.to_string(),
+            },
+            MigrationError::HandleUnavailable { upstream } => ApiError::HandleUnavailable {
+                message: upstream.to_string(),
+            },
+            MigrationError::InvalidInviteCode { upstream } => ApiError::InvalidInviteCode {
+                message: upstream.to_string(),
+            },
+            MigrationError::AccountAlreadyExists { upstream } => ApiError::AccountAlreadyExists {
+                message: upstream.to_string(),
+            },
+            MigrationError::ExpiredToken { upstream } => ApiError::ExpiredToken {
+                message: upstream.to_string(),
+            },
+            MigrationError::NotFound { upstream } => ApiError::NotFound {
+                message: upstream.to_string(),
+            },
+            MigrationError::Xrpc { upstream } => ApiError::Upstream {
+                message: upstream.to_string(),
+            },
+            MigrationError::VerificationFailed { failed } => ApiError::VerificationFailed {
+                message: failed.join("; "),
+            },
         }
     }
 }
@@ -6,14 +6,15 @@ mod middleware;
 mod openapi;
 
 use crate::api::{
-    activate_account_api, cancel_job_api, create_account_api, deactivate_account_api,
-    enqueue_export_blobs_job_api, export_blobs_api, export_pds_api, get_job_api,
-    get_service_auth_api, health_check, import_pds_api, list_jobs_api, long_health_check,
-    migrate_plc_api, migrate_preferences_api, missing_blobs_api, request_token_api,
-    upload_blobs_api,
+    activate_account_api, cancel_job_api, catch_up_repo_api, create_account_api,
+    deactivate_account_api, enqueue_export_blobs_job_api, export_blobs_api, export_pds_api,
+    get_job_api, get_service_auth_api, health_check, import_pds_api, list_jobs_api, login_api,
+    long_health_check, migrate_plc_api, migrate_preferences_api, missing_blobs_api, preflight_api,
+    preview_plc_api, request_token_api, resolve_identity_api, transfer_blobs_api, upload_blobs_api,
+    verify_migration_api,
 };
 use crate::background_jobs::JobManager;
-use crate::config::AppConfig;
+use crate::config::{AppConfig, ServerConfig};
 use crate::middleware::rate_limit::RateLimiter;
 use crate::openapi::ApiDoc;
 use actix_web::dev::Server;
@@ -29,18 +30,19 @@ use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
 pub const APPLICATION_JSON: &str = "application/json";
+const STAGING_SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
 /*
  * Initialize the HTTP server
  *
  * @param app_config: The application configuration
+ * @param job_manager: The background jobs shared by every worker
  * @return: The initialized HTTP server
  * @throws: io::Error if the server fails to start
  */
-fn init_http_server(app_config: AppConfig) -> io::Result<Server> {
+fn init_http_server(app_config: AppConfig, job_manager: JobManager) -> io::Result<Server> {
     let server_port = app_config.server.port;
     let worker_count = app_config.server.workers;
-    let job_manager = JobManager::new();
     let prometheus = PrometheusMetricsBuilder::new("api")
         .endpoint("/metrics")
         .build()
@@ -63,6 +65,9 @@ fn init_http_server(app_config: AppConfig) -> io::Result<Server> {
                 let resp = api_err.error_response();
                 actix_web::error::InternalError::from_response(err, resp).into()
             }))
+            .service(login_api)
+            .service(resolve_identity_api)
+            .service(preflight_api)
             .service(request_token_api)
             .service(create_account_api)
             .service(export_pds_api)
@@ -70,13 +75,17 @@ fn init_http_server(app_config: AppConfig) -> io::Result<Server> {
             .service(missing_blobs_api)
             .service(export_blobs_api)
             .service(upload_blobs_api)
+            .service(transfer_blobs_api)
             .service(enqueue_export_blobs_job_api)
             .service(list_jobs_api)
             .service(get_job_api)
             .service(cancel_job_api)
             .service(activate_account_api)
+            .service(verify_migration_api)
             .service(deactivate_account_api)
             .service(migrate_preferences_api)
+            .service(catch_up_repo_api)
+            .service(preview_plc_api)
             .service(migrate_plc_api)
             .service(get_service_auth_api)
             .service(health_check)
@@ -93,6 +102,27 @@ fn init_http_server(app_config: AppConfig) -> io::Result<Server> {
     Ok(server)
 }
 
+/*
+ * Periodically remove staged blobs that were never uploaded
+ *
+ * @param server_config: The server configuration holding the staging directory and TTL
+ * @param job_manager: The background jobs, whose staging is kept while they run
+ */
+fn spawn_staging_sweep(server_config: ServerConfig, job_manager: JobManager) {
+    actix_rt::spawn(async move {
+        let mut interval = actix_rt::time::interval(STAGING_SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            let removed = server_config
+                .sweep_staging(&job_manager.running_ids().await)
+                .await;
+            if removed > 0 {
+                tracing::info!("Removed {} abandoned staging directories", removed);
+            }
+        }
+    });
+}
+
 #[actix_rt::main]
 async fn main() -> io::Result<()> {
     dotenv().ok();
@@ -116,7 +146,9 @@ async fn main() -> io::Result<()> {
     let app_config = AppConfig::from_env();
 
     // Start Http Server
-    let server = init_http_server(app_config.clone())?;
+    let job_manager = JobManager::new();
+    spawn_staging_sweep(app_config.server.clone(), job_manager.clone());
+    let server = init_http_server(app_config.clone(), job_manager)?;
     tracing::info!(
         "Server started successfully on 0.0.0.0:{}",
         app_config.server.port
@@ -139,13 +171,17 @@ mod tests {
                 rate_limit_window_secs: 60,
                 rate_limit_max_requests: 60,
                 auth_token: None,
+                staging_dir: std::env::temp_dir(),
+                staging_ttl_secs: 86400,
+                blob_concurrency: 4,
+                retry_max_attempts: 4,
             },
             external_services: ExternalServices {
                 s3_endpoint: "http://test-s3.example.com".to_string(),
             },
         };
 
-        let result = init_http_server(app_config);
+        let result = init_http_server(app_config, JobManager::new());
         assert!(result.is_ok(), "Expected successful server initialization");
     }
 
@@ -159,6 +195,10 @@ mod tests {
                 rate_limit_window_secs: 60,
                 rate_limit_max_requests: 60,
                 auth_token: None,
+                staging_dir: std::env::temp_dir(),
+                staging_ttl_secs: 86400,
+                blob_concurrency: 4,
+                retry_max_attempts: 4,
             },
             external_services: ExternalServices {
                 s3_endpoint: "http://test-s3.example.com".to_string(),
@@ -169,6 +209,9 @@ mod tests {
         let app = actix_web::test::init_service(
             App::new()
                 .app_data(web::Data::new(app_config.clone()))
+                .service(login_api)
+                .service(resolve_identity_api)
+                .service(preflight_api)
                 .service(request_token_api)
                 .service(create_account_api)
                 .service(export_pds_api)
@@ -176,9 +219,13 @@ mod tests {
                 .service(missing_blobs_api)
                 .service(export_blobs_api)
                 .service(upload_blobs_api)
+                .service(transfer_blobs_api)
                 .service(activate_account_api)
+                .service(verify_migration_api)
                 .service(deactivate_account_api)
                 .service(migrate_preferences_api)
+                .service(catch_up_repo_api)
+                .service(preview_plc_api)
                 .service(migrate_plc_api)
                 .service(get_service_auth_api)
                 .service(health_check),
@@ -10,14 +10,21 @@ use crate::api::*;
         health_check,
         activate_account_api,
         create_account_api,
+        verify_migration_api,
         deactivate_account_api,
         export_blobs_api,
         export_pds_api,
         import_pds_api,
         missing_blobs_api,
         request_token_api,
+        login_api,
+        resolve_identity_api,
+        preflight_api,
         upload_blobs_api,
+        transfer_blobs_api,
         migrate_preferences_api,
+        catch_up_repo_api,
+        preview_plc_api,
         migrate_plc_api,
         get_service_auth_api,
         enqueue_export_blobs_job_api,
@@ -29,13 +36,31 @@ use crate::api::*;
         schemas(
             ActivateAccountApiRequest,
             CreateAccountApiRequest,
+            VerifyMigrationApiRequest,
+            VerificationApiResult,
+            VerifyMigrationApiResponse,
             DeactivateAccountApiRequest,
             ExportPDSApiRequest,
             ImportPDSApiRequest,
             MissingBlobsApiRequest,
             RequestTokenApiRequest,
+            LoginApiRequest,
+            LoginApiResponse,
+            ResolveIdentityApiRequest,
+            ResolveIdentityApiResponse,
+            PreflightApiRequest,
+            PreflightApiIssue,
+            PreflightApiResponse,
             UploadBlobsApiRequest,
+            UploadBlobsApiResponse,
+            TransferBlobsApiRequest,
+            TransferBlobsApiResponse,
             MigratePreferencesApiRequest,
+            CatchUpRepoApiRequest,
+            CatchUpRepoApiResponse,
+            PreviewPlcApiRequest,
+            PlcFieldApiChange,
+            PreviewPlcApiResponse,
             MigratePlcApiRequest,
             ServiceAuthApiRequest,
             // Jobs
@@ -43,6 +68,7 @@ use crate::api::*;
             crate::background_jobs::JobStatus,
             crate::background_jobs::JobProgress,
             crate::background_jobs::JobRecord,
+            crate::background_jobs::JobSession,
             crate::api::EnqueueJobResponse,
             crate::api::CancelJobResponse,
             ApiError,
@@ -1,15 +1,22 @@
+// The existing tests pass `&json!(..)` to `set_json`, which takes any `Serialize`
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use actix_web::{http::StatusCode, test, web, App};
 use pdsmigration_web::{
     api::{
-        activate_account_api, cancel_job_api, create_account_api, deactivate_account_api,
-        enqueue_export_blobs_job_api, export_blobs_api, export_pds_api, get_job_api,
-        get_service_auth_api, health_check, import_pds_api, list_jobs_api, migrate_plc_api,
-        migrate_preferences_api, missing_blobs_api, request_token_api, upload_blobs_api,
+        activate_account_api, cancel_job_api, catch_up_repo_api, create_account_api,
+        deactivate_account_api, enqueue_export_blobs_job_api, export_blobs_api, export_pds_api,
+        get_job_api, get_service_auth_api, health_check, import_pds_api, list_jobs_api, login_api,
+        migrate_plc_api, migrate_preferences_api, missing_blobs_api, preflight_api,
+        preview_plc_api, request_token_api, resolve_identity_api, transfer_blobs_api,
+        upload_blobs_api, verify_migration_api,
     },
     background_jobs::JobManager,
     config::{AppConfig, ExternalServices, ServerConfig},
 };
 use serde_json::json;
+use std::collections::HashSet;
+use uuid::Uuid;
 
 #[cfg(test)]
 mod integration_tests {
@@ -23,6 +30,10 @@ mod integration_tests {
                 rate_limit_window_secs: 60,
                 rate_limit_max_requests: 60,
                 auth_token: None,
+                staging_dir: std::env::temp_dir(),
+                staging_ttl_secs: 86400,
+                blob_concurrency: 4,
+                retry_max_attempts: 4,
             },
             external_services: ExternalServices {
                 s3_endpoint: "http://test-s3.example.com".to_string(),
@@ -59,6 +70,8 @@ mod integration_tests {
             App::new()
                 .app_data(web::Data::new(app_config))
                 .service(health_check)
+                .service(resolve_identity_api)
+                .service(preflight_api)
                 .service(request_token_api)
                 .service(create_account_api)
                 .service(export_pds_api)
@@ -66,9 +79,13 @@ mod integration_tests {
                 .service(missing_blobs_api)
                 .service(export_blobs_api)
                 .service(upload_blobs_api)
+                .service(transfer_blobs_api)
                 .service(activate_account_api)
+                .service(verify_migration_api)
                 .service(deactivate_account_api)
                 .service(migrate_preferences_api)
+                .service(catch_up_repo_api)
+                .service(preview_plc_api)
                 .service(migrate_plc_api)
                 .service(get_service_auth_api),
         )
@@ -115,6 +132,126 @@ mod integration_tests {
         assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
     }
 
+    #[actix_rt::test]
+    async fn test_login_missing_fields() {
+        let app_config = create_test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_config))
+                .service(login_api),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/login")
+            .set_json(json!({ "pds_host": "https://pds.example.com" }))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn test_catch_up_repo_missing_fields() {
+        let app_config = create_test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_config))
+                .service(catch_up_repo_api),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/catch-up-repo")
+            .set_json(json!({ "did": "did:plc:abcd1234efgh5678ijkl" }))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn test_resolve_identity_missing_fields() {
+        let app_config = create_test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_config))
+                .service(resolve_identity_api),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/resolve-identity")
+            .set_json(json!({}))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn test_preflight_missing_fields() {
+        let app_config = create_test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_config))
+                .service(preflight_api),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/preflight")
+            .set_json(json!({ "did": "did:plc:abcd1234efgh5678ijkl" }))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn test_verify_migration_missing_fields() {
+        let app_config = create_test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_config))
+                .service(verify_migration_api),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/verify-migration")
+            .set_json(json!({ "did": "did:plc:abcd1234efgh5678ijkl" }))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn test_preview_plc_missing_fields() {
+        let app_config = create_test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_config))
+                .service(preview_plc_api),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/preview-plc")
+            .set_json(json!({ "did": "did:plc:abcd1234efgh5678ijkl" }))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
     #[actix_rt::test]
     async fn test_export_pds_missing_fields() {
         let app_config = create_test_config();
@@ -215,6 +352,26 @@ mod integration_tests {
         assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
     }
 
+    #[actix_rt::test]
+    async fn test_transfer_blobs_missing_fields() {
+        let app_config = create_test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_config))
+                .service(transfer_blobs_api),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/transfer-blobs")
+            .set_json(json!({}))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
     #[actix_rt::test]
     async fn test_activate_account_missing_fields() {
         let app_config = create_test_config();
@@ -666,4 +823,122 @@ mod integration_tests {
         let cancel_response: serde_json::Value = serde_json::from_slice(&cancel_body).unwrap();
         assert_eq!(cancel_response["success"], true);
     }
+
+    fn create_staging_dir(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("pdsmigration-web-{}-{}", name, Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[actix_rt::test]
+    async fn test_upload_blobs_removes_staging() {
+        let mut pds = mockito::Server::new_async().await;
+        pds.mock("GET", "/xrpc/com.atproto.server.getSession")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                json!({ "did": "did:plc:test123456789", "handle": "alice.example.com" })
+                    .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let mut app_config = create_test_config();
+        app_config.server.staging_dir = create_staging_dir("upload");
+        let staging_id = Uuid::new_v4();
+        let staging = app_config.server.staging_dir.join(staging_id.to_string());
+        std::fs::create_dir_all(&staging).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_config.clone()))
+                .service(upload_blobs_api),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/upload-blobs")
+            .set_json(json!({
+                "pds_host": pds.url(),
+                "did": "did:plc:test123456789",
+                "token": "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature",
+                "staging_id": staging_id.to_string()
+            }))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(!staging.exists());
+        std::fs::remove_dir_all(&app_config.server.staging_dir).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_upload_blobs_reports_rate_limit() {
+        let mut pds = mockito::Server::new_async().await;
+        // A window far enough out that the request is not waited out and retried
+        pds.mock("GET", "/xrpc/com.atproto.server.getSession")
+            .with_status(429)
+            .with_header("retry-after", "3600")
+            .with_header("content-type", "application/json")
+            .with_body(json!({ "error": "RateLimitExceeded" }).to_string())
+            .create_async()
+            .await;
+
+        let mut app_config = create_test_config();
+        app_config.server.staging_dir = create_staging_dir("rate-limit");
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_config.clone()))
+                .service(upload_blobs_api),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/upload-blobs")
+            .set_json(json!({
+                "pds_host": pds.url(),
+                "did": "did:plc:test123456789",
+                "token": "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.example.signature",
+                "staging_id": Uuid::new_v4().to_string()
+            }))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        std::fs::remove_dir_all(&app_config.server.staging_dir).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_sweep_staging_removes_abandoned_staging() {
+        let mut app_config = create_test_config();
+        app_config.server.staging_dir = create_staging_dir("sweep");
+        app_config.server.staging_ttl_secs = 0;
+        let abandoned = Uuid::new_v4();
+        let running = Uuid::new_v4();
+        for name in [
+            abandoned.to_string(),
+            running.to_string(),
+            "other".to_string(),
+        ] {
+            std::fs::create_dir_all(app_config.server.staging_dir.join(name)).unwrap();
+        }
+
+        let removed = app_config
+            .server
+            .sweep_staging(&HashSet::from([running]))
+            .await;
+
+        let staging_dir = &app_config.server.staging_dir;
+        assert_eq!(removed, 1);
+        assert!(!staging_dir.join(abandoned.to_string()).exists());
+        assert!(staging_dir.join(running.to_string()).exists());
+        assert!(staging_dir.join("other").exists());
+
+        // Fresh staging survives a sweep with a real TTL
+        app_config.server.staging_ttl_secs = 86400;
+        std::fs::create_dir_all(staging_dir.join(abandoned.to_string())).unwrap();
+        assert_eq!(app_config.server.sweep_staging(&HashSet::new()).await, 0);
+        std::fs::remove_dir_all(staging_dir).unwrap();
+    }
 }